@@ -1,7 +1,8 @@
 use std::time::Duration;
-use anyhow::{anyhow, bail};
+use anyhow::bail;
 use ed25519_dalek::VerifyingKey;
 use futures::StreamExt;
+use thiserror::Error;
 use tokio::net::{TcpStream, ToSocketAddrs};
 use tokio_util::codec::Framed;
 use tokio::io::AsyncWriteExt;
@@ -13,12 +14,26 @@ use crate::connection::Connection;
 
 pub type ServerKey = [u8; 32];
 
+/// Handshake failure of a peer that can't be the server, it doesn't hold the secret of the server key: a liteserver
+/// closes a connection for another key and an impostor can't encrypt the empty packet. IO errors, e.g. a reset
+/// connection, are returned as they are
+#[derive(Debug, Error)]
+pub enum HandshakeError {
+    #[error("missed empty packet")]
+    MissedEmptyPacket,
+}
+
 pub struct Client;
 
 impl Client {
     pub async fn connect<A: ToSocketAddrs>(addr: A, server_key: &ServerKey) -> anyhow::Result<Connection> {
-        let mut stream = TcpStream::connect(addr).await?;
+        let stream = TcpStream::connect(addr).await?;
+
+        Self::handshake(stream, server_key).await
+    }
 
+    /// Handshake over a connected `stream`, for sockets set up by the caller, e.g. bound to a local address
+    pub async fn handshake(mut stream: TcpStream, server_key: &ServerKey) -> anyhow::Result<Connection> {
         let aes_ctr = AesCtr::generate();
         let server_public_key = VerifyingKey::from_bytes(server_key)?;
         let server_key_id = Ed25519KeyId::from_public_key_bytes(server_key);
@@ -35,9 +50,17 @@ impl Client {
         let codec = PacketCodec::from_aes_ctr_as_client(aes_ctr);
         let mut framed = Framed::new(stream, codec);
 
-        let packet = timeout(Duration::from_secs(5), framed.next())
-            .await?
-            .ok_or(anyhow!("missed empty packet"))??;
+        let packet = match timeout(Duration::from_secs(5), framed.next()).await? {
+            Some(Ok(packet)) => packet,
+            // the connection broke, it says nothing about the peer
+            Some(Err(error)) if error.is::<std::io::Error>() => return Err(error),
+            Some(Err(error)) => {
+                tracing::warn!(error = ?error, "undecodable handshake packet");
+
+                return Err(HandshakeError::MissedEmptyPacket.into())
+            },
+            None => return Err(HandshakeError::MissedEmptyPacket.into())
+        };
 
         tracing::info!(packet = ?packet, "received packet");
         if packet.is_empty() {
@@ -6,12 +6,14 @@ use sha2::{Digest, Sha256};
 use tokio_util::bytes::{BufMut, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 use crate::aes_ctr::{Aes256Ctr128, AesCtr};
+use crate::connection::PacketTooLarge;
 use crate::packet::Packet;
 
 pub struct PacketCodec {
     cipher_recv: Aes256Ctr128,
     cipher_send: Aes256Ctr128,
-    next_len: Option<usize>
+    next_len: Option<usize>,
+    max_len: Option<usize>
 }
 
 impl Encoder<Packet> for PacketCodec {
@@ -56,6 +58,9 @@ impl Decoder for PacketCodec {
         if length < 64 {
             bail!("small ADNL packet: {}", length);
         }
+        if let Some(max_length) = self.max_len.filter(|max_length| length > *max_length) {
+            return Err(PacketTooLarge { length, max_length }.into());
+        }
 
         if src.len() < length {
             src.reserve(length);
@@ -100,14 +105,19 @@ impl PacketCodec {
         let cipher_recv = Aes256Ctr128::new(GenericArray::from_slice(&bytes[0..32]), GenericArray::from_slice(&bytes[64 .. 80]));
         let cipher_send = Aes256Ctr128::new(GenericArray::from_slice(&bytes[32..64]), GenericArray::from_slice(&bytes[80 .. 96]));
 
-        Self { cipher_recv, cipher_send, next_len: None }
+        Self { cipher_recv, cipher_send, next_len: None, max_len: None }
     }
 
     fn from_bytes_as_server(bytes: &[u8; 160]) -> Self {
         let cipher_recv = Aes256Ctr128::new(GenericArray::from_slice(&bytes[32..64]), GenericArray::from_slice(&bytes[80 .. 96]));
         let cipher_send = Aes256Ctr128::new(GenericArray::from_slice(&bytes[0..32]), GenericArray::from_slice(&bytes[64 .. 80]));
 
-        Self { cipher_recv, cipher_send, next_len: None }
+        Self { cipher_recv, cipher_send, next_len: None, max_len: None }
+    }
+
+    /// Packets longer than `max_len` fail with [`PacketTooLarge`] as soon as their length prefix is read
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
     }
 }
 
@@ -118,6 +128,7 @@ mod tests {
     use tracing_test::traced_test;
     use anyhow::Result;
     use crate::codec::PacketCodec;
+    use crate::connection::PacketTooLarge;
     use crate::packet::Packet;
 
     #[test]
@@ -166,6 +177,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[traced_test]
+    fn decode_packet_over_max_len() -> Result<()> {
+        let mut codec = given_codec_client();
+        let mut buf = BytesMut::with_capacity(68);
+        // only the length prefix of an empty packet, the rest isn't needed to reject it
+        buf.put(&empty_packet_bytes()[.. 4]);
+        codec.set_max_len(Some(63));
+
+        let error = codec.decode(&mut buf).unwrap_err();
+
+        assert!(matches!(error.downcast_ref::<PacketTooLarge>(), Some(PacketTooLarge { length: 64, max_length: 63 })));
+
+        Ok(())
+    }
+
     fn empty_packet() -> Packet {
         Packet {
             nonce: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32],
@@ -2,11 +2,20 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 use futures::{Sink, Stream};
 use pin_project::pin_project;
+use thiserror::Error;
 use tokio::net::TcpStream;
 use tokio_util::codec::Framed;
 use crate::codec::PacketCodec;
 use crate::packet::Packet;
 
+/// Packet rejected by its length prefix, see [`Connection::set_max_len`]
+#[derive(Debug, Error)]
+#[error("ADNL packet of {length} bytes is longer than {max_length} bytes")]
+pub struct PacketTooLarge {
+    pub length: usize,
+    pub max_length: usize,
+}
+
 #[pin_project]
 pub struct Connection {
     #[pin]
@@ -29,6 +38,11 @@ impl Connection {
     pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut TcpStream> {
         self.project().inner.get_pin_mut()
     }
+
+    /// Fails the stream with [`PacketTooLarge`] on a packet longer than `max_len` before any of it is buffered
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.inner.codec_mut().set_max_len(max_len)
+    }
 }
 
 impl Sink<Packet> for Connection {
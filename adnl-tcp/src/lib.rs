@@ -9,5 +9,5 @@ pub mod deserializer;
 pub mod server;
 pub mod connection;
 mod codec;
-mod key;
+pub mod key;
 mod aes_ctr;
@@ -69,7 +69,6 @@ impl Serializer {
                 self.output.reserve(val.len() + 4);
                 self.output.put_u8(254);
                 self.output.put_slice(&(val.len() as u32).to_le_bytes()[..3]);
-                self.output.put_u8(val.len() as u8);
                 self.output.put_slice(val);
             }
         }
@@ -102,4 +101,16 @@ mod tests {
 
         assert_eq!(serializer.output, expected)
     }
+
+    #[test]
+    fn serialize_bytes_length256() {
+        let mut serializer = Serializer { output: Vec::new() };
+        let value = vec![1; 256];
+        let mut expected = vec![254, 0, 1, 0];
+        expected.append(&mut vec![1; 256]);
+
+        serializer.write_bytes(&value);
+
+        assert_eq!(serializer.output, expected)
+    }
 }
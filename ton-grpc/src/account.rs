@@ -1,21 +1,26 @@
 #![allow(clippy::blocks_in_conditions)]
 
+use std::future::{ready, Future};
 use std::pin::Pin;
 use std::str::FromStr;
+use std::time::Duration;
 use tonic::{async_trait, Request, Response, Status};
+use tonic::codec::CompressionEncoding::{Gzip, Zstd};
 use tonlibjson_client::ton::TonClient;
 use anyhow::Result;
 use futures::{Stream, StreamExt, try_join, TryStreamExt, TryFutureExt};
 use derive_new::new;
 use tonlibjson_client::address::AccountAddressData;
 use tonlibjson_client::block::{RawFullAccountState, TonBlockIdExt, TvmCell};
-use crate::helpers::{extend_block_id, extend_from_tx_id, extend_to_tx_id};
-use crate::ton::account_service_server::AccountService as BaseAccountService;
-use crate::ton::{GetAccountStateRequest, GetAccountStateResponse, GetAccountTransactionsRequest, GetShardAccountCellRequest, GetShardAccountCellResponse, Transaction};
+use crate::helpers::{extend_block_id, extend_from_tx_id, extend_to_tx_id, Compression};
+use crate::ton::account_service_server::{AccountService as BaseAccountService, AccountServiceServer};
+use crate::ton::{GetAccountStateRequest, GetAccountStateResponse, GetAccountTransactionsRequest, GetShardAccountCellRequest, GetShardAccountCellResponse, SubscribeAccountRequest, Transaction};
 use crate::ton::get_account_state_response::AccountState;
 use crate::ton::{get_account_state_request, get_shard_account_cell_request};
 use crate::ton::get_account_transactions_request::Order;
 
+const MASTERCHAIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(new)]
 pub struct AccountService {
     client: TonClient
@@ -34,19 +39,7 @@ impl BaseAccountService for AccountService {
             .map_err(|e| Status::internal(e.to_string()))
             .await?;
 
-        let block_id = state.block_id.clone();
-        let balance = state.balance.unwrap_or_default();
-        let last_transaction_id = state.last_transaction_id.clone().map(|t| (&address, t).into());
-        let state: AccountState = state.into();
-        let block_id = block_id.into();
-
-        Ok(Response::new(GetAccountStateResponse {
-            balance,
-            account_address: msg.account_address,
-            block_id: Some(block_id),
-            last_transaction_id,
-            account_state: Some(state)
-        }))
+        Ok(Response::new(account_state_response(msg.account_address, &address, state)))
     }
 
     #[tracing::instrument(skip_all, err)]
@@ -105,9 +98,107 @@ impl BaseAccountService for AccountService {
 
         Ok(Response::new(stream))
     }
+
+    type SubscribeAccountStream = Pin<Box<dyn Stream<Item=Result<GetAccountStateResponse, Status>> + Send + 'static>>;
+
+    #[tracing::instrument(skip_all, err)]
+    async fn subscribe_account(&self, request: Request<SubscribeAccountRequest>) -> std::result::Result<Response<Self::SubscribeAccountStream>, Status> {
+        let msg = request.into_inner();
+        let client = self.client.clone();
+
+        let address = AccountAddressData::from_str(&msg.account_address)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let account_address = msg.account_address.clone();
+        let blocks = client.get_masterchain_block_stream(MASTERCHAIN_POLL_INTERVAL);
+        let states = changed_states(blocks, move |block| {
+            let client = client.clone();
+            let account_address = account_address.clone();
+
+            async move { client.raw_get_account_state_at_least_block(&account_address, &block).await }
+        }, |state: &RawFullAccountState| state.last_transaction_id.as_ref().map(|t| t.lt));
+
+        let stream = states
+            .map_ok(move |state| account_state_response(msg.account_address.clone(), &address, state))
+            .map_err(|e: anyhow::Error| {
+                tracing::error!(error = %e, "subscribe_account failed");
+                Status::internal(e.to_string())
+            })
+            .boxed();
+
+        Ok(Response::new(stream))
+    }
+}
+
+fn account_state_response(account_address: String, address: &AccountAddressData, state: RawFullAccountState) -> GetAccountStateResponse {
+    let block_id = state.block_id.clone();
+    let balance = state.balance.unwrap_or_default();
+    let last_transaction_id = state.last_transaction_id.clone().map(|t| (address, t).into());
+    let state: AccountState = state.into();
+    let block_id = block_id.into();
+
+    GetAccountStateResponse {
+        balance,
+        account_address,
+        block_id: Some(block_id),
+        last_transaction_id,
+        account_state: Some(state)
+    }
+}
+
+/// Fetches the state of an account after each of `blocks` and keeps the states whose `key` differs from the
+/// previous one, e.g. the lt of the last transaction, so the first state and then one state per change.
+/// A failed fetch is skipped, the state after the next block covers its change. An error of `blocks` is passed on,
+/// the block stream is over then
+fn changed_states<B, I, T, K, F, Fut>(blocks: B, mut fetch: F, key: impl Fn(&T) -> K) -> impl Stream<Item=Result<T>>
+    where B: Stream<Item=Result<I>>,
+          F: FnMut(I) -> Fut,
+          Fut: Future<Output=Result<T>>,
+          K: PartialEq {
+    let mut last = None;
+
+    blocks
+        .filter_map(move |block| {
+            let state = block.map(&mut fetch);
+
+            async move {
+                match state {
+                    Ok(state) => match state.await {
+                        Ok(state) => Some(Ok(state)),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "account state fetch failed, waiting for the next block");
+
+                            None
+                        }
+                    },
+                    Err(e) => Some(Err(e))
+                }
+            }
+        })
+        .try_filter(move |state| {
+            let key = key(state);
+            let changed = last.as_ref() != Some(&key);
+            if changed {
+                last = Some(key);
+            }
+
+            ready(changed)
+        })
 }
 
 impl AccountService {
+    /// Server of the service, account states are large so responses go out with `compression` to the clients accepting it
+    pub fn into_server(self, compression: Compression) -> AccountServiceServer<Self> {
+        let server = AccountServiceServer::new(self)
+            .accept_compressed(Gzip)
+            .accept_compressed(Zstd);
+
+        match compression.encoding() {
+            Some(encoding) => server.send_compressed(encoding),
+            None => server
+        }
+    }
+
     async fn fetch_account_state(&self, msg: &GetAccountStateRequest) -> Result<RawFullAccountState> {
         let state = match &msg.criteria {
             None => {
@@ -167,11 +258,13 @@ impl AccountService {
 
 #[cfg(test)]
 mod tests {
-    use futures::StreamExt;
+    use std::collections::HashMap;
+    use anyhow::anyhow;
+    use futures::{stream, StreamExt, TryStreamExt};
     use tonic::Request;
     use tonlibjson_client::ton::TonClientBuilder;
     use tracing_test::traced_test;
-    use crate::account::AccountService;
+    use crate::account::{changed_states, AccountService};
     use crate::ton::account_service_server::AccountService as BaseAccountService;
     use crate::ton::{get_account_transactions_request, GetAccountStateRequest, GetAccountTransactionsRequest, GetShardAccountCellRequest, PartialTransactionId};
     use crate::ton::get_account_transactions_request::bound;
@@ -244,4 +337,36 @@ mod tests {
         tracing::info!(resp = ?resp);
         assert!(resp.is_ok())
     }
+
+    #[tokio::test]
+    async fn changed_states_emits_once_per_change() {
+        // last transaction lt of the account after each masterchain block
+        let account: HashMap<i32, i64> = [(1, 10), (2, 10), (3, 11), (4, 11), (5, 11), (6, 15)].into();
+        let blocks = stream::iter((1..=6).map(Ok));
+
+        let states: Vec<_> = changed_states(blocks, |seqno| {
+            let lt = account[&seqno];
+
+            async move { Ok((seqno, lt)) }
+        }, |(_, lt): &(i32, i64)| *lt).try_collect().await.unwrap();
+
+        assert_eq!(states, vec![(1, 10), (3, 11), (6, 15)]);
+    }
+
+    #[tokio::test]
+    async fn changed_states_skips_failed_fetches() {
+        // the fetches after blocks 2 and 3 fail, the change of block 3 comes with block 4
+        let account: HashMap<i32, i64> = [(1, 10), (4, 11), (5, 11)].into();
+        let blocks = stream::iter((1..=5).map(Ok).chain([Err(anyhow!("block stream failed"))]));
+
+        let states: Vec<_> = changed_states(blocks, |seqno| {
+            let lt = account.get(&seqno).copied();
+
+            async move { lt.map(|lt| (seqno, lt)).ok_or_else(|| anyhow!("liteserver unavailable")) }
+        }, |(_, lt): &(i32, i64)| *lt).collect().await;
+
+        assert_eq!(states.len(), 3);
+        assert_eq!(states[.. 2].iter().map(|state| *state.as_ref().unwrap()).collect::<Vec<_>>(), vec![(1, 10), (4, 11)]);
+        assert_eq!(states[2].as_ref().unwrap_err().to_string(), "block stream failed");
+    }
 }
@@ -1,19 +1,42 @@
 #![allow(clippy::blocks_in_conditions)]
 
+use std::time::Duration;
 use anyhow::Context;
 use futures::stream::BoxStream;
 use futures::{StreamExt, TryStreamExt};
 use tonic::{async_trait, Request, Response, Status};
+use tonic::codec::CompressionEncoding::{Gzip, Zstd};
+use base64::Engine;
 use derive_new::new;
+use tower::ServiceExt;
+use ton_liteserver_client::lookup::MODE_SEQNO;
+use ton_liteserver_client::tl::{LiteServerGetBlock, LiteServerLookupBlock, TonNodeBlockId};
 use tonlibjson_client::ton::TonClient;
-use crate::helpers::extend_block_id;
-use crate::ton::block_service_server::BlockService as BaseBlockService;
-use crate::ton::{AccountAddress, BlockId, BlockIdExt, GetTransactionIdsRequest, GetLastBlockRequest, GetShardsResponse, TransactionId, GetTransactionsRequest, Transaction};
+use crate::helpers::{extend_block_id, liteserver_status, Compression, LazyLiteServerPool};
+use crate::ton::block_service_server::{BlockService as BaseBlockService, BlockServiceServer};
+use crate::ton::{AccountAddress, BlockId, BlockIdExt, GetTransactionIdsRequest, GetLastBlockRequest, GetShardsResponse, TransactionId, GetTransactionsRequest, SubscribeMasterchainBlocksRequest, Transaction, GetBlockBySeqnoRequest, GetBlockBySeqnoResponse};
 use crate::ton::get_transaction_ids_request::Order;
 
+const MASTERCHAIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(new)]
 pub struct BlockService {
-    client: TonClient
+    client: TonClient,
+    liteserver: LazyLiteServerPool
+}
+
+impl BlockService {
+    /// Server of the service, block data is large so responses go out with `compression` to the clients accepting it
+    pub fn into_server(self, compression: Compression) -> BlockServiceServer<Self> {
+        let server = BlockServiceServer::new(self)
+            .accept_compressed(Gzip)
+            .accept_compressed(Zstd);
+
+        match compression.encoding() {
+            Some(encoding) => server.send_compressed(encoding),
+            None => server
+        }
+    }
 }
 
 #[async_trait]
@@ -117,4 +140,213 @@ impl BaseBlockService for BlockService {
 
         Ok(Response::new(stream))
     }
+
+    type SubscribeMasterchainBlocksStream = BoxStream<'static, Result<BlockIdExt, Status>>;
+
+    async fn subscribe_masterchain_blocks(&self, _request: Request<SubscribeMasterchainBlocksRequest>) -> Result<Response<Self::SubscribeMasterchainBlocksStream>, Status> {
+        let stream = self.client.get_masterchain_block_stream(MASTERCHAIN_POLL_INTERVAL)
+            .map_ok(|block| block.into())
+            .map_err(|e| Status::internal(e.to_string()))
+            .boxed();
+
+        Ok(Response::new(stream))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_block_by_seqno(&self, request: Request<GetBlockBySeqnoRequest>) -> Result<Response<GetBlockBySeqnoResponse>, Status> {
+        let msg = request.into_inner();
+        let id = TonNodeBlockId { workchain: msg.workchain, shard: msg.shard, seqno: msg.seqno };
+
+        let liteserver = self.liteserver.get().await?;
+        let header = liteserver.clone()
+            .oneshot(LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None }).await
+            .map_err(liteserver_status)?;
+        let block = liteserver
+            .oneshot(LiteServerGetBlock { id: header.id }).await
+            .map_err(liteserver_status)?;
+
+        Ok(Response::new(GetBlockBySeqnoResponse {
+            block_id: Some(block.id.into()),
+            data: base64::engine::general_purpose::STANDARD.encode(block.data)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use adnl_tcp::deserializer::from_bytes_boxed;
+    use adnl_tcp::key::Ed25519Key;
+    use adnl_tcp::packet::Packet;
+    use adnl_tcp::ping::is_ping_packet;
+    use adnl_tcp::serializer::to_bytes_boxed;
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::codec::CompressionEncoding::Zstd;
+    use tonic::transport::Server;
+    use tonic::{Code, Request};
+    use ton_liteserver_client::pool::LiteServerPool;
+    use ton_liteserver_client::tl::{AdnlMessageAnswer, AdnlMessageQuery, LiteServerBlockData, LiteServerBlockHeader, LiteServerGetBlock, LiteServerLookupBlock, LiteServerQuery, TonNodeBlockIdExt};
+    use tonlibjson_client::ton::{default_ton_config_url, TonClientBuilder};
+    use tracing_test::traced_test;
+    use crate::block::BlockService;
+    use crate::helpers::{Compression, LazyLiteServerPool};
+    use crate::ton::block_service_client::BlockServiceClient;
+    use crate::ton::block_service_server::{BlockService as BaseBlockService, BlockServiceServer};
+    use crate::ton::{GetBlockBySeqnoRequest, SubscribeMasterchainBlocksRequest};
+
+    async fn block_service() -> BlockService {
+        let mut client = TonClientBuilder::default().await.unwrap();
+        client.ready().await.unwrap();
+        let liteserver = LazyLiteServerPool::new(default_ton_config_url());
+
+        BlockService::new(client, liteserver)
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn subscribe_masterchain_blocks() {
+        let service = block_service().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Server::builder()
+            .add_service(BlockServiceServer::new(service))
+            .serve_with_incoming(TcpListenerStream::new(listener)));
+
+        let mut grpc = BlockServiceClient::connect(format!("http://{}", addr)).await.unwrap();
+        let blocks: Vec<_> = grpc.subscribe_masterchain_blocks(SubscribeMasterchainBlocksRequest {}).await.unwrap()
+            .into_inner()
+            .take(2)
+            .collect().await;
+
+        let first = blocks[0].as_ref().unwrap();
+        let second = blocks[1].as_ref().unwrap();
+        assert_eq!(first.workchain, -1);
+        assert_eq!(second.seqno, first.seqno + 1);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn get_block_by_seqno() {
+        let service = block_service().await;
+
+        let response = service.get_block_by_seqno(Request::new(GetBlockBySeqnoRequest {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: 34000000
+        })).await.unwrap().into_inner();
+
+        let block_id = response.block_id.unwrap();
+        assert_eq!(block_id.seqno, 34000000);
+        assert_eq!(block_id.root_hash.len(), 44);
+        assert!(response.data.starts_with("te6ccg"));
+    }
+
+    /// Liteserver answering the lookup of any block and its data with `data`
+    async fn provided_liteserver(data: Vec<u8>) -> LiteServerPool {
+        let key = Ed25519Key::generate();
+        let server_key = key.public_key().to_bytes();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let SocketAddr::V4(addr) = listener.local_addr().unwrap() else { unreachable!() };
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok((_, mut connection)) = adnl_tcp::server::Server::handshake(stream, &key).await else { continue };
+                let data = data.clone();
+
+                tokio::spawn(async move {
+                    while let Some(Ok(packet)) = connection.next().await {
+                        if is_ping_packet(&packet) {
+                            continue
+                        }
+
+                        let query = from_bytes_boxed::<AdnlMessageQuery>(&packet.data).unwrap();
+                        let request = from_bytes_boxed::<LiteServerQuery>(&query.query).unwrap();
+                        let answer = match from_bytes_boxed::<LiteServerLookupBlock>(&request.data) {
+                            Ok(lookup) => {
+                                let id = TonNodeBlockIdExt { workchain: lookup.id.workchain, shard: lookup.id.shard, seqno: lookup.id.seqno, root_hash: [1; 32], file_hash: [2; 32] };
+
+                                to_bytes_boxed(&LiteServerBlockHeader { id, mode: lookup.mode, header_proof: vec![] })
+                            },
+                            Err(_) => {
+                                let request = from_bytes_boxed::<LiteServerGetBlock>(&request.data).unwrap();
+
+                                to_bytes_boxed(&LiteServerBlockData { id: request.id, data: data.clone() })
+                            }
+                        };
+                        let answer = AdnlMessageAnswer { query_id: query.query_id, answer };
+                        connection.send(Packet::new(to_bytes_boxed(&answer))).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        LiteServerPool::connect(&[(addr, server_key)]).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn get_block_by_seqno_compressed_offline() {
+        // the tonlib client isn't used by get_block_by_seqno, its config is never read
+        let client = TonClientBuilder::from_config_path("/nonexistent/ton-config.json".into()).await.unwrap();
+        let liteserver = provided_liteserver(b"block data ".repeat(4096)).await;
+        let service = BlockService::new(client, liteserver.into());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Server::builder()
+            .add_service(service.into_server(Compression::Zstd))
+            .serve_with_incoming(TcpListenerStream::new(listener)));
+        let request = GetBlockBySeqnoRequest { workchain: -1, shard: i64::MIN, seqno: 34000000 };
+
+        let plain = BlockServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+            .get_block_by_seqno(request.clone()).await.unwrap();
+        let compressed = BlockServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+            .accept_compressed(Zstd)
+            .get_block_by_seqno(request).await.unwrap();
+
+        assert_eq!(plain.metadata().get("grpc-encoding"), None);
+        assert_eq!(compressed.metadata().get("grpc-encoding").unwrap(), "zstd");
+        assert_eq!(compressed.into_inner(), plain.into_inner());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn get_block_by_seqno_compressed() {
+        let service = block_service().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Server::builder()
+            .add_service(BlockServiceServer::new(service).send_compressed(Zstd))
+            .serve_with_incoming(TcpListenerStream::new(listener)));
+        let request = GetBlockBySeqnoRequest { workchain: -1, shard: i64::MIN, seqno: 34000000 };
+
+        let plain = BlockServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+            .get_block_by_seqno(request.clone()).await.unwrap();
+        let compressed = BlockServiceClient::connect(format!("http://{}", addr)).await.unwrap()
+            .accept_compressed(Zstd)
+            .get_block_by_seqno(request).await.unwrap();
+
+        assert_eq!(plain.metadata().get("grpc-encoding"), None);
+        assert_eq!(compressed.metadata().get("grpc-encoding").unwrap(), "zstd");
+        assert_eq!(compressed.into_inner(), plain.into_inner());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn get_block_by_seqno_not_found() {
+        let service = block_service().await;
+
+        let response = service.get_block_by_seqno(Request::new(GetBlockBySeqnoRequest {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: i32::MAX
+        })).await;
+
+        assert_eq!(response.unwrap_err().code(), Code::NotFound);
+    }
 }
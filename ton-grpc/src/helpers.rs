@@ -1,9 +1,20 @@
 use std::ops::Bound;
 use std::ops::Bound::{Excluded, Included};
+use std::str::FromStr;
+use std::sync::Arc;
 use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use tokio::sync::OnceCell;
+use tonic::codec::CompressionEncoding;
+use tonic::Status;
+use ton_liteserver_client::client::Error as LiteServerClientError;
+use ton_liteserver_client::config::GlobalConfig;
+use ton_liteserver_client::pool::LiteServerPool;
 use tonlibjson_client::block;
 use tonlibjson_client::block::InternalTransactionId;
 use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::ton_config::load_ton_config;
+use url::Url;
 use crate::ton;
 use crate::ton::get_account_transactions_request::bound::Bound::{BlockId, TransactionId};
 use crate::ton::get_account_transactions_request::bound::Type;
@@ -23,6 +34,76 @@ pub async fn extend_block_id(client: &TonClient, block_id: &ton::BlockId) -> Res
     }
 }
 
+/// Encoding of the block and account state responses, sent only to clients accepting it. Compressed requests are accepted either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn encoding(self) -> Option<CompressionEncoding> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some(CompressionEncoding::Gzip),
+            Compression::Zstd => Some(CompressionEncoding::Zstd),
+        }
+    }
+}
+
+/// Connects directly to the liteservers of the config, for the calls tonlib doesn't expose
+pub async fn liteserver_pool(config_url: Url) -> Result<LiteServerPool> {
+    let config = load_ton_config(config_url).await?;
+    let config = GlobalConfig::from_str(&config.to_string())?;
+
+    LiteServerPool::from_config(&config).await
+}
+
+/// [`liteserver_pool`] connected on first use, the tonlib services start without a reachable liteserver.
+/// A failed connect answers `unavailable` and is tried again by the next call
+#[derive(Clone)]
+pub struct LazyLiteServerPool {
+    /// `None` for a pool connected up front
+    config_url: Option<Url>,
+    pool: Arc<OnceCell<LiteServerPool>>
+}
+
+impl LazyLiteServerPool {
+    pub fn new(config_url: Url) -> Self {
+        Self { config_url: Some(config_url), pool: Arc::default() }
+    }
+
+    pub async fn get(&self) -> std::result::Result<LiteServerPool, Status> {
+        self.pool
+            .get_or_try_init(|| async {
+                let config_url = self.config_url.clone().ok_or_else(|| anyhow!("no ton config to connect with"))?;
+
+                liteserver_pool(config_url).await
+            })
+            .await
+            .cloned()
+            .map_err(|e| Status::unavailable(e.to_string()))
+    }
+}
+
+impl From<LiteServerPool> for LazyLiteServerPool {
+    fn from(pool: LiteServerPool) -> Self {
+        Self { config_url: None, pool: Arc::new(OnceCell::new_with(Some(pool))) }
+    }
+}
+
+pub fn liteserver_status(error: LiteServerClientError) -> Status {
+    match error {
+        e @ LiteServerClientError::NotFound(_) => Status::not_found(e.to_string()),
+        e @ LiteServerClientError::RateLimited => Status::resource_exhausted(e.to_string()),
+        e @ LiteServerClientError::Unsupported(_) => Status::unimplemented(e.to_string()),
+        e @ LiteServerClientError::ServerTimeout(_) => Status::deadline_exceeded(e.to_string()),
+        e @ LiteServerClientError::Cancelled(_) => Status::cancelled(e.to_string()),
+        e => Status::internal(e.to_string())
+    }
+}
+
 #[tracing::instrument(skip_all, err)]
 pub async fn prev_block_id(client: &TonClient, block_id: &ton::BlockId) -> Result<block::TonBlockIdExt> {
     client.look_up_block_by_seqno(block_id.workchain, block_id.shard, block_id.seqno - 1).await
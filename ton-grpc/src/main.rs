@@ -8,7 +8,7 @@ use std::net::SocketAddr;
 use std::time::Duration;
 use metrics_exporter_prometheus::PrometheusBuilder;
 use tonic::transport::Server;
-use tonic::codec::CompressionEncoding::Gzip;
+use tonic::codec::CompressionEncoding::{Gzip, Zstd};
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tonlibjson_client::ton::TonClientBuilder;
@@ -16,6 +16,7 @@ use clap::Parser;
 use url::Url;
 use crate::account::AccountService;
 use crate::block::BlockService;
+use crate::helpers::{Compression, LazyLiteServerPool};
 use crate::message::MessageService;
 use crate::ton::account_service_server::AccountServiceServer;
 use crate::ton::block_service_server::BlockServiceServer;
@@ -39,6 +40,8 @@ struct Args {
     initial_connection_window_size: u32,
     #[clap(long, default_value = "65535")]
     initial_stream_window_size: u32,
+    #[clap(long, value_enum, default_value = "gzip")]
+    compression: Compression,
 
     #[clap(long)]
     enable_metrics: bool,
@@ -86,7 +89,7 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("TON Config URL: {}", &args.ton_config_url);
 
-    let mut client = TonClientBuilder::from_config_url(args.ton_config_url, Duration::from_secs(60)).set_timeout(args.ton_timeout)
+    let mut client = TonClientBuilder::from_config_url(args.ton_config_url.clone(), Duration::from_secs(60)).set_timeout(args.ton_timeout)
         .set_retry_budget_ttl(args.retry_budget_ttl)
         .set_retry_min_per_sec(args.retry_min_rps)
         .set_retry_percent(args.retry_withdraw_percent)
@@ -99,19 +102,33 @@ async fn main() -> anyhow::Result<()> {
     client.ready().await?;
     tracing::info!("Ton Client is ready");
 
+    // only the block data calls need the liteservers, the other services serve without them
+    let liteserver = LazyLiteServerPool::new(args.ton_config_url);
+    tokio::spawn({
+        let liteserver = liteserver.clone();
+
+        async move {
+            match liteserver.get().await {
+                Ok(_) => tracing::info!("LiteServer Pool is ready"),
+                Err(e) => tracing::warn!(error = ?e, "LiteServer Pool is unavailable, connecting on the next block data call")
+            }
+        }
+    });
+
     let reflection = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
         .register_encoded_file_descriptor_set(ton::FILE_DESCRIPTOR_SET)
         .build()?;
 
-    let account_service = AccountServiceServer::new(AccountService::new(client.clone()))
-        .accept_compressed(Gzip)
-        .send_compressed(Gzip);
-    let block_service = BlockServiceServer::new(BlockService::new(client.clone()))
-        .accept_compressed(Gzip)
-        .send_compressed(Gzip);
+    if let Some(encoding) = args.compression.encoding() {
+        tracing::info!("Compressing block and account state responses with {:?}", encoding);
+    }
+    let account_service = AccountService::new(client.clone()).into_server(args.compression);
+    let block_service = BlockService::new(client.clone(), liteserver).into_server(args.compression);
+    // `--compression` covers the large responses only, message responses stay gzip as they always were
     let message_service = MessageServiceServer::new(MessageService::new(client))
         .accept_compressed(Gzip)
+        .accept_compressed(Zstd)
         .send_compressed(Gzip);
 
     let (mut health_reporter, health_server) = tonic_health::server::health_reporter();
@@ -1,5 +1,7 @@
 use std::str::FromStr;
 use anyhow::anyhow;
+use base64::Engine;
+use ton_liteserver_client::tl::TonNodeBlockIdExt;
 use tonlibjson_client::address::{AccountAddressData};
 use tonlibjson_client::block;
 use tonlibjson_client::block::{MsgBoxedData, MsgDataDecryptedText, MsgDataEncryptedText, MsgDataRaw, MsgDataText};
@@ -22,6 +24,18 @@ impl From<block::TonBlockIdExt> for BlockIdExt {
     }
 }
 
+impl From<TonNodeBlockIdExt> for BlockIdExt {
+    fn from(value: TonNodeBlockIdExt) -> Self {
+        Self {
+            workchain: value.workchain,
+            shard: value.shard,
+            seqno: value.seqno,
+            root_hash: base64::engine::general_purpose::STANDARD.encode(value.root_hash),
+            file_hash: base64::engine::general_purpose::STANDARD.encode(value.file_hash),
+        }
+    }
+}
+
 impl From<BlockIdExt> for block::TonBlockIdExt {
     fn from(value: BlockIdExt) -> Self {
         Self {
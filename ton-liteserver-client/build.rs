@@ -5,6 +5,7 @@ use syn::{GenericArgument, Ident, MetaList};
 use quote::{format_ident, quote, ToTokens};
 use convert_case::{Case, Casing};
 use convert_case::Case::UpperCamel;
+use sha2::{Digest, Sha256};
 use tl_parser::{Combinator, Condition};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,6 +19,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed={}", scheme_path.to_string_lossy());
 
     Generator::from(scheme_path, "generated.rs")
+        .configure("tonNode.blockIdExt", TypeConfiguration::default().derive("Hash"))
         .generate()?;
 
     Ok(())
@@ -39,6 +41,14 @@ impl Default for TypeConfiguration {
     }
 }
 
+impl TypeConfiguration {
+    fn derive(mut self, derive: &str) -> Self {
+        self.derives.push(derive.to_owned());
+
+        self
+    }
+}
+
 impl Generator {
     fn from<I: AsRef<Path>, O: AsRef<Path>>(input: I, output: O) -> Self {
         let input: PathBuf = input.as_ref().to_path_buf();
@@ -47,11 +57,20 @@ impl Generator {
         Self { input, output, types: Default::default() }
     }
 
+    fn configure(mut self, id: &str, configuration: TypeConfiguration) -> Self {
+        self.types.insert(id.to_owned(), configuration);
+
+        self
+    }
+
     fn generate(self) -> anyhow::Result<()> {
         let content = fs::read_to_string(self.input)?;
 
         let combinators = tl_parser::parse(&content)?;
 
+        let schema_hash = hex::encode(Sha256::digest(content.as_bytes()));
+        let mut formatted = format!("/// Hex of the sha256 of the `lite_api.tl` the types are generated from\npub const TL_SCHEMA_HASH: &str = {:?};\n", schema_hash);
+
         let mut map: HashMap<String, Vec<Combinator>> = HashMap::default();
         for combinator in combinators.iter() {
             map.entry(combinator.result_type().to_owned())
@@ -59,8 +78,6 @@ impl Generator {
                 .push(combinator.to_owned());
         }
 
-        let mut formatted = String::new();
-
         let skip_list: Vec<String> = vec!["Vector t", "Int32", "Int53", "Int64", "Int128", "Int256", "Bytes", "SecureString", "SecureBytes", "Function"]
             .into_iter().map(|s| s.to_owned()).collect();
 
@@ -294,6 +311,7 @@ impl Generator {
                                 Some("int256") => quote! { se.write_i256(#field_name_ident); },
                                 Some("bytes") => quote! { se.write_bytes(#field_name_ident); },
                                 Some("string") => quote! { se.write_string(#field_name_ident); },
+                                Some(typ) if is_boxed(typ) => quote! { #field_name_ident.serialize_boxed(se); },
                                 _ => quote! { #field_name_ident.serialize(se); }
                             },
                             Some(Condition { field_ref: _, bit_selector: Some(_) }) =>  {
@@ -306,6 +324,7 @@ impl Generator {
                                     Some("int256") => quote! { se.write_i256(value) },
                                     Some("bytes") => quote! { se.write_bytes(value) },
                                     Some("string") => quote! { se.write_string(value) },
+                                    Some(typ) if is_boxed(typ) => quote! { value.serialize_boxed(se) },
                                     _ => quote! { value.serialize(se) }
                                 };
                                 quote! {
@@ -338,6 +357,10 @@ impl Generator {
                             Some("int256") => quote! { de.parse_i256()? },
                             Some("bytes") => quote! { de.parse_bytes()? },
                             Some("string") => quote! { de.parse_string()? },
+                            Some(typ) if is_boxed(typ) => {
+                                let field_type = format_ident!("{}", structure_ident(typ));
+                                quote! { #field_type::deserialize_boxed(de.parse_constructor_numer()?, de)? }
+                            },
                             _ => {
                                 let field_type = format_ident!("{}", structure_ident(field.field_type().unwrap()));
                                 quote! { #field_type::deserialize(de)? }
@@ -378,10 +401,29 @@ impl Generator {
 
                 let traits = if definition.is_functional() {
                     let result_name = format_ident!("{}", generate_type_name(definition.result_type()));
+                    let name = definition.id();
+                    let block_id = match definition.fields().iter().find(|field| field.id() == Some("id") && field.type_condition().is_none()).and_then(|field| field.field_type()) {
+                        Some("tonNode.blockIdExt") => quote! { Some(TonNodeBlockId { workchain: self.id.workchain, shard: self.id.shard, seqno: self.id.seqno }) },
+                        Some("tonNode.blockId") => quote! { Some(self.id.clone()) },
+                        _ => quote! { None }
+                    };
+                    let idempotent = match name {
+                        "liteServer.sendMessage" => quote! { const IDEMPOTENT: bool = false; },
+                        _ => quote! {}
+                    };
                     quote! {
                         impl Functional for #struct_name {
                             type Result = #result_name;
                         }
+
+                        impl crate::request::Described for #struct_name {
+                            const NAME: &'static str = #name;
+                            #idempotent
+
+                            fn block_id(&self) -> Option<TonNodeBlockId> {
+                                #block_id
+                            }
+                        }
                     }
                 } else {
                     quote! {}
@@ -474,6 +516,13 @@ fn generate_type_name(s: &str) -> String {
     format!("{}{}{}", ns_prefix, boxed_prefix, name.to_case(UpperCamel))
 }
 
+/// Boxed types are serialized with their constructor number, even the ones with a single constructor
+fn is_boxed(s: &str) -> bool {
+    let (_, name) = s.rsplit_once('.').unwrap_or(("", s));
+
+    name.starts_with(|c: char| c.is_uppercase())
+}
+
 fn structure_ident(s: &str) -> Ident {
     format_ident!("{}", generate_type_name(s))
 }
@@ -29,7 +29,7 @@ async fn main() -> Result<(), tower::BoxError> {
         .await
         .unwrap();
 
-    let boc: BoC = unpack_bytes(&shards.data)?;
+    let boc: BoC = unpack_bytes(shards.data)?;
 
     tracing::info!("Got BOC: {:?}", boc);
 
@@ -0,0 +1,303 @@
+//! Accounts in shard states and the stream of their new transactions
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use anyhow::{ensure, Context as _};
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use tower::{Service, ServiceExt};
+use crate::block_stream::poll_last_block;
+use crate::cell::{dict_entries, key_to_int256, read_boc, Cell, CellSlice};
+use crate::client::Error;
+use crate::tl::{Int256, LiteServerAccountId, LiteServerAccountState, LiteServerGetAccountState, LiteServerGetMasterchainInfo, LiteServerGetTransactions, LiteServerMasterchainInfo, LiteServerTransactionList, TonNodeBlockIdExt};
+
+/// Transactions asked for by a single `liteServer.getTransactions`, the most liteservers give out
+pub(crate) const TRANSACTIONS_PAGE: i32 = 16;
+
+/// Latest transaction of an account as recorded in `ShardAccount`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastTransaction {
+    pub lt: i64,
+    pub hash: Int256,
+}
+
+impl LiteServerAccountState {
+    /// Latest transaction of `account` in the shard state proof, `None` when the account doesn't exist
+    pub fn last_transaction(&self, account: &Int256) -> anyhow::Result<Option<LastTransaction>> {
+        parse_last_transaction(&self.proof, account)
+    }
+}
+
+/// Finds `account` in the `ShardAccounts` of the state proof, the last root of `proof`
+pub fn parse_last_transaction(proof: &[u8], account: &Int256) -> anyhow::Result<Option<LastTransaction>> {
+    let roots = read_boc(proof)?;
+    let state = roots.last().context("empty account state proof")?.proof_root()?;
+
+    let Some(accounts) = shard_accounts(state)? else {
+        return Ok(None);
+    };
+
+    for (key, mut value) in dict_entries(accounts, 256)? {
+        if key_to_int256(&key)? != *account {
+            continue;
+        }
+        load_account(&mut value)?;
+
+        return Ok(Some(LastTransaction { hash: value.load_int256()?, lt: value.load_uint(64)? as i64 }));
+    }
+
+    Ok(None)
+}
+
+/// Root of the `ShardAccounts` of `state`, a `ShardStateUnsplit`, `None` when the shard has no accounts
+pub(crate) fn shard_accounts(state: &Cell) -> anyhow::Result<Option<&Arc<Cell>>> {
+    let mut slice = state.parser();
+    ensure!(slice.load_uint(32)? == 0x9023afe2, "not a ShardStateUnsplit");
+    // global_id, shard_id, seq_no, vert_seq_no, gen_utime, gen_lt and min_ref_mc_seqno
+    slice.skip(32 + 104 + 32 + 32 + 32 + 64 + 32)?;
+    // out_msg_queue_info
+    slice.load_reference()?;
+    // before_split
+    slice.skip(1)?;
+
+    let mut accounts = slice.load_reference()?.parser();
+    if !accounts.load_bit()? {
+        return Ok(None);
+    }
+
+    Ok(Some(accounts.load_reference()?))
+}
+
+/// Account cell of the `ShardAccount` at `value`, leaves `value` at `last_trans_hash`
+pub(crate) fn load_account<'a>(value: &mut CellSlice<'a>) -> anyhow::Result<&'a Arc<Cell>> {
+    // DepthBalanceInfo extra: split_depth and the balance with its extra currencies
+    value.skip(5)?;
+    value.load_var_uint(4)?;
+    if value.load_bit()? {
+        value.load_reference()?;
+    }
+
+    value.load_reference()
+}
+
+/// Transaction of an account along with the block it is in
+#[derive(Debug, Clone)]
+pub struct AccountTransaction {
+    pub block_id: TonNodeBlockIdExt,
+    pub lt: i64,
+    pub hash: Int256,
+    pub prev_lt: i64,
+    pub prev_hash: Int256,
+    pub cell: Arc<Cell>,
+}
+
+impl AccountTransaction {
+    /// Hash of the inbound message, `None` for a transaction without one, e.g. a tick-tock one
+    pub fn in_msg_hash(&self) -> anyhow::Result<Option<Int256>> {
+        let mut messages = self.cell.parser().load_reference()?.parser();
+        if !messages.load_bit()? {
+            return Ok(None);
+        }
+
+        Ok(Some(messages.load_reference()?.hash()))
+    }
+}
+
+/// Transaction of `transactions` the message with hash `msg_hash` came in with
+pub fn find_by_in_msg<'a>(transactions: &'a [AccountTransaction], msg_hash: &Int256) -> anyhow::Result<Option<&'a AccountTransaction>> {
+    for transaction in transactions {
+        if transaction.in_msg_hash()?.as_ref() == Some(msg_hash) {
+            return Ok(Some(transaction));
+        }
+    }
+
+    Ok(None)
+}
+
+impl LiteServerTransactionList {
+    /// Transactions in the order of the answer, the newest first
+    pub fn transactions(&self) -> anyhow::Result<Vec<AccountTransaction>> {
+        let roots = read_boc(&self.transactions)?;
+        ensure!(roots.len() == self.ids.len(), "{} transactions for {} block ids", roots.len(), self.ids.len());
+
+        roots.into_iter()
+            .zip(self.ids.iter())
+            .map(|(cell, block_id)| {
+                let mut slice = cell.parser();
+                ensure!(slice.load_uint(4)? == 0b0111, "not a Transaction");
+                // account_addr
+                slice.skip(256)?;
+                let lt = slice.load_uint(64)? as i64;
+                let prev_hash = slice.load_int256()?;
+                let prev_lt = slice.load_uint(64)? as i64;
+
+                Ok(AccountTransaction { block_id: block_id.clone(), lt, hash: cell.hash(), prev_lt, prev_hash, cell })
+            })
+            .collect()
+    }
+}
+
+/// Every transaction of an account after it is subscribed to, in lt order
+pub struct AccountTransactionStream {
+    inner: BoxStream<'static, Result<AccountTransaction, Error>>
+}
+
+impl AccountTransactionStream {
+    /// Checks the account on the last masterchain block every second
+    pub fn new<S>(service: S, account: impl Into<LiteServerAccountId>) -> Self
+        where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+                + Service<LiteServerGetAccountState, Response = LiteServerAccountState, Error = Error>
+                + Service<LiteServerGetTransactions, Response = LiteServerTransactionList, Error = Error>
+                + Clone + Send + 'static,
+              <S as Service<LiteServerGetMasterchainInfo>>::Future: Send,
+              <S as Service<LiteServerGetAccountState>>::Future: Send,
+              <S as Service<LiteServerGetTransactions>>::Future: Send {
+        let last = poll_last_block(service.clone(), Duration::from_secs(1));
+
+        Self::with_blocks(service, account, last)
+    }
+
+    /// Checks the account on every block `last` yields, e.g. [`poll_last_block`]. The transactions the account
+    /// has on the first block aren't emitted, later ones are fetched back from the latest one to the last emitted
+    pub fn with_blocks<S, L>(service: S, account: impl Into<LiteServerAccountId>, last: L) -> Self
+        where S: Service<LiteServerGetAccountState, Response = LiteServerAccountState, Error = Error>
+                + Service<LiteServerGetTransactions, Response = LiteServerTransactionList, Error = Error>
+                + Clone + Send + 'static,
+              <S as Service<LiteServerGetAccountState>>::Future: Send,
+              <S as Service<LiteServerGetTransactions>>::Future: Send,
+              L: Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static {
+        let account = account.into();
+        let inner = try_stream! {
+            // lt of the last emitted transaction
+            let mut seen: Option<i64> = None;
+            for await last in last {
+                let last = last?;
+                let state = ServiceExt::<LiteServerGetAccountState>::oneshot(service.clone(), LiteServerGetAccountState { id: last, account: account.clone() })
+                    .await?;
+                let current = state.last_transaction(&account.id).map_err(|_| Error::Decode)?;
+                let current_lt = current.map_or(0, |current| current.lt);
+
+                let Some(since) = seen else {
+                    seen = Some(current_lt);
+                    continue
+                };
+                // a lagging liteserver may answer with an older state
+                let Some(mut next) = current.filter(|current| current.lt > since) else { continue };
+
+                let mut fresh = Vec::new();
+                'pages: while next.lt > since {
+                    let page = ServiceExt::<LiteServerGetTransactions>::oneshot(service.clone(), LiteServerGetTransactions { count: TRANSACTIONS_PAGE, account: account.clone(), lt: next.lt, hash: next.hash })
+                        .await?;
+                    let transactions = page.transactions().map_err(|_| Error::Decode)?;
+                    if transactions.is_empty() {
+                        break;
+                    }
+
+                    for transaction in transactions {
+                        if transaction.lt <= since {
+                            break 'pages;
+                        }
+                        next = LastTransaction { lt: transaction.prev_lt, hash: transaction.prev_hash };
+                        fresh.push(transaction);
+                    }
+                }
+
+                for transaction in fresh.into_iter().rev() {
+                    yield transaction;
+                }
+                seen = Some(current_lt);
+            }
+        };
+
+        Self { inner: inner.boxed() }
+    }
+}
+
+impl Stream for AccountTransactionStream {
+    type Item = Result<AccountTransaction, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, TryStreamExt};
+    use crate::mock::MockLiteServer;
+    use super::*;
+
+    const ACCOUNT: Int256 = [0x11; 32];
+
+    /// The state proofs after transactions T1 and T3, the list of T3, T2 and T1 and the same list in pages of T3, T2 and of T1
+    fn fixture(line: usize) -> Vec<u8> {
+        hex::decode(include_str!("../fixtures/account_transactions.hex").lines().nth(line).unwrap()).unwrap()
+    }
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    fn state(proof: Vec<u8>) -> LiteServerAccountState {
+        LiteServerAccountState { id: block_id(0), shardblk: block_id(0), shard_proof: vec![], proof, state: vec![] }
+    }
+
+    #[test]
+    fn parse_last_transaction_test() {
+        let first = state(fixture(0)).last_transaction(&ACCOUNT).unwrap().unwrap();
+        let last = state(fixture(1)).last_transaction(&ACCOUNT).unwrap().unwrap();
+
+        assert_eq!(first.lt, 10);
+        assert_eq!(last.lt, 30);
+        assert_eq!(hex::encode(last.hash), "84b25ed188124a75558076915ae17cc34a71fa0dae0a1b323c4a94fa855e4daf");
+        // the neighbour is pruned
+        assert_eq!(state(fixture(1)).last_transaction(&[0x22; 32]).unwrap(), None);
+    }
+
+    #[test]
+    fn transaction_list_test() {
+        let list = LiteServerTransactionList { ids: vec![block_id(3), block_id(2), block_id(1)], transactions: fixture(2) };
+
+        let transactions = list.transactions().unwrap();
+
+        assert_eq!(transactions.iter().map(|tx| tx.lt).collect::<Vec<_>>(), vec![30, 20, 10]);
+        assert_eq!(transactions[0].prev_hash, transactions[1].hash);
+        assert_eq!(transactions[1].prev_lt, 10);
+        assert_eq!(transactions[2].block_id, block_id(1));
+    }
+
+    #[test]
+    fn find_by_in_msg_test() {
+        // T3, T2 without an inbound message and T1, then the messages of T1 and T3
+        let lines: Vec<_> = include_str!("../fixtures/send_message.hex").lines().map(|line| hex::decode(line).unwrap()).collect();
+        let list = LiteServerTransactionList { ids: vec![block_id(3), block_id(2), block_id(1)], transactions: lines[0].clone() };
+        let transactions = list.transactions().unwrap();
+        let hash = |boc: &[u8]| crate::cell::read_single_root(boc).unwrap().hash();
+
+        assert_eq!(transactions[1].in_msg_hash().unwrap(), None);
+        assert_eq!(find_by_in_msg(&transactions, &hash(&lines[1])).unwrap().unwrap().lt, 10);
+        assert_eq!(find_by_in_msg(&transactions, &hash(&lines[2])).unwrap().unwrap().lt, 30);
+        assert!(find_by_in_msg(&transactions, &[0; 32]).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn emits_new_transactions_once_test() {
+        let mock = MockLiteServer::default()
+            .on(|request: LiteServerGetAccountState| Ok(state(fixture(if request.id.seqno == 1 { 0 } else { 1 }))))
+            .on(|request: LiteServerGetTransactions| {
+                assert_eq!(request.lt, 30);
+
+                Ok(LiteServerTransactionList { ids: vec![block_id(2), block_id(2), block_id(1)], transactions: fixture(2) })
+            });
+        let last = stream::iter([1, 2, 3, 1].map(|seqno| Ok(block_id(seqno))));
+
+        let transactions: Vec<_> = AccountTransactionStream::with_blocks(mock, (0, ACCOUNT), last).try_collect().await.unwrap();
+
+        // T2 and T3 both land in block 2, T1 is on the first block
+        assert_eq!(transactions.iter().map(|tx| tx.lt).collect::<Vec<_>>(), vec![20, 30]);
+        assert!(transactions.iter().all(|tx| tx.block_id == block_id(2)));
+    }
+}
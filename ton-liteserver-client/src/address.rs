@@ -0,0 +1,212 @@
+//! Account addresses in the raw and user-friendly forms and contract addresses derived from the state init
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, ensure};
+use base64::Engine;
+use crate::cell::Cell;
+use crate::tl::{Int256, LiteServerAccountId};
+
+const CRC16: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_XMODEM);
+
+const BOUNCEABLE: u8 = 0x11;
+const NON_BOUNCEABLE: u8 = 0x51;
+const TESTNET: u8 = 0x80;
+
+/// Address of an account, parsed from the raw `workchain:hex` form or the user-friendly base64url one.
+/// It is displayed in the user-friendly form, raw addresses count as bounceable mainnet ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccountAddress {
+    pub workchain: i32,
+    pub id: Int256,
+    pub bounceable: bool,
+    pub testnet: bool,
+}
+
+impl AccountAddress {
+    pub fn new(workchain: i32, id: Int256) -> Self {
+        Self { workchain, id, bounceable: true, testnet: false }
+    }
+
+    pub fn non_bounceable(self) -> Self {
+        Self { bounceable: false, ..self }
+    }
+
+    pub fn to_raw_string(&self) -> String {
+        format!("{}:{}", self.workchain, hex::encode(self.id))
+    }
+
+    fn from_raw(workchain: &str, id: &str) -> anyhow::Result<Self> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(id, &mut bytes).map_err(|error| anyhow!("invalid raw address {}: {}", id, error))?;
+
+        Ok(Self::new(workchain.parse()?, bytes))
+    }
+
+    fn from_friendly(s: &str) -> anyhow::Result<Self> {
+        ensure!(s.len() == 48, "user-friendly address must be 48 characters, got {}", s.len());
+        // either base64url or the standard alphabet
+        let data = base64::engine::general_purpose::STANDARD.decode(s.replace('-', "+").replace('_', "/"))
+            .map_err(|error| anyhow!("invalid user-friendly address {}: {}", s, error))?;
+
+        let (payload, crc) = data.split_at(34);
+        ensure!(CRC16.checksum(payload).to_be_bytes() == crc, "invalid checksum of address {}", s);
+
+        let bounceable = match payload[0] & !TESTNET {
+            BOUNCEABLE => true,
+            NON_BOUNCEABLE => false,
+            flags => bail!("invalid address flags {:#x}", flags)
+        };
+
+        Ok(Self { workchain: payload[1] as i8 as i32, id: payload[2..].try_into()?, bounceable, testnet: payload[0] & TESTNET != 0 })
+    }
+}
+
+impl FromStr for AccountAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((workchain, id)) => Self::from_raw(workchain, id),
+            None => Self::from_friendly(s)
+        }
+    }
+}
+
+impl Display for AccountAddress {
+    /// The user-friendly form, it only fits workchains in `-128..=127`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut flags = if self.bounceable { BOUNCEABLE } else { NON_BOUNCEABLE };
+        if self.testnet {
+            flags |= TESTNET;
+        }
+
+        let mut data = vec![flags, self.workchain as i8 as u8];
+        data.extend_from_slice(&self.id);
+        data.extend_from_slice(&CRC16.checksum(&data).to_be_bytes());
+
+        f.write_str(&base64::engine::general_purpose::URL_SAFE.encode(data))
+    }
+}
+
+impl From<AccountAddress> for LiteServerAccountId {
+    fn from(address: AccountAddress) -> Self {
+        LiteServerAccountId { workchain: address.workchain, id: address.id }
+    }
+}
+
+/// `StateInit` without `split_depth`, `special` and libraries, the form wallets and most contracts are deployed with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateInit {
+    pub code: Option<Arc<Cell>>,
+    pub data: Option<Arc<Cell>>,
+}
+
+impl StateInit {
+    pub fn new(code: Arc<Cell>, data: Arc<Cell>) -> Self {
+        Self { code: Some(code), data: Some(data) }
+    }
+
+    pub fn cell(&self) -> Cell {
+        // split_depth and special are absent, then the maybe bits of code and data and an empty library
+        let bits = [false, false, self.code.is_some(), self.data.is_some(), false];
+        let data = vec![bits.iter().fold(0_u8, |byte, bit| (byte << 1) | *bit as u8) << 3];
+        let references = self.code.iter().chain(self.data.iter()).cloned().collect();
+
+        Cell::new(false, data, bits.len(), references).expect("state init fits a cell")
+    }
+
+    pub fn hash(&self) -> Int256 {
+        self.cell().hash()
+    }
+
+    /// Address of the contract in `workchain`, the hash of the state init cell
+    pub fn address(&self, workchain: i32) -> LiteServerAccountId {
+        LiteServerAccountId { workchain, id: self.hash() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cell::read_single_root;
+    use super::*;
+
+    /// Code of the standard wallet v3r2
+    const WALLET_V3R2_CODE: &str = "te6cckEBAQEAcQAA3v8AIN0gggFMl7ohggEznLqxn3Gw7UTQ0x/THzHXC//jBOCk8mCDCNcYINMf0x/TH/gjE7vyY+1E0NMf0x/T/9FRMrryoVFEuvKiBPkBVBBV+RDyo/gAkyDXSpbTB9QC+wDo0QGkyMsfyx/L/8ntVBC9ba0=";
+
+    fn wallet_v3r2_code() -> Arc<Cell> {
+        read_single_root(&base64::engine::general_purpose::STANDARD.decode(WALLET_V3R2_CODE).unwrap()).unwrap()
+    }
+
+    /// Initial wallet v3 data: seqno 0, the default wallet id 698983191 and the public key
+    fn wallet_v3_data(public_key: &str) -> Arc<Cell> {
+        let mut data = vec![0; 4];
+        data.extend(698983191_u32.to_be_bytes());
+        data.extend(hex::decode(public_key).unwrap());
+
+        Arc::new(Cell::new(false, data, 320, vec![]).unwrap())
+    }
+
+    #[test]
+    fn parse_friendly_address_test() {
+        let bounceable: AccountAddress = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS".parse().unwrap();
+        let non_bounceable: AccountAddress = "UQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GB7X".parse().unwrap();
+
+        assert!(bounceable.bounceable);
+        assert!(!non_bounceable.bounceable);
+        assert_eq!(bounceable.workchain, 0);
+        assert_eq!(bounceable.id, non_bounceable.id);
+        assert_eq!(bounceable.to_raw_string(), "0:a3935861f79daf59a13d6d182e1640210c02f98e3df18fda74b8f5ab141abf18");
+        assert_eq!(bounceable.non_bounceable().to_string(), "UQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GB7X");
+        assert_eq!(non_bounceable.to_string(), "UQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GB7X");
+    }
+
+    #[test]
+    fn parse_raw_address_test() {
+        let elector: AccountAddress = "-1:5555555555555555555555555555555555555555555555555555555555555555".parse().unwrap();
+
+        assert_eq!(elector, AccountAddress::new(-1, [0x55; 32]));
+        assert_eq!(elector.to_string(), "Ef9VVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVbxn");
+        assert_eq!("Ef9VVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVVbxn".parse::<AccountAddress>().unwrap(), elector);
+        assert_eq!(LiteServerAccountId::from(elector), LiteServerAccountId { workchain: -1, id: [0x55; 32] });
+        assert!("-1:5555".parse::<AccountAddress>().is_err());
+        assert!("x:5555555555555555555555555555555555555555555555555555555555555555".parse::<AccountAddress>().is_err());
+    }
+
+    #[test]
+    fn parse_address_checksum_failure_test() {
+        // the last character of EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS changed
+        assert!("EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMT".parse::<AccountAddress>().is_err());
+        // the address changed, the checksum didn't
+        assert!("EQCjk1hh952vWbE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS".parse::<AccountAddress>().is_err());
+        assert!("YXNkcXdl".parse::<AccountAddress>().is_err());
+    }
+
+    #[test]
+    fn wallet_v3r2_code_hash_test() {
+        assert_eq!(hex::encode(wallet_v3r2_code().hash()), "84dafa449f98a6987789ba232358072bc0f76dc4524002a5d0918b9a75d2d599");
+    }
+
+    #[test]
+    fn wallet_v3r2_address_test() {
+        let state_init = StateInit::new(wallet_v3r2_code(), wallet_v3_data("78531605392fce73d6cd8c33e8c6082880885e62d96def43a75933d978d249ea"));
+
+        let address = state_init.address(0);
+
+        assert_eq!(address.workchain, 0);
+        assert_eq!(hex::encode(address.id), "258b72fd5e827ee71fe4266905db0c823b7993b5c5455ff43f63328883c953ff");
+        assert_eq!(AccountAddress::new(address.workchain, address.id).to_string(), "EQAli3L9XoJ-5x_kJmkF2wyCO3mTtcVFX_Q_YzKIg8lT_xRy");
+        assert_eq!(state_init.address(-1).id, address.id);
+    }
+
+    #[test]
+    fn state_init_without_data_test() {
+        let code = wallet_v3r2_code();
+        let with_data = StateInit::new(code.clone(), wallet_v3_data(&"00".repeat(32)));
+        let without_data = StateInit { code: Some(code), data: None };
+
+        assert_eq!(without_data.cell().references().len(), 1);
+        assert_ne!(without_data.hash(), with_data.hash());
+    }
+}
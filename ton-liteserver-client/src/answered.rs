@@ -0,0 +1,56 @@
+use std::ops::Deref;
+use crate::client::Error;
+use crate::tl::TonNodeBlockIdExt;
+
+/// Response that only holds at the block `block_id`, e.g. an account state or a config. Derefs to the response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answered<T> {
+    pub block_id: TonNodeBlockIdExt,
+    pub value: T,
+}
+
+impl<T> Answered<T> {
+    /// `value` was answered for the block `answered`, `Error::BlockMismatch` unless that is the `requested` one
+    pub fn checked(requested: TonNodeBlockIdExt, answered: TonNodeBlockIdExt, value: T) -> Result<Self, Error> {
+        if requested != answered {
+            return Err(Error::BlockMismatch { requested: Box::new(requested), answered: Box::new(answered) });
+        }
+
+        Ok(Self { block_id: requested, value })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Answered<U> {
+        Answered { block_id: self.block_id, value: f(self.value) }
+    }
+}
+
+impl<T> Deref for Answered<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    #[test]
+    fn checked_test() {
+        let answered = Answered::checked(block_id(1), block_id(1), 7).unwrap();
+
+        assert_eq!(answered.block_id, block_id(1));
+        assert_eq!(*answered, 7);
+        assert_eq!(answered.map(|value| value * 2).into_inner(), 14);
+        assert!(matches!(Answered::checked(block_id(1), block_id(2), 7), Err(Error::BlockMismatch { .. })));
+    }
+}
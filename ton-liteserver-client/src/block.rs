@@ -0,0 +1,283 @@
+//! Structured view of the block BoCs `liteServer.getBlock` answers with
+
+use anyhow::ensure;
+use sha2::{Digest, Sha256};
+use crate::cell::{key_to_int256, key_to_uint, read_single_root, Cell, CellSlice};
+use crate::client::Error;
+use crate::shards::load_shard_hashes;
+use crate::tl::{Int256, LiteServerBlockData, TonNodeBlockIdExt};
+
+/// Fields of `BlockInfo` identifying the block and its place in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct BlockInfo {
+    pub workchain: i32,
+    #[cfg_attr(feature = "json", serde(serialize_with = "serialize_shard"))]
+    pub shard: i64,
+    pub seqno: i32,
+    pub key_block: bool,
+    pub gen_utime: u32,
+    pub start_lt: i64,
+    pub end_lt: i64,
+}
+
+/// Transaction of an account listed in `account_blocks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct BlockTransaction {
+    #[cfg_attr(feature = "json", serde(serialize_with = "serialize_hash"))]
+    pub account: Int256,
+    pub lt: i64,
+}
+
+/// Serializes to JSON with the `json` feature, shards are 16 hex digits and hashes uppercase hex as printed by lite-client
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Block {
+    pub info: BlockInfo,
+    /// Top blocks of the shards a masterchain block refers to, empty for shard blocks
+    #[cfg_attr(feature = "json", serde(serialize_with = "serialize_block_ids"))]
+    pub shards: Vec<TonNodeBlockIdExt>,
+    /// Ordered by account and then by lt
+    pub transactions: Vec<BlockTransaction>,
+}
+
+#[cfg(feature = "json")]
+fn serialize_shard<S: serde::Serializer>(shard: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:016X}", *shard as u64))
+}
+
+#[cfg(feature = "json")]
+fn serialize_hash<S: serde::Serializer>(hash: &Int256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode_upper(hash))
+}
+
+#[cfg(feature = "json")]
+fn serialize_block_ids<S: serde::Serializer>(ids: &[TonNodeBlockIdExt], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(ids.iter().map(ToString::to_string))
+}
+
+impl LiteServerBlockData {
+    /// Decodes the block BoC, checked to be the block `id`
+    pub fn parse(&self) -> anyhow::Result<Block> {
+        let root = read_single_root(&self.data)?;
+        ensure!(root.hash() == self.id.root_hash, "block hash mismatch");
+
+        let block = parse_block(&root)?;
+        ensure!(block.info.seqno == self.id.seqno && block.info.workchain == self.id.workchain && block.info.shard == self.id.shard, "data of another block");
+
+        Ok(block)
+    }
+
+    /// `Error::HashMismatch` unless the BoC is the block `id`: it hashes to the file hash and its root to the root hash
+    pub fn check(&self, id: &TonNodeBlockIdExt) -> Result<(), Error> {
+        let file_hash: Int256 = Sha256::digest(&self.data).into();
+        let root_hash = read_single_root(&self.data).map(|root| root.hash()).ok();
+        if self.id != *id || file_hash != id.file_hash || root_hash != Some(id.root_hash) {
+            return Err(Error::HashMismatch(id.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes the `Block` rooted at `root`
+pub fn parse_block(root: &Cell) -> anyhow::Result<Block> {
+    let mut extra = block_extra(root)?;
+    let info = parse_info(root.reference(0)?)?.info;
+    let transactions = parse_account_blocks(extra.load_reference()?)?;
+    let shards = match load_mc_block_extra(&mut extra)? {
+        Some((_, mut custom)) => load_shard_hashes(&mut custom)?,
+        None => Vec::new(),
+    };
+
+    Ok(Block { info, shards, transactions })
+}
+
+/// `BlockInfo` up to `prev_key_block_seqno`, the fields block proofs are checked against besides [`BlockInfo`]
+pub(crate) struct BlockInfoFields {
+    pub(crate) info: BlockInfo,
+    pub(crate) not_master: bool,
+    pub(crate) after_merge: bool,
+    pub(crate) after_split: bool,
+    pub(crate) gen_validator_list_hash_short: u32,
+    pub(crate) gen_catchain_seqno: u32,
+    pub(crate) prev_key_block_seqno: i32,
+}
+
+pub(crate) fn parse_info(cell: &Cell) -> anyhow::Result<BlockInfoFields> {
+    let mut slice = cell.parser();
+    ensure!(slice.load_uint(32)? == 0x9bc7a987, "not a BlockInfo");
+    // version
+    slice.skip(32)?;
+    let not_master = slice.load_bit()?;
+    let after_merge = slice.load_bit()?;
+    // before_split
+    slice.skip(1)?;
+    let after_split = slice.load_bit()?;
+    // want_split and want_merge
+    slice.skip(2)?;
+    let key_block = slice.load_bit()?;
+    // vert_seqno_incr and flags
+    slice.skip(1 + 8)?;
+    let seqno = slice.load_uint(32)? as i32;
+    // vert_seq_no and the shard_ident tag
+    slice.skip(32 + 2)?;
+    let prefix_bits = slice.load_uint(6)?;
+    ensure!(prefix_bits <= 60, "invalid shard prefix");
+    let workchain = slice.load_int(32)? as i32;
+    let shard = (slice.load_uint(64)? | 1 << (63 - prefix_bits)) as i64;
+    let gen_utime = slice.load_uint(32)? as u32;
+    let start_lt = slice.load_uint(64)? as i64;
+    let end_lt = slice.load_uint(64)? as i64;
+    let gen_validator_list_hash_short = slice.load_uint(32)? as u32;
+    let gen_catchain_seqno = slice.load_uint(32)? as u32;
+    // min_ref_mc_seqno
+    slice.skip(32)?;
+    let prev_key_block_seqno = slice.load_uint(32)? as i32;
+
+    Ok(BlockInfoFields {
+        info: BlockInfo { workchain, shard, seqno, key_block, gen_utime, start_lt, end_lt },
+        not_master,
+        after_merge,
+        after_split,
+        gen_validator_list_hash_short,
+        gen_catchain_seqno,
+        prev_key_block_seqno,
+    })
+}
+
+/// `key_block` flag of the block rooted at `root` and its `McBlockExtra` past it, `None` for shard blocks
+pub(crate) fn mc_block_extra(root: &Cell) -> anyhow::Result<Option<(bool, CellSlice<'_>)>> {
+    let mut extra = block_extra(root)?;
+    // account_blocks
+    extra.load_reference()?;
+
+    load_mc_block_extra(&mut extra)
+}
+
+/// `BlockExtra` of the block rooted at `root`, positioned at `account_blocks`
+fn block_extra(root: &Cell) -> anyhow::Result<CellSlice<'_>> {
+    ensure!(root.parser().load_uint(32)? == 0x11ef55aa, "not a Block");
+
+    let mut extra = root.reference(3)?.parser();
+    ensure!(extra.load_uint(32)? == 0x4a33f6fd, "not a BlockExtra");
+    // in_msg_descr and out_msg_descr
+    extra.load_reference()?;
+    extra.load_reference()?;
+
+    Ok(extra)
+}
+
+fn load_mc_block_extra<'a>(extra: &mut CellSlice<'a>) -> anyhow::Result<Option<(bool, CellSlice<'a>)>> {
+    // rand_seed and created_by
+    extra.skip(256 + 256)?;
+    if !extra.load_bit()? {
+        return Ok(None);
+    }
+
+    let mut custom = extra.load_reference()?.parser();
+    ensure!(custom.load_uint(16)? == 0xcca5, "not a McBlockExtra");
+    let key_block = custom.load_bit()?;
+
+    Ok(Some((key_block, custom)))
+}
+
+/// Entries of `ShardAccountBlocks`, a `HashmapAugE 256 AccountBlock CurrencyCollection`
+fn parse_account_blocks(cell: &Cell) -> anyhow::Result<Vec<BlockTransaction>> {
+    let mut transactions = Vec::new();
+    for (key, mut value) in cell.parser().load_dict(256)? {
+        skip_currency_collection(&mut value)?;
+        ensure!(value.load_uint(4)? == 0x5, "not an AccountBlock");
+        let account = value.load_int256()?;
+        ensure!(account == key_to_int256(&key)?, "AccountBlock of another account");
+
+        // HashmapAug 64 ^Transaction CurrencyCollection, the lt is the key
+        for (lt, _) in value.parse_dict(64)? {
+            transactions.push(BlockTransaction { account, lt: key_to_uint(&lt) as i64 });
+        }
+    }
+
+    Ok(transactions)
+}
+
+fn skip_currency_collection(slice: &mut CellSlice) -> anyhow::Result<()> {
+    slice.load_var_uint(4)?;
+    if slice.load_bit()? {
+        slice.load_reference()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Masterchain block 200 with transactions of two accounts and two shards
+    fn fixture() -> LiteServerBlockData {
+        let fields: Vec<_> = include_str!("../fixtures/block.hex").split_whitespace().collect();
+        let id = TonNodeBlockIdExt {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: fields[0].parse().unwrap(),
+            root_hash: hex::decode(fields[1]).unwrap().try_into().unwrap(),
+            file_hash: hex::decode(fields[2]).unwrap().try_into().unwrap(),
+        };
+
+        LiteServerBlockData { id, data: hex::decode(fields[3]).unwrap() }
+    }
+
+    #[test]
+    fn parse_block_test() {
+        let data = fixture();
+
+        let block = data.parse().unwrap();
+
+        assert_eq!(block.info.seqno, data.id.seqno);
+        assert_eq!((block.info.workchain, block.info.shard), (-1, i64::MIN));
+        assert!(!block.info.key_block);
+        assert_eq!(block.info.gen_utime, 1718001234);
+        assert_eq!(block.info.end_lt - block.info.start_lt, 5);
+        assert_eq!(block.transactions.len(), 3);
+        assert!(block.transactions.iter().all(|tx| (block.info.start_lt..=block.info.end_lt).contains(&tx.lt)));
+        assert_eq!(block.transactions[0], BlockTransaction { account: [0x11; 32], lt: block.info.start_lt + 1 });
+        assert_eq!(block.transactions[2], BlockTransaction { account: [0xaa; 32], lt: block.info.start_lt + 2 });
+        assert_eq!(block.shards.iter().map(|shard| shard.seqno).collect::<Vec<_>>(), vec![45000001, 45000002]);
+    }
+
+    #[test]
+    fn check_block_test() {
+        let data = fixture();
+        let mut tampered = fixture();
+        tampered.data[18] ^= 1;
+
+        assert!(data.check(&data.id).is_ok());
+        assert!(matches!(tampered.check(&data.id), Err(Error::HashMismatch(_))));
+        assert!(matches!(data.check(&TonNodeBlockIdExt { file_hash: [0; 32], ..data.id.clone() }), Err(Error::HashMismatch(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn block_json_test() {
+        let block = fixture().parse().unwrap();
+
+        let json = serde_json::to_value(&block).unwrap();
+
+        assert_eq!(json["info"]["seqno"], 200);
+        assert_eq!(json["info"]["shard"], "8000000000000000");
+        assert_eq!(json["info"]["gen_utime"], 1718001234);
+        assert_eq!(json["shards"].as_array().unwrap().len(), 2);
+        assert!(json["shards"][0].as_str().unwrap().starts_with("(0,"));
+        assert_eq!(json["transactions"][0]["account"], "11".repeat(32));
+        assert_eq!(json["transactions"][0]["lt"], block.transactions[0].lt);
+    }
+
+    #[test]
+    fn parse_block_of_another_id_test() {
+        let mut data = fixture();
+        data.id.root_hash = [0; 32];
+
+        assert!(data.parse().is_err());
+    }
+}
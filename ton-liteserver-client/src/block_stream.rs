@@ -0,0 +1,105 @@
+//! Contiguous stream of masterchain block headers
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use tokio::time::MissedTickBehavior;
+use tower::{Service, ServiceExt};
+use crate::client::Error;
+use crate::lookup::MODE_SEQNO;
+use crate::tl::{LiteServerBlockHeader, LiteServerGetMasterchainInfo, LiteServerLookupBlock, LiteServerMasterchainInfo, TonNodeBlockId, TonNodeBlockIdExt};
+
+/// Every masterchain block header in seqno order, the ones the last block source skipped are looked up
+pub struct MasterchainBlockStream {
+    inner: BoxStream<'static, Result<LiteServerBlockHeader, Error>>
+}
+
+impl MasterchainBlockStream {
+    /// `last` yields the latest masterchain block, e.g. [`poll_last_block`]. The stream starts at `start`
+    /// or at the first block `last` yields when `None`
+    pub fn new<S, L>(service: S, last: L, start: Option<i32>) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + 'static,
+              S::Future: Send,
+              L: Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static {
+        let inner = try_stream! {
+            let mut next = start;
+            for await last in last {
+                let last = last?;
+                let from = next.unwrap_or(last.seqno);
+
+                for seqno in from ..= last.seqno {
+                    let id = TonNodeBlockId { workchain: last.workchain, shard: last.shard, seqno };
+                    let header = service.clone()
+                        .oneshot(LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None })
+                        .await?;
+
+                    yield header;
+                }
+
+                next = Some(from.max(last.seqno + 1));
+            }
+        };
+
+        Self { inner: inner.boxed() }
+    }
+}
+
+impl Stream for MasterchainBlockStream {
+    type Item = Result<LiteServerBlockHeader, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Last masterchain block every `interval`, repeated while it doesn't change
+pub fn poll_last_block<S>(service: S, interval: Duration) -> impl Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static
+    where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error> + Clone + Send + 'static,
+          S::Future: Send {
+    try_stream! {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            yield service.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, TryStreamExt};
+    use tower::service_fn;
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    async fn lookup(request: LiteServerLookupBlock) -> Result<LiteServerBlockHeader, Error> {
+        Ok(LiteServerBlockHeader { id: block_id(request.id.seqno), mode: 0, header_proof: vec![] })
+    }
+
+    #[tokio::test]
+    async fn fills_skipped_seqnos_test() {
+        let last = stream::iter([1, 1, 4, 5].map(|seqno| Ok(block_id(seqno))));
+
+        let headers: Vec<_> = MasterchainBlockStream::new(service_fn(lookup), last, None).try_collect().await.unwrap();
+
+        assert_eq!(headers.iter().map(|h| h.id.seqno).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn starts_at_given_seqno_test() {
+        let last = stream::iter([5, 7].map(|seqno| Ok(block_id(seqno))));
+
+        let headers: Vec<_> = MasterchainBlockStream::new(service_fn(lookup), last, Some(3)).try_collect().await.unwrap();
+
+        assert_eq!(headers.iter().map(|h| h.id.seqno).collect::<Vec<_>>(), vec![3, 4, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::client::Error;
+use crate::tl::LiteServerMasterchainInfoExt;
+
+/// Builds proof chains for `liteServer.getBlockProof`
+pub const CAP_PROOF_CHAINS: i64 = 1;
+/// Answers `liteServer.getMasterchainInfoExt`
+pub const CAP_MASTERCHAIN_INFO_EXT: i64 = 1 << 1;
+/// Runs get methods with `liteServer.runSmcMethod`
+pub const CAP_RUN_SMC_METHOD: i64 = 1 << 2;
+/// Every capability of the schema the crate is generated from
+const KNOWN_CAPABILITIES: i64 = CAP_PROOF_CHAINS | CAP_MASTERCHAIN_INFO_EXT | CAP_RUN_SMC_METHOD;
+
+/// Version and capabilities a liteserver reports in `liteServer.getMasterchainInfoExt`,
+/// servers that don't answer it have none of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: i32,
+    pub capabilities: i64,
+}
+
+impl Capabilities {
+    pub fn has(&self, capability: i64) -> bool {
+        self.capabilities & capability == capability
+    }
+
+    /// Capabilities the crate doesn't know of, a server reporting any speaks a newer schema than the generated one
+    pub fn unknown(&self) -> i64 {
+        self.capabilities & !KNOWN_CAPABILITIES
+    }
+
+    /// `Error::Unsupported` naming `request` unless the server has `capability`
+    pub fn require(&self, capability: i64, request: &'static str) -> Result<(), Error> {
+        if !self.has(capability) {
+            return Err(Error::Unsupported(request));
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&LiteServerMasterchainInfoExt> for Capabilities {
+    fn from(info: &LiteServerMasterchainInfoExt) -> Self {
+        Self { version: info.version, capabilities: info.capabilities }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn require_test() {
+        let capabilities = Capabilities { version: 0x101, capabilities: CAP_PROOF_CHAINS | CAP_MASTERCHAIN_INFO_EXT };
+
+        assert!(capabilities.require(CAP_PROOF_CHAINS, "liteServer.getBlockProof").is_ok());
+        assert!(capabilities.require(CAP_PROOF_CHAINS | CAP_MASTERCHAIN_INFO_EXT, "liteServer.getBlockProof").is_ok());
+        assert!(matches!(capabilities.require(CAP_RUN_SMC_METHOD, "liteServer.runSmcMethod"), Err(Error::Unsupported("liteServer.runSmcMethod"))));
+        assert!(!Capabilities::default().has(CAP_PROOF_CHAINS));
+    }
+
+    #[test]
+    fn unknown_capabilities_test() {
+        assert_eq!(Capabilities { version: 0x101, capabilities: 7 }.unknown(), 0);
+        assert_eq!(Capabilities { version: 0x200, capabilities: 7 | 1 << 5 }.unknown(), 1 << 5);
+    }
+}
@@ -0,0 +1,651 @@
+//! Minimal bag-of-cells reader for liteserver responses.
+//!
+//! Most liteserver payloads are merkle proofs full of pruned branches, so unlike a plain cell
+//! parser this one keeps exotic cells and their levels around to get the hashes right.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, ensure};
+use sha2::{Digest, Sha256};
+
+use crate::tl::Int256;
+
+const BOC_GENERIC_MAGIC: u32 = 0xb5ee9c72;
+const CRC32C: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellType {
+    Ordinary,
+    PrunedBranch,
+    Library,
+    MerkleProof,
+    MerkleUpdate,
+}
+
+impl CellType {
+    fn is_merkle(&self) -> bool {
+        matches!(self, CellType::MerkleProof | CellType::MerkleUpdate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    cell_type: CellType,
+    data: Vec<u8>,
+    bit_len: usize,
+    references: Vec<Arc<Cell>>,
+    level_mask: u8,
+    hashes: [Int256; 4],
+    depths: [u16; 4],
+}
+
+impl Cell {
+    pub fn new(
+        exotic: bool,
+        mut data: Vec<u8>,
+        bit_len: usize,
+        references: Vec<Arc<Cell>>,
+    ) -> anyhow::Result<Self> {
+        ensure!(bit_len <= 1023, "cell data overflow: {} bits", bit_len);
+        ensure!(references.len() <= 4, "cell references overflow: {}", references.len());
+        ensure!(data.len() * 8 >= bit_len, "cell data is shorter than {} bits", bit_len);
+
+        data.truncate(bit_len.div_ceil(8));
+        if bit_len % 8 != 0 {
+            if let Some(last) = data.last_mut() {
+                *last &= 0xff << (8 - bit_len % 8);
+            }
+        }
+
+        let cell_type = if exotic {
+            ensure!(bit_len >= 8, "exotic cell without type");
+            match data[0] {
+                1 => CellType::PrunedBranch,
+                2 => CellType::Library,
+                3 => CellType::MerkleProof,
+                4 => CellType::MerkleUpdate,
+                t => bail!("unknown exotic cell type {}", t),
+            }
+        } else {
+            CellType::Ordinary
+        };
+
+        let level_mask = match cell_type {
+            CellType::Ordinary => references.iter().fold(0, |mask, r| mask | r.level_mask),
+            CellType::PrunedBranch => {
+                ensure!(bit_len >= 16 && references.is_empty(), "invalid pruned branch");
+                let mask = data[1];
+                ensure!((1..=7).contains(&mask), "invalid pruned branch level mask");
+                ensure!(
+                    bit_len == 16 + hash_index(mask) * (256 + 16),
+                    "invalid pruned branch length"
+                );
+                mask
+            }
+            CellType::Library => {
+                ensure!(bit_len == 8 + 256 && references.is_empty(), "invalid library cell");
+                0
+            }
+            CellType::MerkleProof => {
+                ensure!(bit_len == 8 + 256 + 16 && references.len() == 1, "invalid merkle proof");
+                references[0].level_mask >> 1
+            }
+            CellType::MerkleUpdate => {
+                ensure!(
+                    bit_len == 8 + 2 * (256 + 16) && references.len() == 2,
+                    "invalid merkle update"
+                );
+                (references[0].level_mask | references[1].level_mask) >> 1
+            }
+        };
+
+        let mut cell = Self {
+            cell_type,
+            data,
+            bit_len,
+            references,
+            level_mask,
+            hashes: Default::default(),
+            depths: Default::default(),
+        };
+        cell.compute_hashes();
+
+        Ok(cell)
+    }
+
+    pub fn cell_type(&self) -> CellType {
+        self.cell_type
+    }
+
+    pub fn is_exotic(&self) -> bool {
+        self.cell_type != CellType::Ordinary
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn references(&self) -> &[Arc<Cell>] {
+        &self.references
+    }
+
+    pub fn reference(&self, index: usize) -> anyhow::Result<&Arc<Cell>> {
+        self.references
+            .get(index)
+            .ok_or_else(|| anyhow!("no reference {} in cell", index))
+    }
+
+    pub fn level(&self) -> usize {
+        level(self.level_mask)
+    }
+
+    /// Representation hash, the one block ids and account states refer to
+    pub fn hash(&self) -> Int256 {
+        self.hashes[3]
+    }
+
+    /// Hash of the original cell with every pruned branch in place, what a proof is checked against
+    pub fn virtual_hash(&self) -> Int256 {
+        self.hashes[0]
+    }
+
+    pub fn depth(&self) -> u16 {
+        self.depths[3]
+    }
+
+    /// Root of the virtual tree when the cell is a merkle proof
+    pub fn proof_root(&self) -> anyhow::Result<&Arc<Cell>> {
+        ensure!(self.cell_type == CellType::MerkleProof, "cell isn't a merkle proof");
+
+        self.reference(0)
+    }
+
+    pub fn parser(&self) -> CellSlice<'_> {
+        CellSlice {
+            cell: self,
+            bit: 0,
+            reference: 0,
+        }
+    }
+
+    fn level_hash(&self, level: usize) -> &Int256 {
+        &self.hashes[level.min(3)]
+    }
+
+    fn level_depth(&self, level: usize) -> u16 {
+        self.depths[level.min(3)]
+    }
+
+    fn d1(&self, level_mask: u8) -> u8 {
+        self.references.len() as u8 + 8 * self.is_exotic() as u8 + 32 * level_mask
+    }
+
+    fn d2(&self) -> u8 {
+        (self.bit_len / 8 + self.bit_len.div_ceil(8)) as u8
+    }
+
+    fn padded_data(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+        if self.bit_len % 8 != 0 {
+            if let Some(last) = data.last_mut() {
+                *last |= 0x80 >> (self.bit_len % 8);
+            }
+        }
+
+        data
+    }
+
+    fn compute_hashes(&mut self) {
+        let pruned = self.cell_type == CellType::PrunedBranch;
+        let total_hash_count = hash_index(self.level_mask) + 1;
+        let hash_count = if pruned { 1 } else { total_hash_count };
+        let offset = total_hash_count - hash_count;
+
+        let mut hashes: Vec<Int256> = Vec::with_capacity(hash_count);
+        let mut depths: Vec<u16> = Vec::with_capacity(hash_count);
+        let mut hash_i = 0;
+        for level_i in 0..=level(self.level_mask) {
+            if !is_significant(self.level_mask, level_i) {
+                continue;
+            }
+            if hash_i < offset {
+                hash_i += 1;
+                continue;
+            }
+
+            let mut hasher = Sha256::new();
+            hasher.update([self.d1(apply(self.level_mask, level_i)), self.d2()]);
+            match hashes.last() {
+                Some(previous) if hash_i != offset => hasher.update(previous),
+                _ => hasher.update(self.padded_data()),
+            }
+
+            let child_level = if self.cell_type.is_merkle() { level_i + 1 } else { level_i };
+            let mut depth = 0;
+            for reference in &self.references {
+                let child_depth = reference.level_depth(child_level);
+                hasher.update(child_depth.to_be_bytes());
+                depth = depth.max(child_depth + 1);
+            }
+            for reference in &self.references {
+                hasher.update(reference.level_hash(child_level));
+            }
+
+            hashes.push(hasher.finalize().into());
+            depths.push(depth);
+            hash_i += 1;
+        }
+
+        for level_i in 0..4 {
+            let index = hash_index(apply(self.level_mask, level_i));
+            if pruned && index != hash_index(self.level_mask) {
+                let hash_offset = 2 + index * 32;
+                let depth_offset = 2 + hash_index(self.level_mask) * 32 + index * 2;
+                self.hashes[level_i].copy_from_slice(&self.data[hash_offset..hash_offset + 32]);
+                self.depths[level_i] =
+                    u16::from_be_bytes([self.data[depth_offset], self.data[depth_offset + 1]]);
+            } else {
+                let index = if pruned { 0 } else { index };
+                self.hashes[level_i] = hashes[index];
+                self.depths[level_i] = depths[index];
+            }
+        }
+    }
+}
+
+fn level(mask: u8) -> usize {
+    8 - mask.leading_zeros() as usize
+}
+
+fn hash_index(mask: u8) -> usize {
+    mask.count_ones() as usize
+}
+
+fn apply(mask: u8, level: usize) -> u8 {
+    mask & ((1 << level) - 1)
+}
+
+fn is_significant(mask: u8, level: usize) -> bool {
+    level == 0 || (mask >> (level - 1)) & 1 == 1
+}
+
+/// Reads a serialized bag of cells and returns its roots
+pub fn read_boc(bytes: &[u8]) -> anyhow::Result<Vec<Arc<Cell>>> {
+    let mut reader = Reader::new(bytes);
+    let magic = reader.uint(4)?;
+    ensure!(magic == BOC_GENERIC_MAGIC as usize, "unknown boc magic {:#x}", magic);
+
+    let flags = reader.take(1)?[0];
+    let has_idx = flags & 0x80 != 0;
+    let has_crc32c = flags & 0x40 != 0;
+    let size = (flags & 0x07) as usize;
+    ensure!((1..=4).contains(&size), "invalid boc ref size {}", size);
+    let offset_size = reader.take(1)?[0] as usize;
+    ensure!((1..=8).contains(&offset_size), "invalid boc offset size {}", offset_size);
+
+    let cells_count = reader.uint(size)?;
+    let roots_count = reader.uint(size)?;
+    let _absent_count = reader.uint(size)?;
+    let total_size = reader.uint(offset_size)?;
+    ensure!(cells_count * 2 <= total_size, "boc cells count overflow");
+
+    let roots = (0..roots_count)
+        .map(|_| reader.uint(size))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if has_idx {
+        reader.take(cells_count * offset_size)?;
+    }
+    let data = reader.take(total_size)?;
+    if has_crc32c {
+        let checked = reader.position;
+        let crc = u32::from_le_bytes(reader.take(4)?.try_into()?);
+        ensure!(CRC32C.checksum(&bytes[..checked]) == crc, "boc crc32c mismatch");
+    }
+
+    let mut reader = Reader::new(data);
+    let mut raw_cells = Vec::with_capacity(cells_count);
+    for index in 0..cells_count {
+        let d1 = reader.take(1)?[0];
+        let d2 = reader.take(1)?[0];
+        let references_count = (d1 & 0x07) as usize;
+        ensure!(references_count <= 4, "invalid references count in cell {}", index);
+        if d1 & 0x10 != 0 {
+            reader.take((hash_index(d1 >> 5) + 1) * (32 + 2))?;
+        }
+
+        let data = reader.take((d2 as usize).div_ceil(2))?;
+        let bit_len = if d2 % 2 == 0 {
+            data.len() * 8
+        } else {
+            let last = data[data.len() - 1];
+            ensure!(last != 0, "missing completion tag in cell {}", index);
+            data.len() * 8 - last.trailing_zeros() as usize - 1
+        };
+
+        let references = (0..references_count)
+            .map(|_| {
+                let reference = reader.uint(size)?;
+                ensure!(
+                    reference > index && reference < cells_count,
+                    "invalid reference {} in cell {}",
+                    reference,
+                    index
+                );
+                Ok(reference)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        raw_cells.push((d1 & 0x08 != 0, data, bit_len, references));
+    }
+
+    let mut cells: Vec<Option<Arc<Cell>>> = vec![None; cells_count];
+    for (index, (exotic, data, bit_len, references)) in raw_cells.into_iter().enumerate().rev() {
+        let references = references
+            .into_iter()
+            .map(|reference| cells[reference].clone().ok_or_else(|| anyhow!("cell {} not built", reference)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        cells[index] = Some(Arc::new(Cell::new(exotic, data.to_vec(), bit_len, references)?));
+    }
+
+    roots
+        .into_iter()
+        .map(|root| {
+            cells
+                .get(root)
+                .cloned()
+                .flatten()
+                .ok_or_else(|| anyhow!("invalid boc root {}", root))
+        })
+        .collect()
+}
+
+/// Reads a bag of cells with exactly one root
+pub fn read_single_root(bytes: &[u8]) -> anyhow::Result<Arc<Cell>> {
+    let mut roots = read_boc(bytes)?;
+    ensure!(roots.len() == 1, "expected single root, got {}", roots.len());
+
+    Ok(roots.remove(0))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("unexpected end of boc"))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, len: usize) -> anyhow::Result<usize> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as usize))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CellSlice<'a> {
+    cell: &'a Cell,
+    bit: usize,
+    reference: usize,
+}
+
+impl<'a> CellSlice<'a> {
+    pub fn remaining_bits(&self) -> usize {
+        self.cell.bit_len - self.bit
+    }
+
+    pub fn remaining_references(&self) -> usize {
+        self.cell.references.len() - self.reference
+    }
+
+    pub fn load_bit(&mut self) -> anyhow::Result<bool> {
+        ensure!(self.bit < self.cell.bit_len, "cell underflow");
+        let bit = (self.cell.data[self.bit / 8] >> (7 - self.bit % 8)) & 1 == 1;
+        self.bit += 1;
+
+        Ok(bit)
+    }
+
+    pub fn skip(&mut self, bits: usize) -> anyhow::Result<()> {
+        ensure!(bits <= self.remaining_bits(), "cell underflow");
+        self.bit += bits;
+
+        Ok(())
+    }
+
+    pub fn load_uint(&mut self, bits: usize) -> anyhow::Result<u64> {
+        ensure!(bits <= 64, "can't load {} bits into u64", bits);
+        ensure!(bits <= self.remaining_bits(), "cell underflow");
+
+        let mut value = 0;
+        for _ in 0..bits {
+            value = value << 1 | self.load_bit()? as u64;
+        }
+
+        Ok(value)
+    }
+
+    pub fn load_int(&mut self, bits: usize) -> anyhow::Result<i64> {
+        let value = self.load_uint(bits)?;
+        if bits == 0 {
+            return Ok(0);
+        }
+
+        Ok(((value << (64 - bits)) as i64) >> (64 - bits))
+    }
+
+    /// `VarUInteger` with a `len_bits` wide byte length, e.g. 4 for `Grams`
+    pub fn load_var_uint(&mut self, len_bits: usize) -> anyhow::Result<u128> {
+        let len = self.load_uint(len_bits)? as usize;
+        ensure!(len <= 16, "var uint overflow");
+
+        let mut value = 0;
+        for _ in 0..len {
+            value = value << 8 | self.load_uint(8)? as u128;
+        }
+
+        Ok(value)
+    }
+
+    pub fn load_int256(&mut self) -> anyhow::Result<Int256> {
+        let mut value = Int256::default();
+        for byte in value.iter_mut() {
+            *byte = self.load_uint(8)? as u8;
+        }
+
+        Ok(value)
+    }
+
+    pub fn load_reference(&mut self) -> anyhow::Result<&'a Arc<Cell>> {
+        let reference = self.cell.reference(self.reference)?;
+        self.reference += 1;
+
+        Ok(reference)
+    }
+
+    /// `HashmapE n X`, returns the keys with slices positioned at the values
+    pub fn load_dict(&mut self, key_bits: usize) -> anyhow::Result<Vec<(Vec<bool>, CellSlice<'a>)>> {
+        if !self.load_bit()? {
+            return Ok(Vec::new());
+        }
+
+        dict_entries(self.load_reference()?, key_bits)
+    }
+
+    /// `Hashmap n X` stored inline, starting at the current position
+    pub fn parse_dict(self, key_bits: usize) -> anyhow::Result<Vec<(Vec<bool>, CellSlice<'a>)>> {
+        let mut entries = Vec::new();
+        walk_dict(self, key_bits, &mut Vec::with_capacity(key_bits), &mut entries)?;
+
+        Ok(entries)
+    }
+}
+
+/// Entries of a non-empty `Hashmap n X` rooted at `cell`. Pruned subtrees are skipped, since
+/// proofs only carry the branches they have to.
+pub fn dict_entries(cell: &Cell, key_bits: usize) -> anyhow::Result<Vec<(Vec<bool>, CellSlice<'_>)>> {
+    if cell.cell_type() == CellType::PrunedBranch {
+        return Ok(Vec::new());
+    }
+
+    cell.parser().parse_dict(key_bits)
+}
+
+/// Value of `key` in a non-empty `Hashmap n X` rooted at `cell`. Unlike [`dict_entries`] it fails on a pruned
+/// branch on the way to the key, so a proof can't hide the key by pruning it
+pub fn dict_get<'a>(cell: &'a Cell, key: &[bool]) -> anyhow::Result<Option<CellSlice<'a>>> {
+    let mut cell = cell;
+    let mut rest = key;
+    loop {
+        ensure!(cell.cell_type() != CellType::PrunedBranch, "hashmap key is in a pruned branch");
+
+        let mut slice = cell.parser();
+        let mut label = Vec::with_capacity(rest.len());
+        let len = load_label(&mut slice, rest.len(), &mut label)?;
+        if label[..] != rest[..len] {
+            return Ok(None);
+        }
+        if len == rest.len() {
+            return Ok(Some(slice));
+        }
+
+        cell = cell.reference(rest[len] as usize)?.as_ref();
+        rest = &rest[len + 1..];
+    }
+}
+
+/// Bits of `bytes`, most significant first, e.g. the key of an account id
+pub fn bytes_to_key(bytes: &[u8]) -> Vec<bool> {
+    bytes.iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1 == 1))
+        .collect()
+}
+
+fn walk_dict<'a>(
+    mut slice: CellSlice<'a>,
+    key_bits: usize,
+    key: &mut Vec<bool>,
+    entries: &mut Vec<(Vec<bool>, CellSlice<'a>)>,
+) -> anyhow::Result<()> {
+    let prefix = key.len();
+    let label = load_label(&mut slice, key_bits, key)?;
+    if label == key_bits {
+        entries.push((key.clone(), slice));
+    } else {
+        for side in [false, true] {
+            let child = slice.load_reference()?;
+            if child.cell_type() == CellType::PrunedBranch {
+                continue;
+            }
+
+            key.push(side);
+            walk_dict(child.parser(), key_bits - label - 1, key, entries)?;
+            key.pop();
+        }
+    }
+    key.truncate(prefix);
+
+    Ok(())
+}
+
+fn load_label(slice: &mut CellSlice, max: usize, key: &mut Vec<bool>) -> anyhow::Result<usize> {
+    let len_bits = (usize::BITS - max.leading_zeros()) as usize;
+    let len = if !slice.load_bit()? {
+        let mut len = 0;
+        while slice.load_bit()? {
+            len += 1;
+        }
+        ensure!(len <= max, "hashmap label overflow");
+        for _ in 0..len {
+            key.push(slice.load_bit()?);
+        }
+        len
+    } else if !slice.load_bit()? {
+        let len = slice.load_uint(len_bits)? as usize;
+        ensure!(len <= max, "hashmap label overflow");
+        for _ in 0..len {
+            key.push(slice.load_bit()?);
+        }
+        len
+    } else {
+        let bit = slice.load_bit()?;
+        let len = slice.load_uint(len_bits)? as usize;
+        ensure!(len <= max, "hashmap label overflow");
+        key.extend(std::iter::repeat(bit).take(len));
+        len
+    };
+
+    Ok(len)
+}
+
+/// Big-endian value of up to 64 key bits
+pub fn key_to_uint(key: &[bool]) -> u64 {
+    key.iter().fold(0, |value, bit| value << 1 | *bit as u64)
+}
+
+/// Bytes of a 256 bit key, e.g. an account id or a public key
+pub fn key_to_int256(key: &[bool]) -> anyhow::Result<Int256> {
+    ensure!(key.len() == 256, "expected a 256 bit key, got {} bits", key.len());
+
+    Ok(key.chunks(8)
+        .map(|byte| byte.iter().fold(0, |value, bit| value << 1 | *bit as u8))
+        .collect::<Vec<_>>()
+        .try_into()
+        .expect("key is 256 bits"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_cell_hash_test() {
+        let cell = read_single_root(&hex::decode("b5ee9c724101010100020000004cacb9cd").unwrap()).unwrap();
+
+        assert_eq!(cell.bit_len(), 0);
+        assert!(cell.references().is_empty());
+        assert_eq!(
+            hex::encode(cell.hash()),
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7"
+        );
+    }
+
+    #[test]
+    fn invalid_crc_test() {
+        let result = read_boc(&hex::decode("b5ee9c724101010100020000004cacb9ce").unwrap());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn slice_reads_bits_test() {
+        let cell = Cell::new(false, vec![0b1011_0000, 0xff], 12, vec![]).unwrap();
+        let mut slice = cell.parser();
+
+        assert!(slice.load_bit().unwrap());
+        assert_eq!(slice.load_int(3).unwrap(), 3);
+        assert_eq!(slice.load_uint(8).unwrap(), 0x0f);
+        assert_eq!(slice.remaining_bits(), 0);
+        assert!(slice.load_bit().is_err());
+    }
+}
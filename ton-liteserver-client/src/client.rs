@@ -1,64 +1,272 @@
 use std::collections::HashMap;
-use std::future::Future;
+use std::future::{Future, IntoFuture};
 use std::marker::PhantomData;
 use std::net::SocketAddrV4;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
-use tower::Service;
-use adnl_tcp::client::{Client, ServerKey};
-use futures::{ready, SinkExt, StreamExt};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tower::{Service, ServiceExt};
+use adnl_tcp::client::{Client, HandshakeError, ServerKey};
+use anyhow::anyhow;
+use async_stream::try_stream;
+use futures::{ready, FutureExt, SinkExt, Stream, StreamExt, TryStreamExt};
+use futures::future::BoxFuture;
 use pin_project::pin_project;
 use rand::random;
 use thiserror::Error;
 use tokio::select;
+use tokio::net::TcpSocket;
 use tokio::sync::mpsc;
-use tokio::sync::oneshot;
-use tokio::time::MissedTickBehavior;
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio::sync::{oneshot, watch, OnceCell};
+use tokio::time::{MissedTickBehavior, Sleep};
+use tokio_stream::wrappers::{UnboundedReceiverStream, WatchStream};
 use tokio_util::sync::{CancellationToken, DropGuard};
+use tokio_util::task::TaskTracker;
+use tracing::Span;
 use adnl_tcp::packet::Packet;
-use adnl_tcp::connection::Connection;
+use adnl_tcp::connection::{Connection, PacketTooLarge};
 use adnl_tcp::ping::{is_pong_packet, ping_packet};
 use adnl_tcp::deserializer::{DeserializeBoxed, from_bytes_boxed};
 use adnl_tcp::serializer::to_bytes_boxed;
-use crate::request::Requestable;
-use crate::tl::{AdnlMessageAnswer, AdnlMessageQuery, Bytes, Int256, LiteServerError, LiteServerQuery};
+use crate::account::{find_by_in_msg, AccountTransaction, LastTransaction, TRANSACTIONS_PAGE};
+use crate::cell::read_single_root;
+use crate::config::LiteServerDesc;
+use crate::answered::Answered;
+use crate::lookup;
+use crate::header;
+use crate::capabilities::{Capabilities, CAP_PROOF_CHAINS, CAP_RUN_SMC_METHOD};
+use crate::request::{Described, Requestable, WaitSeqno};
+use crate::tl::{AdnlMessageAnswer, AdnlMessageQuery, Bytes, Int256, LiteServerAccountId, LiteServerAccountState, LiteServerAllShardsInfo, LiteServerBlockData, LiteServerBlockHeader, LiteServerBlockState, LiteServerBlockTransactions, LiteServerConfigInfo, LiteServerError, LiteServerGetAccountState, LiteServerGetAllShardsInfo, LiteServerGetBlock, LiteServerGetBlockHeader, LiteServerGetBlockProof, LiteServerGetConfigAll, LiteServerGetConfigParams, LiteServerGetLibraries, LiteServerGetMasterchainInfo, LiteServerGetMasterchainInfoExt, LiteServerGetVersion, LiteServerGetOneTransaction, LiteServerGetShardBlockProof, LiteServerGetShardInfo, LiteServerGetState, LiteServerGetTime, LiteServerGetTransactions, LiteServerGetValidatorStats, LiteServerLibraryResult, LiteServerListBlockTransactions, LiteServerLookupBlock, LiteServerMasterchainInfo, LiteServerMasterchainInfoExt, LiteServerNonfinalGetValidatorGroups, LiteServerNonfinalValidatorGroups, LiteServerPartialBlockProof, LiteServerQuery, LiteServerRunMethodResult, LiteServerRunSmcMethod, LiteServerSendMessage, LiteServerSendMsgStatus, LiteServerShardBlockProof, LiteServerShardInfo, LiteServerTransactionId, LiteServerTransactionId3, LiteServerTransactionInfo, LiteServerTransactionList, LiteServerValidatorStats, LiteServerVersion, TonNodeBlockId, TonNodeBlockIdExt};
+use crate::paging::{Page, Pager};
+use crate::tracker::MasterchainLastBlockTracker;
+use crate::validator_stats::CreatorStats;
+use crate::smc::MethodId;
 
 pub type RequestId = Int256;
 
-#[derive(Error, Debug)]
+/// Clock offset above which the liteserver time is considered unreliable, e.g. for lookups by utime
+const MAX_CLOCK_OFFSET: Duration = Duration::from_secs(5);
+
+/// Age of the last masterchain block above which [`LiteServerClient::healthcheck`] reports the server out of sync
+const MAX_SYNC_LAG: Duration = Duration::from_secs(60);
+
+/// Interval of the account checks of [`LiteServerClient::send_message_and_wait`]
+const MESSAGE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bound of the bytes an answer packet of `liteServer.blockState` adds to the state: nonce, checksum,
+/// the answer with its query id, the block id and the hashes, the length prefixes and paddings
+const STATE_ANSWER_OVERHEAD: usize = 262;
+
+/// Liteserver error code for data the server doesn't have (yet), e.g. a block it hasn't synced
+const LITESERVER_NOT_READY: i32 = 651;
+/// Liteserver error code for a wait that ran out of time, e.g. of `WaitSeqno`
+const LITESERVER_TIMEOUT: i32 = 652;
+/// Liteserver error code for a query the server dropped, e.g. while shutting down
+const LITESERVER_CANCELLED: i32 = 653;
+/// Liteserver error code for a query the server doesn't know, e.g. a method newer than the server
+const LITESERVER_UNKNOWN_QUERY: i32 = -400;
+
+#[derive(Error, Debug, Clone)]
 pub enum Error {
-    #[error("LiteServer error: {0}")]
-    LiteServerError(#[from] LiteServerError),
-    #[error("Deserialize error")]
-    Deserialize,
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("LiteServer error: Error code: {code}, message: {message:?}")]
+    LiteServerError { code: i32, message: String },
+    #[error("Decode error")]
+    Decode,
+    #[error("Transport error: {0}")]
+    Transport(#[from] TransportError),
+    #[error("Timeout")]
+    Timeout,
+    #[error("No available liteserver")]
+    NoAvailableBackend,
+    #[error("Invalid request: {0}")]
+    InvalidRequest(&'static str),
+    /// `size` is the length of the answer packet when the state is rejected by its length prefix
+    #[error("State of {size} bytes is larger than {max_size} bytes")]
+    StateTooLarge { size: usize, max_size: usize },
+    #[error("Rate limit exceeded")]
+    RateLimited,
+    #[error("Unsupported by the liteserver: {0}")]
+    Unsupported(&'static str),
+    #[error("Answer is for block {answered} instead of {requested}")]
+    BlockMismatch { requested: Box<TonNodeBlockIdExt>, answered: Box<TonNodeBlockIdExt> },
+    #[error("Data doesn't hash to block {0}")]
+    HashMismatch(TonNodeBlockIdExt),
+    #[error("LiteServer timeout: {0}")]
+    ServerTimeout(String),
+    #[error("LiteServer cancelled the query: {0}")]
+    Cancelled(String),
+    #[error("Peer doesn't hold the liteserver key")]
+    IdentityMismatch,
+}
+
+impl Error {
+    /// Code of the `liteServer.error` the error was decoded from, see `From<LiteServerError>` for the mapping
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Error::NotFound(_) => Some(LITESERVER_NOT_READY),
+            Error::ServerTimeout(_) => Some(LITESERVER_TIMEOUT),
+            Error::Cancelled(_) => Some(LITESERVER_CANCELLED),
+            Error::LiteServerError { code, .. } => Some(*code),
+            _ => None
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum TransportError {
     #[error("Inner channel is closed")]
     ChannelClosed,
     #[error("Response oneshot channel is closed")]
     OneshotClosed,
+    #[error("Connection to the liteserver failed")]
+    ConnectionFailed,
+}
+
+/// Codes of the liteserver `ErrorCode`: 651 `notready` is `NotFound`, 652 `timeout` is `ServerTimeout` and
+/// 653 `cancelled` is `Cancelled`. Other codes, e.g. -400 of an unknown query, are kept in `LiteServerError`
+impl From<LiteServerError> for Error {
+    fn from(error: LiteServerError) -> Self {
+        match error.code {
+            LITESERVER_NOT_READY => Error::NotFound(error.message),
+            LITESERVER_TIMEOUT => Error::ServerTimeout(error.message),
+            LITESERVER_CANCELLED => Error::Cancelled(error.message),
+            code => Error::LiteServerError { code, message: error.message }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LiteServerClient {
+    addr: SocketAddrV4,
+    server_key: ServerKey,
+    socket_options: SocketOptions,
     tx: mpsc::UnboundedSender<ClientActorMessage>,
+    connection_state: ConnectionState,
+    request_timeout: Option<Duration>,
+    capabilities: Arc<OnceCell<Capabilities>>,
+    masterchain_info: Arc<tokio::sync::Mutex<Option<(Instant, LiteServerMasterchainInfo)>>>,
+    cancellation_token: CancellationToken,
+    actor: TaskTracker,
     drop_guard: Arc<DropGuard>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub max_attempts: usize,
+    pub delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 3, delay: Duration::from_secs(1) }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn disabled() -> Self {
+        Self { max_attempts: 0, ..Default::default() }
+    }
+}
+
+/// Answer of [`LiteServerClient::healthcheck`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthStatus {
+    pub latency: Duration,
+    /// Age of the last masterchain block by the server clock
+    pub lag: Duration,
+    /// The lag is within `MAX_SYNC_LAG`, a minute
+    pub synced: bool,
+}
+
+/// Options of the socket a client connects from, reconnects use them as well
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Local address to bind to, e.g. to pick the interface in a multi-homed setup. The OS picks one when `None`
+    pub local_addr: Option<SocketAddrV4>,
+    /// `SO_REUSEADDR`, lets a fixed local port be bound again while the previous connection is in `TIME_WAIT`
+    pub reuse_address: bool,
+}
+
+/// Keeps an idle connection warm with a `liteServer.getTime`, NATs and firewalls may silently drop idle ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepalivePolicy {
+    /// Time without queries and answers after which a ping is sent, it has to be answered within the same time
+    pub interval: Duration,
+    /// A ping left unanswered drops the connection and reconnects, otherwise it is only logged
+    pub reconnect: bool,
+}
+
+impl Default for KeepalivePolicy {
+    fn default() -> Self {
+        Self { interval: Duration::from_secs(30), reconnect: true }
+    }
+}
+
+/// Whether the actor is connected, `false` while it reconnects. Every clone waits for the changes on its own
+#[derive(Debug)]
+struct ConnectionState {
+    connected: watch::Receiver<bool>,
+    changes: WatchStream<bool>,
+}
+
+impl ConnectionState {
+    fn new(connected: watch::Receiver<bool>) -> Self {
+        Self { changes: WatchStream::from_changes(connected.clone()), connected }
+    }
+
+    /// Ready with `false` once the actor is gone, having given up reconnecting
+    fn poll_connected(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        loop {
+            if *self.connected.borrow() {
+                return Poll::Ready(true)
+            }
+            if ready!(self.changes.poll_next_unpin(cx)).is_none() {
+                return Poll::Ready(false)
+            }
+        }
+    }
+}
+
+impl Clone for ConnectionState {
+    fn clone(&self) -> Self {
+        Self::new(self.connected.clone())
+    }
+}
+
 struct ClientActor {
+    addr: SocketAddrV4,
+    server_key: ServerKey,
+    socket_options: SocketOptions,
+    reconnect_policy: ReconnectPolicy,
+    keepalive: Option<KeepalivePolicy>,
     connection: Connection,
     receiver: mpsc::UnboundedReceiver<ClientActorMessage>,
+    connected: watch::Sender<bool>,
     cancellation_token: CancellationToken
 }
 
 impl ClientActor {
-    pub fn new(connection: Connection, receiver: mpsc::UnboundedReceiver<ClientActorMessage>, cancellation_token: CancellationToken) -> Self {
-        Self { connection, receiver, cancellation_token }
+    pub fn new(addr: SocketAddrV4, server_key: ServerKey, socket_options: SocketOptions, reconnect_policy: ReconnectPolicy, connection: Connection, receiver: mpsc::UnboundedReceiver<ClientActorMessage>, cancellation_token: CancellationToken) -> Self {
+        let (connected, _) = watch::channel(true);
+
+        Self { addr, server_key, socket_options, reconnect_policy, keepalive: None, connection, receiver, connected, cancellation_token }
     }
 
-    pub fn run(mut self) {
-        tokio::spawn(async move {
+    fn connection_state(&self) -> ConnectionState {
+        ConnectionState::new(self.connected.subscribe())
+    }
+
+    pub fn with_keepalive(mut self, keepalive: Option<KeepalivePolicy>) -> Self {
+        self.keepalive = keepalive;
+
+        self
+    }
+
+    pub fn run(mut self, tasks: &TaskTracker) {
+        tasks.spawn(async move {
             let mut responses: HashMap<RequestId, oneshot::Sender<Bytes>> = Default::default();
 
             let mut interval = tokio::time::interval(Duration::from_secs(5));
@@ -67,48 +275,103 @@ impl ClientActor {
             let stream = UnboundedReceiverStream::new(self.receiver);
             let mut stream = tokio_stream::StreamExt::timeout_repeating(stream, interval);
 
+            let mut keepalive = self.keepalive.map(|policy| (policy, Box::pin(tokio::time::sleep(policy.interval))));
+            // query id and answer of the keepalive ping in flight
+            let mut ping: Option<(RequestId, oneshot::Receiver<Bytes>)> = None;
+
             loop {
-                select! {
+                let result = select! {
                     _ = self.cancellation_token.cancelled() => {
                         tracing::error!("LiteServerClient cancelled");
                         break;
                     },
-                    Some(response) = self.connection.next() => {
+                    response = self.connection.next() => {
                         match response {
-                            Ok(packet) if is_pong_packet(&packet) => {
+                            Some(Ok(packet)) if is_pong_packet(&packet) => {
                                 tracing::trace!("pong packet received");
+
+                                Ok(())
                             },
-                            Ok(packet) => {
+                            Some(Ok(packet)) => {
                                 tracing::trace!(?packet);
-                                let adnl_answer = from_bytes_boxed::<AdnlMessageAnswer>(&packet.data)
-                                    .expect("expect adnl answer packet");
-
-                                if let Some(oneshot) = responses.remove(&adnl_answer.query_id) {
-                                    oneshot
-                                        .send(adnl_answer.answer)
-                                        .expect("expect oneshot alive");
+                                // a malformed answer leaves the stream in an unknown state, the connection is dropped
+                                match from_bytes_boxed::<AdnlMessageAnswer>(&packet.data) {
+                                    Ok(adnl_answer) => {
+                                        if let Some(oneshot) = responses.remove(&adnl_answer.query_id) {
+                                            if oneshot.send(adnl_answer.answer).is_err() {
+                                                tracing::trace!(query_id = ?adnl_answer.query_id, "response receiver dropped");
+                                            }
+                                        }
+                                        put_off(&mut keepalive);
+
+                                        Ok(())
+                                    },
+                                    Err(error) => Err(error.context("malformed adnl answer packet"))
                                 }
                             }
-                            Err(error) => {
-                                tracing::error!(error = ?error, "reading error");
-
-                                return
-                            }
+                            Some(Err(error)) => Err(error),
+                            None => Err(anyhow!("connection closed"))
                         }
                     },
                     Some(request) = stream.next() => {
                         match request {
                             Ok(ClientActorMessage::Query { query, oneshot }) => {
                                 responses.insert(query.query_id, oneshot);
+                                put_off(&mut keepalive);
 
                                 let data = to_bytes_boxed(&query);
-                                self.connection.send(Packet::new(data)).await.expect("expect to send adnl query packet")
+                                self.connection.send(Packet::new(data)).await
                             }
                             Err(_) => {
-                                self.connection.send(ping_packet()).await.expect("expect to send ping packet")
+                                self.connection.send(ping_packet()).await
                             }
                         }
+                    },
+                    _ = async { keepalive.as_mut().unwrap().1.as_mut().await }, if keepalive.is_some() => {
+                        let policy = keepalive.as_ref().unwrap().0;
+                        put_off(&mut keepalive);
+
+                        let unanswered = match ping.take() {
+                            Some((query_id, mut answer)) => answer.try_recv().is_err().then_some(query_id),
+                            None => None
+                        };
+                        if let Some(query_id) = unanswered {
+                            responses.remove(&query_id);
+                            tracing::warn!(interval = ?policy.interval, "keepalive ping unanswered");
+                        }
+
+                        if unanswered.is_some() && policy.reconnect {
+                            Err(anyhow!("keepalive ping unanswered"))
+                        } else {
+                            let query = keepalive_query();
+                            let (tx, rx) = oneshot::channel();
+                            responses.insert(query.query_id, tx);
+                            ping = Some((query.query_id, rx));
+
+                            self.connection.send(Packet::new(to_bytes_boxed(&query))).await
+                        }
+                    }
+                };
+
+                if let Err(error) = result {
+                    tracing::error!(error = ?error, "connection error");
+
+                    // in-flight queries are lost with the connection, dropping their senders fails them with OneshotClosed
+                    responses.clear();
+                    self.connected.send_replace(false);
+
+                    let connection = select! {
+                        _ = self.cancellation_token.cancelled() => None,
+                        connection = reconnect(self.addr, &self.server_key, &self.socket_options, &self.reconnect_policy) => connection
+                    };
+
+                    match connection {
+                        Some(connection) => self.connection = connection,
+                        None => break
                     }
+                    self.connected.send_replace(true);
+                    ping = None;
+                    put_off(&mut keepalive);
                 }
             }
 
@@ -117,222 +380,2258 @@ impl ClientActor {
     }
 }
 
-enum ClientActorMessage {
-    Query { query: AdnlMessageQuery, oneshot: oneshot::Sender<Bytes> },
+/// Puts the keepalive ping off by a whole interval
+fn put_off(keepalive: &mut Option<(KeepalivePolicy, Pin<Box<Sleep>>)>) {
+    if let Some((policy, sleep)) = keepalive {
+        sleep.as_mut().reset(tokio::time::Instant::now() + policy.interval);
+    }
 }
 
-impl LiteServerClient {
-    pub async fn connect(addr: SocketAddrV4, server_key: &ServerKey) -> anyhow::Result<Self> {
-        let inner = Client::connect(addr, server_key).await?;
-        let cancel_token = CancellationToken::new();
-        let (tx, rx) = mpsc::unbounded_channel::<ClientActorMessage>();
-        ClientActor::new(inner, rx, cancel_token.clone()).run();
+fn keepalive_query() -> AdnlMessageQuery {
+    let query = to_bytes_boxed(&LiteServerQuery { data: to_bytes_boxed(&LiteServerGetTime::default()) });
+
+    AdnlMessageQuery { query_id: random(), query }
+}
 
-        Ok(Self { tx, drop_guard: Arc::new(cancel_token.drop_guard()) })
+async fn connect(addr: SocketAddrV4, server_key: &ServerKey, options: &SocketOptions) -> anyhow::Result<Connection> {
+    let socket = TcpSocket::new_v4()?;
+    socket.set_reuseaddr(options.reuse_address)?;
+    if let Some(local_addr) = options.local_addr {
+        socket.bind(local_addr.into())?;
     }
+    let stream = socket.connect(addr.into()).await?;
+
+    Client::handshake(stream, server_key).await.map_err(|error| match error.downcast_ref::<HandshakeError>() {
+        Some(HandshakeError::MissedEmptyPacket) => Error::IdentityMismatch.into(),
+        None => error
+    })
 }
 
-impl<R> Service<R> for LiteServerClient where R: Requestable {
-    type Response = R::Response;
-    type Error = Error;
-    type Future = ResponseFuture<R::Response>;
+async fn reconnect(addr: SocketAddrV4, server_key: &ServerKey, options: &SocketOptions, policy: &ReconnectPolicy) -> Option<Connection> {
+    for attempt in 1 ..= policy.max_attempts {
+        tokio::time::sleep(policy.delay).await;
 
-    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if self.tx.is_closed() {
-            return Poll::Ready(Err(Error::ChannelClosed))
-        }
+        match connect(addr, server_key, options).await {
+            Ok(connection) => {
+                tracing::info!(attempt, "reconnected");
 
-        Poll::Ready(Ok(()))
+                return Some(connection)
+            },
+            Err(error) => {
+                tracing::warn!(attempt, error = ?error, "reconnect failed");
+            }
+        }
     }
 
-    fn call(&mut self, req: R) -> Self::Future {
-        let data = to_bytes_boxed(&req);
+    None
+}
 
-        let query = LiteServerQuery { data };
-        let query = to_bytes_boxed(&query);
+enum ClientActorMessage {
+    Query { query: AdnlMessageQuery, oneshot: oneshot::Sender<Bytes> },
+}
 
-        let query = AdnlMessageQuery { query_id: random(), query };
+impl LiteServerClient {
+    pub async fn connect(addr: SocketAddrV4, server_key: &ServerKey) -> anyhow::Result<Self> {
+        Self::builder(addr, server_key).build().await
+    }
 
-        let (tx, rx) = oneshot::channel();
+    /// Tries `addrs` in order and keeps the first one that connects, reconnects go to the same address
+    pub async fn connect_any(addrs: &[SocketAddrV4], server_key: &ServerKey) -> anyhow::Result<Self> {
+        let mut last_error = anyhow!("no liteserver address given");
+        for addr in addrs {
+            match Self::connect(*addr, server_key).await {
+                Ok(client) => return Ok(client),
+                Err(error) => {
+                    tracing::warn!(addr = %addr, error = ?error, "connect failed, trying the next address");
 
-        if self.tx.send(ClientActorMessage::Query { query, oneshot: tx }).is_err() {
-            return ResponseFuture::failed(Error::ChannelClosed);
+                    last_error = error;
+                }
+            }
         }
 
-        ResponseFuture::new(rx, self.drop_guard.clone())
+        Err(last_error)
     }
-}
 
+    pub async fn from_config_entry(liteserver: &LiteServerDesc) -> anyhow::Result<Self> {
+        Self::connect(liteserver.addr(), &liteserver.server_key()?).await
+    }
 
-#[pin_project(project = ResponseStateProj)]
-pub enum ResponseState {
-    Failed { error: Option<Error> },
-    Rx {
-        #[pin]
-        rx: oneshot::Receiver<Bytes>,
-        drop_guard: Arc<DropGuard>
+    pub fn builder(addr: SocketAddrV4, server_key: &ServerKey) -> LiteServerClientBuilder {
+        LiteServerClientBuilder::new(addr, server_key)
     }
-}
 
-#[pin_project]
-pub struct ResponseFuture<Response> {
-    #[pin]
-    state: ResponseState,
-    _phantom: PhantomData<Response>,
+    /// Stops the connection actor and returns once the socket is closed. Requests in flight fail with
+    /// `TransportError::OneshotClosed`, clones of the client fail every later request with `TransportError::ChannelClosed`
+    pub async fn close(self) {
+        self.cancellation_token.cancel();
+        self.actor.wait().await;
+    }
 }
 
-impl<Response> ResponseFuture<Response> {
-    fn new(rx: oneshot::Receiver<Bytes>, drop_guard: Arc<DropGuard>) -> Self {
-        Self { state: ResponseState::Rx { rx, drop_guard }, _phantom: PhantomData }
+impl LiteServerClient {
+    /// State of `account` at the block `id`, the answer is checked to be for that block
+    pub async fn get_account_state(&self, id: TonNodeBlockIdExt, account: impl Into<LiteServerAccountId>) -> Result<Answered<LiteServerAccountState>, Error> {
+        let state = self.clone()
+            .oneshot(LiteServerGetAccountState { id: id.clone(), account: account.into() })
+            .await?;
+
+        Answered::checked(id, state.id.clone(), state)
     }
 
-    fn failed(error: Error) -> Self {
-        Self { state: ResponseState::Failed { error: Some(error) }, _phantom: PhantomData }
+    /// State of `account` at the last masterchain block, the one `tracker` follows when it has one and the one
+    /// the server reports otherwise
+    pub async fn account_state_at_tip(&self, account: impl Into<LiteServerAccountId>, tracker: Option<&MasterchainLastBlockTracker>) -> Result<Answered<LiteServerAccountState>, Error> {
+        let last = match tracker.and_then(|tracker| tracker.last()) {
+            Some(last) => last,
+            None => self.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last
+        };
+
+        self.get_account_state(last, account).await
     }
-}
 
-impl<Response> Future for ResponseFuture<Response> where Response: DeserializeBoxed {
-    type Output = Result<Response, Error>;
+    /// Last masterchain block along with the server version, capabilities and clock
+    pub async fn get_masterchain_info_ext(&self) -> Result<LiteServerMasterchainInfoExt, Error> {
+        self.clone()
+            .oneshot(LiteServerGetMasterchainInfoExt { mode: 0 })
+            .await
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.project();
+    /// Version, capabilities and the current time of the server, see [`Self::capabilities`] for the cached ones
+    pub async fn get_version(&self) -> Result<LiteServerVersion, Error> {
+        self.clone()
+            .oneshot(LiteServerGetVersion::default())
+            .await
+    }
 
-        return match this.state.as_mut().project() {
-            ResponseStateProj::Failed { error } => {
-                Poll::Ready(Err(error.take().expect("polled after error")))
-            },
-            ResponseStateProj::Rx { rx, .. } => return match ready!(rx.poll(cx)) {
-                Ok(response) => {
-                    let response = from_bytes_boxed::<Result<Response, LiteServerError>>(&response)
-                        .map_err(|_| Error::Deserialize)?
-                        .map_err(Error::LiteServerError)?;
+    /// Last masterchain block, asked for again only when the one at hand is older than `max_age`. Concurrent
+    /// callers share a single request, shared by the clones of the client. For following the chain see [`crate::tracker`]
+    pub async fn get_masterchain_info_cached(&self, max_age: Duration) -> Result<LiteServerMasterchainInfo, Error> {
+        let mut cached = self.masterchain_info.lock().await;
+        if let Some((fetched_at, info)) = cached.as_ref() {
+            if fetched_at.elapsed() < max_age {
+                return Ok(info.clone());
+            }
+        }
 
-                    Poll::Ready(Ok(response))
-                }
-                Err(_) => {
-                    Poll::Ready(Err(Error::OneshotClosed))
+        let info = self.clone()
+            .oneshot(LiteServerGetMasterchainInfo::default())
+            .await?;
+        *cached = Some((Instant::now(), info.clone()));
+
+        Ok(info)
+    }
+
+    /// Sha256 of the `lite_api.tl` the client is generated from, in hex
+    pub fn tl_schema_hash() -> &'static str {
+        crate::tl::TL_SCHEMA_HASH
+    }
+
+    /// Version and capabilities of the server, asked for once per client. Capabilities the schema doesn't have
+    /// are logged, the server may answer with types the client mis-decodes
+    pub async fn capabilities(&self) -> Result<Capabilities, Error> {
+        self.capabilities
+            .get_or_try_init(|| async {
+                match self.get_masterchain_info_ext().await {
+                    Ok(info) => {
+                        let capabilities = Capabilities::from(&info);
+                        if capabilities.unknown() != 0 {
+                            tracing::warn!(version = capabilities.version, unknown = capabilities.unknown(), tl_schema_hash = Self::tl_schema_hash(), "liteserver is newer than the generated TL schema");
+                        }
+
+                        Ok(capabilities)
+                    },
+                    // servers without `liteServer.getMasterchainInfoExt` predate every capability, other errors are asked again
+                    Err(Error::LiteServerError { code: LITESERVER_UNKNOWN_QUERY, .. }) => Ok(Capabilities::default()),
+                    Err(error) => Err(error)
                 }
-            }
+            })
+            .await
+            .copied()
+    }
+
+    /// Milliseconds the server clock is ahead of the local one, negative when it's behind.
+    /// The server answered somewhere within the round trip, so its time is matched against the middle of it
+    pub async fn server_time_offset(&self) -> Result<i64, Error> {
+        let sent = SystemTime::now();
+        let started = Instant::now();
+        let now = self.clone().oneshot(LiteServerGetTime::default()).await?.now;
+        let rtt = started.elapsed();
+
+        let local = sent.duration_since(UNIX_EPOCH).unwrap_or_default() + rtt / 2;
+        // `now` is truncated to seconds, the middle of that second is the best guess
+        let offset = now as i64 * 1000 + 500 - local.as_millis() as i64;
+        if offset.unsigned_abs() > MAX_CLOCK_OFFSET.as_millis() as u64 {
+            tracing::warn!(offset, rtt = ?rtt, "liteserver clock is off");
         }
+
+        Ok(offset)
     }
-}
 
+    /// Round trip of a `liteServer.getMasterchainInfoExt` and how far the server is behind the chain, for readiness probes
+    pub async fn healthcheck(&self) -> Result<HealthStatus, Error> {
+        let started = Instant::now();
+        let info = self.get_masterchain_info_ext().await?;
+        let latency = started.elapsed();
 
-#[cfg(test)]
-mod tests {
-    use std::net::Ipv4Addr;
-    use base64::Engine;
-    use tower::ServiceExt;
-    use tracing_test::traced_test;
-    use std::time::SystemTime;
-    use std::time::UNIX_EPOCH;
-    use crate::request::WaitSeqno;
-    use crate::tl::{LiteServerGetAllShardsInfo, LiteServerGetBlockProof, LiteServerGetMasterchainInfo, LiteServerGetMasterchainInfoExt, LiteServerGetVersion};
-    use super::*;
+        // both times are of the server clock, a server with a clock off still reports its own lag
+        let lag = Duration::from_secs(info.now.saturating_sub(info.last_utime).max(0) as u64);
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_get_masterchain_info() -> anyhow::Result<()> {
-        let client = provided_client().await?;
+        Ok(HealthStatus { latency, lag, synced: lag <= MAX_SYNC_LAG })
+    }
 
-        let response = client.oneshot(LiteServerGetMasterchainInfo::default()).await?;
+    /// Block BoC of `id`, checked to be that block, see [`LiteServerBlockData::check`]. [`LiteServerBlockData::parse`] decodes it
+    pub async fn get_block(&self, id: TonNodeBlockIdExt) -> Result<LiteServerBlockData, Error> {
+        let block = self.clone()
+            .oneshot(LiteServerGetBlock { id: id.clone() })
+            .await?;
+        block.check(&id)?;
 
-        assert_eq!(response.last.workchain, -1);
-        assert_eq!(response.last.shard, -9223372036854775808);
+        Ok(block)
+    }
 
-        Ok(())
+    /// Shard configuration at the masterchain block `id`, use [`LiteServerAllShardsInfo::shards`] to decode it
+    pub async fn get_all_shards_info(&self, id: TonNodeBlockIdExt) -> Result<LiteServerAllShardsInfo, Error> {
+        self.clone()
+            .oneshot(LiteServerGetAllShardsInfo { id })
+            .await
     }
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_wait_seqno_info() -> anyhow::Result<()> {
-        let mut client = provided_client().await?;
-        let current = (&mut client).oneshot(LiteServerGetMasterchainInfo::default()).await?;
+    /// Latest block of the shard `shard` of `workchain` as of the masterchain block `id`. Unless `exact`, a shard
+    /// that has been split or merged is resolved to the shard containing it
+    pub async fn get_shard_info(&self, id: TonNodeBlockIdExt, workchain: i32, shard: i64, exact: bool) -> Result<LiteServerShardInfo, Error> {
+        self.clone()
+            .oneshot(LiteServerGetShardInfo { id, workchain, shard, exact: exact.into() })
+            .await
+    }
 
-        let actual = (&mut client).oneshot(WaitSeqno::new(LiteServerGetMasterchainInfo::default(), current.last.seqno + 1)).await?;
+    /// Whole masterchain config at the block `id`, use [`LiteServerConfigInfo::config_params`] to read params
+    pub async fn get_config_all(&self, id: TonNodeBlockIdExt) -> Result<Answered<LiteServerConfigInfo>, Error> {
+        let config = self.clone()
+            .oneshot(LiteServerGetConfigAll { mode: 0, id: id.clone() })
+            .await?;
 
-        assert_eq!(actual.last.workchain, -1);
-        assert_eq!(actual.last.shard, -9223372036854775808);
-        assert!(current.last.seqno < actual.last.seqno);
-        Ok(())
+        Answered::checked(id, config.id.clone(), config)
     }
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_get_all_shards_info() -> anyhow::Result<()> {
-        let mut client = provided_client().await?;
-        let response = (&mut client).oneshot(LiteServerGetMasterchainInfo::default()).await?;
+    /// Only the `params` of the masterchain config at the block `id`, params missing from the config are silently left out
+    pub async fn get_config_params(&self, id: TonNodeBlockIdExt, params: &[i32]) -> Result<Answered<LiteServerConfigInfo>, Error> {
+        let config = self.clone()
+            .oneshot(LiteServerGetConfigParams { mode: 0, id: id.clone(), param_list: params.to_vec() })
+            .await?;
 
-        let response = (&mut client).oneshot(LiteServerGetAllShardsInfo {
-            id: response.last
-        }).await?;
+        Answered::checked(id, config.id.clone(), config)
+    }
 
-        assert_eq!(response.id.workchain, -1);
-        assert_eq!(response.id.shard, -9223372036854775808);
+    /// Block of the shard `id` selected by `mode`, see `crate::lookup::MODE_*`. Only the field `mode` selects is used:
+    /// `id.seqno`, `lt` or `utime`, the latter gives the block that was active at that wall-clock time
+    pub async fn lookup_block(&self, id: TonNodeBlockId, mode: i32, lt: Option<i64>, utime: Option<i32>) -> Result<LiteServerBlockHeader, Error> {
+        lookup::validate(mode, lt, utime)?;
 
-        Ok(())
+        self.clone()
+            .oneshot(LiteServerLookupBlock { mode, id, lt, utime })
+            .await
     }
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_get_version() -> anyhow::Result<()> {
-        let client = provided_client().await?;
+    /// Header proof of the block `id`, `mode` is a set of `crate::header::WITH_*` flags naming the parts of the block
+    /// to keep unpruned. Mode 0 gives the `BlockInfo` only, the smallest answer
+    pub async fn get_block_header(&self, id: TonNodeBlockIdExt, mode: i32) -> Result<LiteServerBlockHeader, Error> {
+        header::validate(mode)?;
 
-        let response = client.oneshot(LiteServerGetVersion::default()).await?;
+        self.clone()
+            .oneshot(LiteServerGetBlockHeader { id, mode })
+            .await
+    }
 
-        assert!(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs().abs_diff(response.now as u64) <= 10);
+    /// Masterchain headers from `from_seqno` to `to_seqno` inclusive in seqno order, up to `concurrency` lookups
+    /// are in flight at once. The range ends at the last block the server has, it is empty when `from_seqno > to_seqno`
+    pub fn header_range(&self, from_seqno: i32, to_seqno: i32, concurrency: usize) -> impl Stream<Item = Result<LiteServerBlockHeader, Error>> + 'static {
+        let client = self.clone();
+
+        try_stream! {
+            if from_seqno <= to_seqno {
+                let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+                let headers = futures::stream::iter(from_seqno..=to_seqno.min(last.seqno))
+                    .map(|seqno| {
+                        let client = client.clone();
+                        let id = TonNodeBlockId { workchain: last.workchain, shard: last.shard, seqno };
+
+                        async move { client.lookup_block(id, lookup::MODE_SEQNO, None, None).await }
+                    })
+                    .buffered(concurrency.max(1));
+
+                for await header in headers {
+                    yield header?;
+                }
+            }
+        }
+    }
 
-        Ok(())
+    /// Mode 0 headers of `ids` in the same order, up to `concurrency` requests are in flight at once. Every id
+    /// gets its own result, a failed one doesn't fail the others
+    pub async fn batch_headers(&self, ids: &[TonNodeBlockIdExt], concurrency: usize) -> Vec<Result<LiteServerBlockHeader, Error>> {
+        futures::stream::iter(ids.iter().cloned())
+            .map(|id| self.get_block_header(id, 0))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
     }
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_error_test() -> anyhow::Result<()> {
-        let client = provided_client().await?;
+    /// Serialized state after block `id`, liteservers only give out the zero state. States over `max_size` bytes
+    /// fail with `Error::StateTooLarge`: they are fetched over a connection of their own, which drops an answer
+    /// by its length prefix before buffering any of it
+    pub async fn get_state(&self, id: TonNodeBlockIdExt, max_size: Option<usize>) -> Result<LiteServerBlockState, Error> {
+        let Some(max_size) = max_size else {
+            return self.clone().oneshot(LiteServerGetState { id }).await
+        };
 
-        let response = client.oneshot(LiteServerGetMasterchainInfoExt { mode: 1 }).await;
+        let state = match self.request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.get_state_within(id, max_size)).await.map_err(|_| Error::Timeout)??,
+            None => self.get_state_within(id, max_size).await?
+        };
+        // the packet limit leaves room for the rest of the answer
+        if state.data.len() > max_size {
+            return Err(Error::StateTooLarge { size: state.data.len(), max_size });
+        }
 
-        assert!(response.is_err());
-        assert_eq!(response.unwrap_err().to_string(), "LiteServer error: Error code: -400, message: \"unsupported getMasterchainInfo mode\"".to_owned());
+        Ok(state)
+    }
 
-        Ok(())
+    async fn get_state_within(&self, id: TonNodeBlockIdExt, max_size: usize) -> Result<LiteServerBlockState, Error> {
+        let mut connection = connect(self.addr, &self.server_key, &self.socket_options).await
+            .map_err(|error| error.downcast::<Error>().unwrap_or_else(|error| {
+                tracing::warn!(error = ?error, "state connection failed");
+
+                TransportError::ConnectionFailed.into()
+            }))?;
+        connection.set_max_len(Some(max_size + STATE_ANSWER_OVERHEAD));
+
+        let query = AdnlMessageQuery { query_id: random(), query: to_bytes_boxed(&LiteServerQuery { data: to_bytes_boxed(&LiteServerGetState { id }) }) };
+        connection.send(Packet::new(to_bytes_boxed(&query))).await
+            .map_err(|_| TransportError::ConnectionFailed)?;
+
+        let packet = match connection.next().await {
+            Some(Ok(packet)) => packet,
+            Some(Err(error)) => return Err(match error.downcast_ref::<PacketTooLarge>() {
+                Some(too_large) => Error::StateTooLarge { size: too_large.length, max_size },
+                None => TransportError::ConnectionFailed.into()
+            }),
+            None => return Err(TransportError::ConnectionFailed.into())
+        };
+        let answer = from_bytes_boxed::<AdnlMessageAnswer>(&packet.data).map_err(|_| Error::Decode)?;
+
+        from_bytes_boxed::<Result<LiteServerBlockState, LiteServerError>>(&answer.answer)
+            .map_err(|_| Error::Decode)?
+            .map_err(Error::from)
     }
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_get_block_proof_test() -> anyhow::Result<()> {
-        let mut client = provided_client().await?;
-        let known_block = (&mut client).oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+    /// Block `id.seqno` of the shard `id`, see [`lookup::lookup_seqno_or_wait`] for the masterchain blocks the server hasn't reached yet
+    pub async fn lookup_seqno_or_wait(&self, id: TonNodeBlockId, wait: Duration) -> Result<LiteServerBlockHeader, Error> {
+        lookup::lookup_seqno_or_wait(self.clone(), id, wait).await
+    }
 
-        let request = LiteServerGetBlockProof { mode: 0, known_block: known_block.clone(), target_block: None };
-        let response = client.oneshot(request).await?;
+    /// Masterchain block that was active at the unix time `utime`, `Error::NotFound` when it is before the first block the server has
+    pub async fn block_at_time(&self, utime: i32) -> Result<TonNodeBlockIdExt, Error> {
+        let id = TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno: 0 };
 
-        assert_eq!(&response.from.seqno, &known_block.seqno);
+        Ok(self.lookup_block(id, lookup::MODE_UTIME, None, Some(utime)).await?.id)
+    }
 
-        Ok(())
+    /// Masterchain block `seqno` once the masterchain reaches it, `Error::Timeout` when it doesn't within `timeout`
+    pub async fn wait_for_seqno(&self, seqno: i32, timeout: Duration) -> Result<TonNodeBlockIdExt, Error> {
+        Ok(self.lookup_block_or_wait(seqno, timeout).await?.id)
     }
 
-    #[tokio::test]
-    #[traced_test]
-    #[ignore]
-    async fn client_drop_test() -> anyhow::Result<()> {
-        let future = {
-            let client = provided_client().await?;
+    /// Header of the masterchain block `seqno`, which may be ahead of the last block, once the masterchain
+    /// reaches it. `Error::Timeout` when it doesn't within `timeout`, e.g. to await the block a message lands in
+    pub async fn lookup_block_or_wait(&self, seqno: i32, timeout: Duration) -> Result<LiteServerBlockHeader, Error> {
+        let id = TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno };
+        let request = WaitSeqno::with_server_timeout(LiteServerLookupBlock { mode: lookup::MODE_SEQNO, id, lt: None, utime: None }, seqno, timeout);
+
+        // the server gives up after `timeout` itself, the local timeout is for a server that doesn't answer at all
+        let response = tokio::time::timeout(timeout + Duration::from_secs(1), self.clone().oneshot(request))
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match response {
+            Ok(header) => Ok(header),
+            Err(Error::ServerTimeout(_)) => Err(Error::Timeout),
+            Err(error) => Err(error)
+        }
+    }
 
-            client.oneshot(LiteServerGetMasterchainInfo::default())
-        };
+    /// Proof links from the masterchain block `known` to `target`, the last known block when `None`.
+    /// Long chains come in pages with `complete` unset, see [`crate::proof::verify_proof_chain`]
+    pub async fn get_block_proof(&self, known: TonNodeBlockIdExt, target: Option<TonNodeBlockIdExt>) -> Result<LiteServerPartialBlockProof, Error> {
+        self.capabilities().await?.require(CAP_PROOF_CHAINS, LiteServerGetBlockProof::NAME)?;
 
-        let response = future.await;
+        self.clone()
+            .oneshot(LiteServerGetBlockProof { mode: target.is_some() as i32, known_block: known, target_block: target })
+            .await
+    }
 
-        assert!(response.is_ok());
+    /// Links from a masterchain block to the shard block `id`, see [`crate::proof::verify_shard_block_proof`]
+    pub async fn get_shard_block_proof(&self, id: TonNodeBlockIdExt) -> Result<LiteServerShardBlockProof, Error> {
+        self.clone()
+            .oneshot(LiteServerGetShardBlockProof { id })
+            .await
+    }
 
-        Ok(())
+    /// Up to `count` transaction ids of the block `id` following `after`, `incomplete` is set when the block has more
+    pub async fn list_block_transactions(&self, id: TonNodeBlockIdExt, count: i32, after: Option<LiteServerTransactionId3>) -> Result<LiteServerBlockTransactions, Error> {
+        // account, lt and hash of every id, they are needed to continue from the last one
+        let mode = 7 | (after.is_some() as i32) << 7;
+
+        self.clone()
+            .oneshot(LiteServerListBlockTransactions { id, mode, count, after, reverse_order: None, want_proof: None })
+            .await
+    }
+
+    /// All transaction ids of the block `id`, requested `page_size` at a time
+    pub fn list_block_transactions_stream(&self, id: TonNodeBlockIdExt, page_size: i32) -> impl Stream<Item = Result<LiteServerTransactionId, Error>> + 'static {
+        let client = self.clone();
+
+        Pager::new(
+            move |after| {
+                let (client, id) = (client.clone(), id.clone());
+
+                async move { client.list_block_transactions(id, page_size, after).await }
+            },
+            |_, page: LiteServerBlockTransactions| {
+                // an incomplete page without ids gives nothing to continue from
+                let next = page.ids.last()
+                    .and_then(|tx| Some(LiteServerTransactionId3 { account: tx.account?, lt: tx.lt? }))
+                    .filter(|_| bool::from(&page.incomplete));
+
+                Ok(Page { items: page.ids, next })
+            }
+        )
+    }
+
+    /// Block creation stats of up to `limit` validators of the masterchain state after `id`, starting after the public key
+    /// `start_after` and only with the ones updated since `modified_after` when set. `complete` is set on the last page
+    pub async fn get_validator_stats(&self, id: TonNodeBlockIdExt, limit: i32, start_after: Option<Int256>, modified_after: Option<i32>) -> Result<LiteServerValidatorStats, Error> {
+        let mode = start_after.is_some() as i32 | (modified_after.is_some() as i32) << 2;
+
+        self.clone()
+            .oneshot(LiteServerGetValidatorStats { mode, id, limit, start_after, modified_after })
+            .await
+    }
+
+    /// Block creation stats of all validators of the masterchain state after `id`, requested `page_size` at a time
+    pub fn validator_stats_stream(&self, id: TonNodeBlockIdExt, page_size: i32) -> impl Stream<Item = Result<CreatorStats, Error>> + 'static {
+        let client = self.clone();
+
+        Pager::new(
+            move |after| {
+                let (client, id) = (client.clone(), id.clone());
+
+                async move { client.get_validator_stats(id, page_size, after, None).await }
+            },
+            |after: Option<&Int256>, page: LiteServerValidatorStats| {
+                let stats = page.creator_stats().map_err(|_| Error::Decode)?;
+                // the proof may carry entries around the page
+                let stats: Vec<_> = stats.into_iter()
+                    .filter(|stats| after.map_or(true, |after| stats.public_key > *after))
+                    .take(page.count as usize)
+                    .collect();
+                let next = stats.last()
+                    .map(|stats| stats.public_key)
+                    .filter(|_| !bool::from(&page.complete));
+
+                Ok(Page { items: stats, next })
+            }
+        )
+    }
+
+    /// Transactions of `account` going backwards from the one identified by `lt` and `hash`, at most `count` of them.
+    /// `ids` holds the block of every returned transaction, so it is shorter than `count` when the history ends earlier
+    pub async fn get_transactions(&self, count: i32, account: impl Into<LiteServerAccountId>, lt: i64, hash: Int256) -> Result<LiteServerTransactionList, Error> {
+        self.clone()
+            .oneshot(LiteServerGetTransactions { count, account: account.into(), lt, hash })
+            .await
+    }
+
+    /// Whole history of `account` from the transaction `from_lt` and `from_hash` back to the first one, the newest first.
+    /// Pages follow the previous transaction of the last one on a page, the first transaction has none with lt 0
+    pub fn transactions_stream(&self, account: impl Into<LiteServerAccountId>, from_lt: i64, from_hash: Int256) -> impl Stream<Item = Result<AccountTransaction, Error>> + 'static {
+        let client = self.clone();
+        let account = account.into();
+        let from = LastTransaction { lt: from_lt, hash: from_hash };
+
+        Pager::new(
+            move |next: Option<LastTransaction>| {
+                let (client, account, next) = (client.clone(), account.clone(), next.unwrap_or(from));
+
+                async move { client.get_transactions(TRANSACTIONS_PAGE, account, next.lt, next.hash).await }
+            },
+            |_, page: LiteServerTransactionList| {
+                let transactions = page.transactions().map_err(|_| Error::Decode)?;
+                let next = transactions.last()
+                    .filter(|transaction| transaction.prev_lt != 0)
+                    .map(|transaction| LastTransaction { lt: transaction.prev_lt, hash: transaction.prev_hash });
+
+                Ok(Page { items: transactions, next })
+            }
+        )
+    }
+
+    /// The transaction of `account` with logical time `lt` in block `id`, along with its proof
+    pub async fn get_one_transaction(&self, id: TonNodeBlockIdExt, account: impl Into<LiteServerAccountId>, lt: i64) -> Result<Answered<LiteServerTransactionInfo>, Error> {
+        let transaction = self.clone()
+            .oneshot(LiteServerGetOneTransaction { id: id.clone(), account: account.into(), lt })
+            .await?;
+
+        Answered::checked(id, transaction.id.clone(), transaction)
+    }
+
+    /// Library cells by hash as BoCs, the libraries the server doesn't have are left out
+    pub async fn get_libraries(&self, hashes: &[Int256]) -> Result<LiteServerLibraryResult, Error> {
+        self.clone()
+            .oneshot(LiteServerGetLibraries { library_list: hashes.to_vec() })
+            .await
+    }
+
+    /// Validator groups with the block candidates they are collating or signing, none of them final yet.
+    /// `None` leaves the workchain or shard out of the filter. Only liteservers that follow non-final blocks answer it
+    pub async fn get_nonfinal_validator_groups(&self, workchain: Option<i32>, shard: Option<i64>) -> Result<LiteServerNonfinalValidatorGroups, Error> {
+        let mode = workchain.is_some() as i32 | ((shard.is_some() as i32) << 1);
+
+        self.clone()
+            .oneshot(LiteServerNonfinalGetValidatorGroups { mode, wc: workchain, shard })
+            .await
+    }
+
+    /// `body` is a BoC with the serialized external message
+    pub async fn send_message(&self, body: Bytes) -> Result<LiteServerSendMsgStatus, Error> {
+        self.clone()
+            .oneshot(LiteServerSendMessage { body })
+            .await
+    }
+
+    /// Sends the external message `body` to `account` and waits up to `timeout` for the transaction it comes in with.
+    /// The account is checked on the last block every second, its new transactions are matched by the message hash
+    pub async fn send_message_and_wait(&self, body: Bytes, account: impl Into<LiteServerAccountId>, timeout: Duration) -> Result<Answered<LiteServerTransactionInfo>, Error> {
+        let account = account.into();
+        let msg_hash = read_single_root(&body).map_err(|_| Error::InvalidRequest("message isn't a single root BoC"))?.hash();
+        let last_transaction = |state: Answered<LiteServerAccountState>| state.last_transaction(&account.id).map_err(|_| Error::Decode);
+
+        let mut since = last_transaction(self.account_state_at_tip(account.clone(), None).await?)?.map_or(0, |last| last.lt);
+        self.send_message(body).await?;
+
+        tokio::time::timeout(timeout, async {
+            loop {
+                tokio::time::sleep(MESSAGE_POLL_INTERVAL).await;
+
+                // a lagging liteserver may answer with an older state
+                let current = last_transaction(self.account_state_at_tip(account.clone(), None).await?)?;
+                let Some(current) = current.filter(|current| current.lt > since) else { continue };
+
+                let fresh: Vec<_> = self.transactions_stream(account.clone(), current.lt, current.hash)
+                    .try_take_while(|transaction| futures::future::ready(Ok(transaction.lt > since)))
+                    .try_collect()
+                    .await?;
+                if let Some(transaction) = find_by_in_msg(&fresh, &msg_hash).map_err(|_| Error::Decode)? {
+                    return self.get_one_transaction(transaction.block_id.clone(), account.clone(), transaction.lt).await;
+                }
+                since = current.lt;
+            }
+        }).await.map_err(|_| Error::Timeout)?
+    }
+
+    /// `params` is a BoC with the serialized `VmStack`, `mode` is a combination of `crate::smc::MODE_*` flags
+    pub async fn run_smc_method(&self, mode: i32, id: TonNodeBlockIdExt, account: impl Into<LiteServerAccountId>, method_id: impl Into<MethodId>, params: Bytes) -> Result<Answered<LiteServerRunMethodResult>, Error> {
+        self.capabilities().await?.require(CAP_RUN_SMC_METHOD, LiteServerRunSmcMethod::NAME)?;
+
+        let result = self.clone()
+            .oneshot(LiteServerRunSmcMethod { mode, id: id.clone(), account: account.into(), method_id: method_id.into().value(), params })
+            .await?;
+
+        Answered::checked(id, result.id.clone(), result)
+    }
+}
+
+pub struct LiteServerClientBuilder {
+    addr: SocketAddrV4,
+    server_key: ServerKey,
+    socket_options: SocketOptions,
+    reconnect_policy: ReconnectPolicy,
+    keepalive: Option<KeepalivePolicy>,
+    request_timeout: Option<Duration>,
+}
+
+impl LiteServerClientBuilder {
+    fn new(addr: SocketAddrV4, server_key: &ServerKey) -> Self {
+        Self { addr, server_key: *server_key, socket_options: SocketOptions::default(), reconnect_policy: ReconnectPolicy::default(), keepalive: None, request_timeout: None }
+    }
+
+    pub fn set_socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+
+        self
+    }
+
+    pub fn set_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+
+        self
+    }
+
+    /// Pings an idle connection with a `liteServer.getTime`, by default only ADNL pings are sent
+    pub fn set_keepalive(mut self, keepalive: KeepalivePolicy) -> Self {
+        self.keepalive = Some(keepalive);
+
+        self
+    }
+
+    /// Requests without an answer in `request_timeout` fail with `Error::Timeout`, by default they wait forever.
+    /// It is added to the server timeout of `WaitSeqno` requests
+    pub fn set_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+
+        self
+    }
+
+    pub async fn build(self) -> anyhow::Result<LiteServerClient> {
+        let inner = connect(self.addr, &self.server_key, &self.socket_options).await?;
+        let cancel_token = CancellationToken::new();
+        let (tx, rx) = mpsc::unbounded_channel::<ClientActorMessage>();
+        let actor = TaskTracker::new();
+        let client_actor = ClientActor::new(self.addr, self.server_key, self.socket_options, self.reconnect_policy, inner, rx, cancel_token.clone())
+            .with_keepalive(self.keepalive);
+        let connection_state = client_actor.connection_state();
+        client_actor.run(&actor);
+        actor.close();
+
+        Ok(LiteServerClient { addr: self.addr, server_key: self.server_key, socket_options: self.socket_options, tx, connection_state, request_timeout: self.request_timeout, capabilities: Default::default(), masterchain_info: Default::default(), cancellation_token: cancel_token.clone(), actor, drop_guard: Arc::new(cancel_token.drop_guard()) })
+    }
+}
+
+impl IntoFuture for LiteServerClientBuilder {
+    type Output = anyhow::Result<LiteServerClient>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        self.build().boxed()
+    }
+}
+
+impl<R> Service<R> for LiteServerClient where R: Requestable {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = ResponseFuture<R::Response>;
+
+    /// Pending while the connection is being reestablished, fails once the reconnect attempts run out
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.tx.is_closed() || !ready!(self.connection_state.poll_connected(cx)) {
+            return Poll::Ready(Err(TransportError::ChannelClosed.into()))
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let id = req.block_id();
+        let span = tracing::debug_span!("liteserver_request", request = req.name(), workchain = id.as_ref().map(|id| id.workchain), shard = id.as_ref().map(|id| id.shard), seqno = id.as_ref().map(|id| id.seqno));
+        let data = to_bytes_boxed(&req);
+
+        let query = LiteServerQuery { data };
+        let query = to_bytes_boxed(&query);
+
+        let query = AdnlMessageQuery { query_id: random(), query };
+
+        let (tx, rx) = oneshot::channel();
+
+        if self.tx.send(ClientActorMessage::Query { query, oneshot: tx }).is_err() {
+            return ResponseFuture::failed(TransportError::ChannelClosed.into(), span, req.name());
+        }
+
+        // the request timeout starts once the server is done waiting
+        let server_timeout = req.server_timeout();
+        let timeout = self.request_timeout.map(|timeout| timeout + server_timeout.unwrap_or_default());
+
+        ResponseFuture::new(rx, timeout, self.drop_guard.clone(), span, req.name())
+    }
+}
+
+
+#[pin_project(project = ResponseStateProj)]
+pub enum ResponseState {
+    Failed { error: Option<Error> },
+    Rx {
+        #[pin]
+        rx: oneshot::Receiver<Bytes>,
+        timeout: Option<Pin<Box<Sleep>>>,
+        drop_guard: Arc<DropGuard>
+    }
+}
+
+#[pin_project]
+pub struct ResponseFuture<Response> {
+    #[pin]
+    state: ResponseState,
+    span: Span,
+    started: Instant,
+    #[cfg(feature = "metrics")]
+    request: &'static str,
+    _phantom: PhantomData<Response>,
+}
+
+impl<Response> ResponseFuture<Response> {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn new(rx: oneshot::Receiver<Bytes>, timeout: Option<Duration>, drop_guard: Arc<DropGuard>, span: Span, request: &'static str) -> Self {
+        let timeout = timeout.map(|timeout| Box::pin(tokio::time::sleep(timeout)));
+
+        Self {
+            state: ResponseState::Rx { rx, timeout, drop_guard },
+            span,
+            started: Instant::now(),
+            #[cfg(feature = "metrics")]
+            request,
+            _phantom: PhantomData
+        }
+    }
+
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn failed(error: Error, span: Span, request: &'static str) -> Self {
+        Self {
+            state: ResponseState::Failed { error: Some(error) },
+            span,
+            started: Instant::now(),
+            #[cfg(feature = "metrics")]
+            request,
+            _phantom: PhantomData
+        }
+    }
+}
+
+impl<Response> Future for ResponseFuture<Response> where Response: DeserializeBoxed {
+    type Output = Result<Response, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        let response = ready!(this.state.as_mut().poll_response(cx));
+        tracing::debug!(parent: &*this.span, elapsed_ms = this.started.elapsed().as_millis() as u64, ok = response.is_ok(), "liteserver request finished");
+
+        #[cfg(feature = "metrics")]
+        {
+            let outcome = if response.is_ok() { "ok" } else { "error" };
+            metrics::counter!("ton_liteserver_client_requests_total", "request" => *this.request, "outcome" => outcome).increment(1);
+            metrics::histogram!("ton_liteserver_client_request_duration_seconds", "request" => *this.request, "outcome" => outcome).record(this.started.elapsed().as_secs_f64());
+        }
+
+        Poll::Ready(response)
+    }
+}
+
+impl ResponseState {
+    fn poll_response<Response: DeserializeBoxed>(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Response, Error>> {
+        return match self.project() {
+            ResponseStateProj::Failed { error } => {
+                Poll::Ready(Err(error.take().expect("polled after error")))
+            },
+            ResponseStateProj::Rx { rx, timeout, .. } => {
+                if let Poll::Ready(response) = rx.poll(cx) {
+                    return match response {
+                        Ok(response) => {
+                            let response = from_bytes_boxed::<Result<Response, LiteServerError>>(&response)
+                                .map_err(|_| Error::Decode)?
+                                .map_err(Error::from)?;
+
+                            Poll::Ready(Ok(response))
+                        }
+                        Err(_) => {
+                            Poll::Ready(Err(TransportError::OneshotClosed.into()))
+                        }
+                    }
+                }
+
+                match timeout {
+                    Some(timeout) => {
+                        ready!(timeout.as_mut().poll(cx));
+
+                        Poll::Ready(Err(Error::Timeout))
+                    }
+                    None => Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use base64::Engine;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
+    use tracing_test::traced_test;
+    use std::time::SystemTime;
+    use std::time::UNIX_EPOCH;
+    use adnl_tcp::key::Ed25519Key;
+    use adnl_tcp::ping::is_ping_packet;
+    use adnl_tcp::server::Server;
+    use crate::config_params::CURRENT_VALIDATORS;
+    use crate::lookup::{MODE_LT, MODE_SEQNO, MODE_UTIME};
+    use crate::pool::LiteServerPool;
+    use crate::header::{WITH_STATE_UPDATE, WITH_VALUE_FLOW};
+    use crate::proof::{verify_proof_chain, BlockHeader};
+    use crate::smc::MODE_RESULT;
+    use crate::tl::{LiteServerCurrentTime, LiteServerGetBlockProof, LiteServerGetTime, LiteServerLibraryEntry, LiteServerListBlockTransactions, TonNodeZeroStateIdExt};
+    use super::*;
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_masterchain_info() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+
+        let response = client.oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+        assert_eq!(response.last.workchain, -1);
+        assert_eq!(response.last.shard, -9223372036854775808);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_wait_seqno_info() -> anyhow::Result<()> {
+        let mut client = provided_client().await?;
+        let current = (&mut client).oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+        let actual = (&mut client).oneshot(WaitSeqno::new(LiteServerGetMasterchainInfo::default(), current.last.seqno + 1)).await?;
+
+        assert_eq!(actual.last.workchain, -1);
+        assert_eq!(actual.last.shard, -9223372036854775808);
+        assert!(current.last.seqno < actual.last.seqno);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_all_shards_info() -> anyhow::Result<()> {
+        let mut client = provided_client().await?;
+        let response = (&mut client).oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+        let response = client.get_all_shards_info(response.last).await?;
+        let shards = response.shards()?;
+
+        assert_eq!(response.id.workchain, -1);
+        assert_eq!(response.id.shard, -9223372036854775808);
+        assert!(!shards.is_empty());
+        assert!(shards.iter().all(|shard| shard.workchain == 0));
+
+        Ok(())
+    }
+
+    /// Whether the shard `ancestor` is `shard` or one of its parents
+    fn contains_shard(ancestor: i64, shard: i64) -> bool {
+        let (ancestor, shard) = (ancestor as u64, shard as u64);
+        let lower_bit = ancestor & ancestor.wrapping_neg();
+
+        lower_bit >= shard & shard.wrapping_neg() && (ancestor ^ shard) & (lower_bit.wrapping_neg() << 1) == 0
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_shard_info_test() -> anyhow::Result<()> {
+        const SHARDS: [i64; 2] = [0x4000000000000000, 0xc000000000000000_u64 as i64];
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerGetShardInfo>(&data).unwrap();
+            let exact: bool = (&request.exact).into();
+            let shard = SHARDS.into_iter()
+                .find(|shard| if exact { *shard == request.shard } else { contains_shard(*shard, request.shard) });
+
+            match shard {
+                Some(shard) => to_bytes_boxed(&LiteServerShardInfo {
+                    id: request.id,
+                    shardblk: TonNodeBlockIdExt { workchain: request.workchain, shard, seqno: 42, root_hash: [1; 32], file_hash: [2; 32] },
+                    shard_proof: vec![],
+                    shard_descr: vec![]
+                }),
+                None => to_bytes_boxed(&LiteServerError { code: 651, message: "shard not found".to_owned() })
+            }
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+
+        let exact = client.get_shard_info(id.clone(), 0, SHARDS[1], true).await?;
+        let child = 0x2000000000000000;
+        let missing = client.get_shard_info(id.clone(), 0, child, true).await;
+        let containing = client.get_shard_info(id.clone(), 0, child, false).await?;
+
+        assert_eq!(exact.id, id);
+        assert_eq!(exact.shardblk.shard, SHARDS[1]);
+        assert!(matches!(missing, Err(Error::NotFound(_))));
+        assert_eq!(containing.shardblk.shard, SHARDS[0]);
+        assert_eq!(containing.shardblk.workchain, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_shard_info_known_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+
+        let response = client.get_shard_info(last, 0, i64::MIN, false).await?;
+
+        assert_eq!(response.shardblk.workchain, 0);
+        assert!(contains_shard(i64::MIN, response.shardblk.shard));
+        assert!(!response.shard_descr.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_config_all_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+
+        let response = client.get_config_all(last.clone()).await?;
+        let config = response.config_params()?;
+
+        assert_eq!(response.id, last);
+        assert_eq!(config.elector_address()?, Some([0x33; 32]));
+        assert!(config.validator_set(CURRENT_VALIDATORS)?.is_some_and(|validators| !validators.list.is_empty()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_config_params_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+
+        let config = client.get_config_params(last, &[0, 34, 999]).await?.config_params()?;
+
+        assert_eq!(config.indices().collect::<Vec<_>>(), vec![0, 34]);
+        assert!(config.config_address()?.is_some());
+        assert!(config.param(999).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_lookup_block_by_seqno_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let id = TonNodeBlockId { workchain: last.workchain, shard: last.shard, seqno: last.seqno - 10 };
+
+        let header = client.lookup_block(id, MODE_SEQNO, None, None).await?;
+
+        assert_eq!(header.id.seqno, last.seqno - 10);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_lookup_block_by_lt_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let block_txs = client.clone().oneshot(LiteServerListBlockTransactions { id: last.clone(), mode: 7, count: 1, after: None, reverse_order: None, want_proof: None }).await?;
+        let lt = block_txs.ids.first().and_then(|tx| tx.lt).expect("masterchain block has transactions");
+        let id = TonNodeBlockId { workchain: last.workchain, shard: last.shard, seqno: 0 };
+
+        let header = client.lookup_block(id, MODE_LT, Some(lt), None).await?;
+
+        assert_eq!(header.id, last);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_lookup_block_by_utime_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let now = client.clone().oneshot(LiteServerGetTime::default()).await?.now;
+        let id = TonNodeBlockId { workchain: last.workchain, shard: last.shard, seqno: 0 };
+
+        let header = client.lookup_block(id, MODE_UTIME, None, Some(now - 3600)).await?;
+
+        assert_eq!(header.id.workchain, -1);
+        assert!(header.id.seqno < last.seqno);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_lookup_block_rejects_mode_zero() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_server(1).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno: 1 };
+
+        let response = client.lookup_block(id, 0, None, None).await;
+
+        assert!(matches!(response, Err(Error::InvalidRequest(_))));
+
+        Ok(())
+    }
+
+    /// Header proofs of the same block in modes 0 and `WITH_STATE_UPDATE | WITH_VALUE_FLOW`
+    fn block_header_modes() -> Vec<LiteServerBlockHeader> {
+        include_str!("../fixtures/block_header_modes.hex")
+            .lines()
+            .map(|line| {
+                let fields: Vec<_> = line.split(' ').collect();
+                let id = TonNodeBlockIdExt {
+                    workchain: -1,
+                    shard: i64::MIN,
+                    seqno: fields[1].parse().unwrap(),
+                    root_hash: hex::decode(fields[2]).unwrap().try_into().unwrap(),
+                    file_hash: hex::decode(fields[3]).unwrap().try_into().unwrap()
+                };
+
+                LiteServerBlockHeader { id, mode: fields[0].parse().unwrap(), header_proof: hex::decode(fields[4]).unwrap() }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_block_header_modes_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerGetBlockHeader>(&data).unwrap();
+            let header = block_header_modes().into_iter().find(|header| header.mode == request.mode).unwrap();
+
+            to_bytes_boxed(&header)
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = block_header_modes()[0].id.clone();
+
+        let info_only = client.get_block_header(id.clone(), 0).await?;
+        let with_parts = client.get_block_header(id.clone(), WITH_STATE_UPDATE | WITH_VALUE_FLOW).await?;
+
+        assert_eq!(with_parts.mode, WITH_STATE_UPDATE | WITH_VALUE_FLOW);
+        assert!(info_only.header_proof.len() < with_parts.header_proof.len());
+        // both prove the same block
+        assert!(BlockHeader::from_proof(&info_only.header_proof, &id).is_ok());
+        assert!(BlockHeader::from_proof(&with_parts.header_proof, &id).is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_batch_headers_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerGetBlockHeader>(&data).unwrap();
+            if request.id.seqno > 12 {
+                return to_bytes_boxed(&LiteServerError { code: 651, message: "block not found".to_owned() });
+            }
+
+            to_bytes_boxed(&LiteServerBlockHeader { id: request.id, mode: request.mode, header_proof: vec![] })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let ids: Vec<_> = [10, 11, 100, 12].into_iter()
+            .map(|seqno| TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [seqno as u8; 32], file_hash: [0; 32] })
+            .collect();
+
+        let headers = client.batch_headers(&ids, 2).await;
+
+        assert_eq!(headers.len(), 4);
+        assert!(matches!(headers[2], Err(Error::NotFound(_))));
+        for (header, id) in headers.iter().zip(&ids).filter(|(_, id)| id.seqno != 100) {
+            assert_eq!(&header.as_ref().unwrap().id, id);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_block_header_rejects_unknown_mode() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_server(1).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = block_header_modes()[0].id.clone();
+
+        let response = client.get_block_header(id, 1 << 2).await;
+
+        assert!(matches!(response, Err(Error::InvalidRequest(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_version() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+
+        let response = client.get_version().await?;
+
+        assert!(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs().abs_diff(response.now as u64) <= 10);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_masterchain_info_ext_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+
+        let response = client.get_masterchain_info_ext().await?;
+
+        assert_eq!(response.last.workchain, -1);
+        assert!(response.version > 0);
+        assert!(response.clock_skew(SystemTime::now()).abs() <= 10);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_error_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+
+        let response = client.oneshot(LiteServerGetMasterchainInfoExt { mode: 1 }).await;
+
+        assert!(response.is_err());
+        assert_eq!(response.unwrap_err().to_string(), "LiteServer error: Error code: -400, message: \"unsupported getMasterchainInfo mode\"".to_owned());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_liteserver_error_decode_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |_| to_bytes_boxed(&LiteServerError { code: -400, message: "unsupported".to_owned() })).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let response = client.oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::LiteServerError { code: -400, ref message }) if message == "unsupported"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_not_ready_error_decode_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |_| to_bytes_boxed(&LiteServerError { code: 651, message: "block not found".to_owned() })).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let response = client.oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::NotFound(ref message)) if message == "block not found"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_block_test() -> anyhow::Result<()> {
+        // masterchain block 200, see `crate::block`
+        let fields: Vec<_> = include_str!("../fixtures/block.hex").split_whitespace().collect();
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: fields[0].parse()?, root_hash: hex::decode(fields[1])?.try_into().unwrap(), file_hash: hex::decode(fields[2])?.try_into().unwrap() };
+        let data = hex::decode(fields[3])?;
+        let mut tampered = data.clone();
+        // the first byte of global_id in the root cell
+        tampered[18] ^= 1;
+
+        for (data, valid) in [(data, true), (tampered, false)] {
+            let (addr, server_key) = provided_answering_server(usize::MAX, move |request| {
+                let request = from_bytes_boxed::<LiteServerGetBlock>(&request).unwrap();
+
+                to_bytes_boxed(&LiteServerBlockData { id: request.id, data: data.clone() })
+            }).await?;
+            let client = LiteServerClient::connect(addr, &server_key).await?;
+
+            let block = client.get_block(id.clone()).await;
+
+            match valid {
+                true => assert_eq!(block?.parse()?.info.seqno, 200),
+                false => assert!(matches!(block, Err(Error::HashMismatch(ref mismatched)) if *mismatched == id))
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_keepalive_test() -> anyhow::Result<()> {
+        let pings = Arc::new(AtomicUsize::new(0));
+        let (addr, server_key) = provided_answering_server(usize::MAX, {
+            let pings = pings.clone();
+            move |data| {
+                from_bytes_boxed::<LiteServerGetTime>(&data).unwrap();
+                pings.fetch_add(1, Ordering::SeqCst);
+
+                to_bytes_boxed(&LiteServerCurrentTime { now: 1 })
+            }
+        }).await?;
+        let client = LiteServerClient::builder(addr, &server_key)
+            .set_keepalive(KeepalivePolicy { interval: Duration::from_millis(100), reconnect: true })
+            .await?;
+
+        tokio::time::sleep(Duration::from_millis(450)).await;
+        let idle_pings = pings.load(Ordering::SeqCst);
+        let time = client.clone().oneshot(LiteServerGetTime::default()).await?;
+
+        assert!(idle_pings >= 2, "{} pings while idle", idle_pings);
+        assert_eq!(time.now, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn liteserver_error_codes_test() {
+        let decode = |code| Error::from(LiteServerError { code, message: "message".to_owned() });
+
+        assert!(matches!(decode(651), Error::NotFound(ref message) if message == "message"));
+        assert!(matches!(decode(652), Error::ServerTimeout(_)));
+        assert!(matches!(decode(653), Error::Cancelled(_)));
+        assert!(matches!(decode(-400), Error::LiteServerError { code: -400, .. }));
+        for code in [651, 652, 653, -400] {
+            assert_eq!(decode(code).code(), Some(code));
+        }
+        assert_eq!(Error::Timeout.code(), None);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_block_proof_test() -> anyhow::Result<()> {
+        let mut client = provided_client().await?;
+        let known_block = (&mut client).oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+
+        let request = LiteServerGetBlockProof { mode: 0, known_block: known_block.clone(), target_block: None };
+        let response = client.oneshot(request).await?;
+
+        assert_eq!(&response.from.seqno, &known_block.seqno);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_list_block_transactions_stream_test() -> anyhow::Result<()> {
+        // a block with transactions at lt 1 to 5
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerListBlockTransactions>(&data).unwrap();
+            let start = request.after.map(|after| after.lt).unwrap_or_default();
+            let end = (start + request.count as i64).min(5);
+
+            to_bytes_boxed(&LiteServerBlockTransactions {
+                id: request.id,
+                req_count: request.count,
+                incomplete: (end < 5).into(),
+                ids: (start + 1 ..= end).map(|lt| LiteServerTransactionId { mode: 7, account: Some([1; 32]), lt: Some(lt), hash: Some([lt as u8; 32]) }).collect(),
+                proof: vec![]
+            })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 1, root_hash: [0; 32], file_hash: [0; 32] };
+
+        let ids: Vec<_> = client.list_block_transactions_stream(id, 2).try_collect().await?;
+
+        assert_eq!(ids.iter().map(|tx| tx.lt.unwrap()).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_validator_stats_stream_test() -> anyhow::Result<()> {
+        // five validators with public keys [1; 32] to [5; 32], the proof carries all of them
+        let data_proof = hex::decode(include_str!("../fixtures/validator_stats.hex").lines().next().unwrap())?;
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (addr, server_key) = provided_answering_server(usize::MAX, {
+            let requests = requests.clone();
+            move |data| {
+                let request = from_bytes_boxed::<LiteServerGetValidatorStats>(&data).unwrap();
+                let after = request.start_after.map(|key| key[0]).unwrap_or(0);
+                let count = request.limit.min(5 - after as i32);
+                requests.lock().unwrap().push(request.start_after);
+
+                to_bytes_boxed(&LiteServerValidatorStats { mode: request.mode, id: request.id, count, complete: (after as i32 + count == 5).into(), state_proof: vec![], data_proof: data_proof.clone() })
+            }
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 1, root_hash: [0; 32], file_hash: [0; 32] };
+
+        let stats: Vec<_> = client.validator_stats_stream(id, 2).try_collect().await?;
+
+        assert_eq!(stats.iter().map(|s| s.public_key[0]).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(*requests.lock().unwrap(), vec![None, Some([2; 32]), Some([4; 32])]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_list_block_transactions_paging_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+
+        let all = client.list_block_transactions(last.clone(), 256, None).await?;
+        let paged: Vec<_> = client.list_block_transactions_stream(last, 1).try_collect().await?;
+
+        assert!(!bool::from(&all.incomplete));
+        assert!(all.ids.len() > 1);
+        assert_eq!(paged, all.ids);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_verify_proof_chain_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let known = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let id = TonNodeBlockId { workchain: known.workchain, shard: known.shard, seqno: known.seqno - 1000 };
+        let target = client.lookup_block(id, MODE_SEQNO, None, None).await?.id;
+
+        verify_proof_chain(client, known, target).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_account_state_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+
+        let response = client.get_account_state(last.clone(), (-1, [0x33; 32])).await?;
+
+        assert_eq!(response.id, last);
+        assert!(!response.state.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_account_state_answered_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerGetAccountState>(&data).unwrap();
+            // a lagging server answers seqno 8 from its last block
+            let id = if request.id.seqno == 8 { TonNodeBlockIdExt { seqno: 7, ..request.id } } else { request.id };
+
+            to_bytes_boxed(&LiteServerAccountState { id: id.clone(), shardblk: id, shard_proof: vec![], proof: vec![], state: vec![1] })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+
+        let response = client.get_account_state(id.clone(), (-1, [3; 32])).await?;
+        let mismatch = client.get_account_state(TonNodeBlockIdExt { seqno: 8, ..id.clone() }, (-1, [3; 32])).await;
+
+        assert_eq!(response.block_id, id);
+        assert_eq!(response.state, vec![1]);
+        assert!(matches!(mismatch, Err(Error::BlockMismatch { answered, .. }) if *answered == id));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_run_smc_method_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let empty_stack = hex::decode("b5ee9c72410101010005000006000000d0095f45")?;
+
+        let response = client.run_smc_method(MODE_RESULT, last, (-1, [0x33; 32]), "active_election_id", empty_stack).await?;
+
+        assert_eq!(response.exit_code, 0);
+        assert!(response.result.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_transactions_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let block_txs = client.clone().oneshot(LiteServerListBlockTransactions { id: last.clone(), mode: 7, count: 1, after: None, reverse_order: None, want_proof: None }).await?;
+        let tx = block_txs.ids.first().expect("masterchain block has transactions");
+
+        let response = client.get_transactions(16, (-1, tx.account.unwrap()), tx.lt.unwrap(), tx.hash.unwrap()).await?;
+
+        assert!(!response.ids.is_empty());
+        assert!(response.ids.len() <= 16);
+        assert_eq!(response.ids[0], last);
+
+        Ok(())
+    }
+
+    /// Masterchain blocks 100 to 200 every 5 seconds starting at 1500, like a liteserver that has pruned the older ones
+    async fn provided_history_server() -> anyhow::Result<(SocketAddrV4, ServerKey)> {
+        provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerLookupBlock>(&data).unwrap();
+            let utime = request.utime.unwrap();
+            if utime < 1500 {
+                return to_bytes_boxed(&LiteServerError { code: 651, message: "block not found".to_owned() });
+            }
+
+            let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: ((utime - 1000) / 5).min(200), root_hash: [0; 32], file_hash: [0; 32] };
+
+            to_bytes_boxed(&LiteServerBlockHeader { id, mode: request.mode, header_proof: vec![] })
+        }).await
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_block_at_time_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_history_server().await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        assert_eq!(client.block_at_time(1752).await?.seqno, 150);
+        assert_eq!(client.block_at_time(1500).await?.seqno, 100);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_block_at_time_before_first_block_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_history_server().await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let response = client.block_at_time(1499).await;
+
+        assert!(matches!(response, Err(Error::NotFound(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_state_test() -> anyhow::Result<()> {
+        // zero state made of the empty cell
+        let (addr, server_key) = provided_answering_server(usize::MAX, |_| hex::decode(include_str!("../fixtures/block_state.hex").trim()).unwrap()).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 0, root_hash: [0; 32], file_hash: [0; 32] };
+
+        let state = client.get_state(id.clone(), Some(1024)).await?;
+        let too_large = client.get_state(id, Some(16)).await;
+
+        assert_eq!(hex::encode(state.root_hash), "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7");
+        assert_eq!(hex::encode(state.file_hash), "a8043ef4e2832a9e20b86de378e963d392680affe5514ce7e306e5cf15e4e76b");
+        assert_eq!(state.id.root_hash, state.root_hash);
+        assert_eq!(read_single_root(&state.data)?.hash(), state.root_hash);
+        assert!(matches!(too_large, Err(Error::StateTooLarge { size: 17, max_size: 16 })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_state_over_packet_limit_test() -> anyhow::Result<()> {
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 0, root_hash: [0; 32], file_hash: [0; 32] };
+        let (addr, server_key) = provided_answering_server(usize::MAX, {
+            let id = id.clone();
+
+            move |_| to_bytes_boxed(&LiteServerBlockState { id: id.clone(), root_hash: [0; 32], file_hash: [0; 32], data: vec![0; 1 << 20] })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let too_large = client.get_state(id.clone(), Some(1024)).await;
+        let state = client.get_state(id, None).await?;
+
+        assert!(matches!(too_large, Err(Error::StateTooLarge { size, max_size: 1024 }) if size > 1 << 20));
+        assert_eq!(state.data.len(), 1 << 20);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_one_transaction_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerGetOneTransaction>(&data).unwrap();
+
+            to_bytes_boxed(&LiteServerTransactionInfo { id: request.id, proof: vec![], transaction: request.lt.to_be_bytes().to_vec() })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+
+        let response = client.get_one_transaction(id.clone(), (-1, [3; 32]), 42).await?;
+
+        assert_eq!(response.id, id);
+        assert_eq!(response.transaction, 42_i64.to_be_bytes().to_vec());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_get_one_transaction_known_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let last = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last;
+        let block_txs = client.list_block_transactions(last.clone(), 1, None).await?;
+        let tx = block_txs.ids.first().expect("masterchain block has transactions");
+
+        let response = client.get_one_transaction(last.clone(), (-1, tx.account.unwrap()), tx.lt.unwrap()).await?;
+
+        assert_eq!(response.id, last);
+        assert!(!response.transaction.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_transactions_stream_test() -> anyhow::Result<()> {
+        // T3, T2 on the first page and T1, the first transaction of the account, on the second
+        let pages: Vec<_> = include_str!("../fixtures/account_transactions.hex").lines().skip(3).map(|line| hex::decode(line).unwrap()).collect();
+        let last_hash: Int256 = hex::decode("84b25ed188124a75558076915ae17cc34a71fa0dae0a1b323c4a94fa855e4daf")?.try_into().unwrap();
+        let (addr, server_key) = provided_answering_server(usize::MAX, move |data| {
+            let request = from_bytes_boxed::<LiteServerGetTransactions>(&data).unwrap();
+            let block = TonNodeBlockIdExt { workchain: 0, shard: i64::MIN, seqno: 1, root_hash: [0; 32], file_hash: [0; 32] };
+            let list = match request.lt {
+                30 => LiteServerTransactionList { ids: vec![block.clone(), block], transactions: pages[0].clone() },
+                10 => LiteServerTransactionList { ids: vec![block], transactions: pages[1].clone() },
+                lt => panic!("unexpected lt {}", lt)
+            };
+
+            to_bytes_boxed(&list)
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let transactions: Vec<_> = client.transactions_stream((0, [0x11; 32]), 30, last_hash).try_collect().await?;
+
+        assert_eq!(transactions.iter().map(|tx| tx.lt).collect::<Vec<_>>(), vec![30, 20, 10]);
+        assert_eq!(transactions[0].hash, last_hash);
+        assert_eq!(transactions[2].prev_lt, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_get_libraries_test() -> anyhow::Result<()> {
+        let empty_cell = hex::decode("b5ee9c724101010100020000004cacb9cd")?;
+        let empty_cell_hash = read_single_root(&empty_cell)?.hash();
+        let (addr, server_key) = provided_answering_server(usize::MAX, move |data| {
+            let request = from_bytes_boxed::<LiteServerGetLibraries>(&data).unwrap();
+            let result = request.library_list.into_iter()
+                .filter(|hash| hash == &empty_cell_hash)
+                .map(|hash| LiteServerLibraryEntry { hash, data: empty_cell.clone() })
+                .collect();
+
+            to_bytes_boxed(&LiteServerLibraryResult { result })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let response = client.get_libraries(&[empty_cell_hash, [7; 32]]).await?;
+
+        assert_eq!(response.result.len(), 1);
+        assert_eq!(response.result[0].hash, empty_cell_hash);
+        assert_eq!(read_single_root(&response.result[0].data)?.hash(), empty_cell_hash);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_send_message_and_wait_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let empty_cell = hex::decode("b5ee9c724101010100020000004cacb9cd")?;
+
+        // the server rejects the message before there is anything to wait for
+        let response = client.send_message_and_wait(empty_cell, (0, [0; 32]), Duration::from_secs(10)).await;
+
+        assert!(matches!(response, Err(Error::LiteServerError { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_send_message_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let empty_cell = hex::decode("b5ee9c724101010100020000004cacb9cd")?;
+
+        let response = client.send_message(empty_cell).await;
+
+        assert!(matches!(response, Err(Error::LiteServerError { .. })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_drop_test() -> anyhow::Result<()> {
+        let future = {
+            let client = provided_client().await?;
+
+            client.oneshot(LiteServerGetMasterchainInfo::default())
+        };
+
+        let response = future.await;
+
+        assert!(response.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_reconnects_after_connection_dropped() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_server(1).await?;
+        let mut client = LiteServerClient::builder(addr, &server_key)
+            .set_reconnect_policy(ReconnectPolicy { max_attempts: 3, delay: Duration::from_millis(10) })
+            .await?;
+
+        let first = (&mut client).oneshot(LiteServerGetTime::default()).await?;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let second = (&mut client).oneshot(LiteServerGetTime::default()).await?;
+
+        assert_eq!(first.now, 1);
+        assert_eq!(second.now, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_ready_waits_for_reconnect() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_server(1).await?;
+        let mut client = LiteServerClient::builder(addr, &server_key)
+            .set_reconnect_policy(ReconnectPolicy { max_attempts: 3, delay: Duration::from_millis(300) })
+            .await?;
+
+        // the server drops the connection after the first query
+        (&mut client).oneshot(LiteServerGetTime::default()).await?;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let reconnecting = tokio::time::timeout(Duration::from_millis(100), ServiceExt::<LiteServerGetTime>::ready(&mut client)).await.is_err();
+        let reconnected = tokio::time::timeout(Duration::from_secs(1), ServiceExt::<LiteServerGetTime>::ready(&mut client)).await.map(|ready| ready.is_ok());
+
+        assert!(reconnecting);
+        assert!(matches!(reconnected, Ok(true)));
+        assert_eq!((&mut client).oneshot(LiteServerGetTime::default()).await?.now, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_closed_when_reconnect_disabled() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_server(1).await?;
+        let mut client = LiteServerClient::builder(addr, &server_key)
+            .set_reconnect_policy(ReconnectPolicy::disabled())
+            .await?;
+
+        (&mut client).oneshot(LiteServerGetTime::default()).await?;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let response = (&mut client).oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::Transport(TransportError::ChannelClosed))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_server_time_offset_test() -> anyhow::Result<()> {
+        // the server always answers `now: 1`
+        let (addr, server_key) = provided_server(usize::MAX).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let before = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let offset = client.server_time_offset().await?;
+        let after = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+
+        assert!(offset >= 1500 - after - 1);
+        assert!(offset <= 1500 - before);
+        assert!(logs_contain("liteserver clock is off"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_request_span_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let request = from_bytes_boxed::<LiteServerGetBlockHeader>(&data).unwrap();
+
+            to_bytes_boxed(&LiteServerBlockHeader { id: request.id, mode: request.mode, header_proof: vec![] })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 42, root_hash: [0; 32], file_hash: [0; 32] };
+
+        client.oneshot(LiteServerGetBlockHeader { id, mode: 0 }).await?;
+
+        assert!(logs_contain("liteserver_request{request=\"liteServer.getBlockHeader\""));
+        assert!(logs_contain("seqno=42"));
+        assert!(logs_contain("liteserver request finished elapsed_ms="));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn client_request_metrics_test() -> anyhow::Result<()> {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            // a current thread runtime keeps the request on the thread the local recorder is set on
+            tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(async {
+                let (addr, server_key) = provided_answering_server(usize::MAX, |_| to_bytes_boxed(&LiteServerCurrentTime { now: 1 })).await?;
+                let client = LiteServerClient::connect(addr, &server_key).await?;
+
+                client.clone().oneshot(LiteServerGetTime::default()).await?;
+                client.oneshot(LiteServerGetTime::default()).await?;
+
+                Ok::<_, anyhow::Error>(())
+            })
+        })?;
+
+        let requests = snapshotter.snapshot().into_vec().into_iter()
+            .find(|(key, ..)| key.key().name() == "ton_liteserver_client_requests_total")
+            .map(|(key, _, _, value)| (key.key().labels().map(|label| (label.key().to_owned(), label.value().to_owned())).collect::<Vec<_>>(), value));
+
+        assert_eq!(requests, Some((vec![("request".to_owned(), "liteServer.getTime".to_owned()), ("outcome".to_owned(), "ok".to_owned())], DebugValue::Counter(2))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_close_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |_| to_bytes_boxed(&LiteServerCurrentTime { now: 1 })).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let mut clone = client.clone();
+        (&mut clone).oneshot(LiteServerGetTime::default()).await?;
+
+        client.close().await;
+
+        assert!(matches!(ServiceExt::<LiteServerGetMasterchainInfo>::ready(&mut clone).await, Err(Error::Transport(TransportError::ChannelClosed))));
+        assert!(matches!(clone.oneshot(LiteServerGetTime::default()).await, Err(Error::Transport(TransportError::ChannelClosed))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_header_range_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_answering_server(usize::MAX, |data| {
+            let Ok(request) = from_bytes_boxed::<LiteServerLookupBlock>(&data) else {
+                let last = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 12, root_hash: [0; 32], file_hash: [0; 32] };
+
+                return to_bytes_boxed(&LiteServerMasterchainInfo { last, state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } });
+            };
+            let id = TonNodeBlockIdExt { workchain: request.id.workchain, shard: request.id.shard, seqno: request.id.seqno, root_hash: [0; 32], file_hash: [0; 32] };
+
+            to_bytes_boxed(&LiteServerBlockHeader { id, mode: request.mode, header_proof: vec![] })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let headers: Vec<_> = client.header_range(5, 9, 2).try_collect().await?;
+        let past_tip: Vec<_> = client.header_range(10, 20, 4).try_collect().await?;
+        let empty: Vec<_> = client.header_range(9, 5, 2).try_collect().await?;
+
+        assert_eq!(headers.iter().map(|header| header.id.seqno).collect::<Vec<_>>(), vec![5, 6, 7, 8, 9]);
+        assert!(headers.iter().all(|header| header.id.workchain == -1 && header.id.shard == i64::MIN));
+        assert_eq!(past_tip.iter().map(|header| header.id.seqno).collect::<Vec<_>>(), vec![10, 11, 12]);
+        assert!(empty.is_empty());
+
+        Ok(())
+    }
+
+    /// Answers `WaitSeqno` lookups of blocks up to 11 and times out the later ones
+    async fn provided_waiting_server() -> anyhow::Result<(SocketAddrV4, ServerKey)> {
+        provided_answering_server(usize::MAX, |data| {
+            // waitMasterchainSeqno prefix with the constructor number, seqno and timeout
+            let seqno = i32::from_le_bytes(data[4..8].try_into().unwrap());
+            let request = from_bytes_boxed::<LiteServerLookupBlock>(&data[12..]).unwrap();
+            if seqno > 11 {
+                return to_bytes_boxed(&LiteServerError { code: 652, message: "timeout".to_owned() });
+            }
+
+            let hash = [request.id.seqno as u8; 32];
+            let id = TonNodeBlockIdExt { workchain: request.id.workchain, shard: request.id.shard, seqno: request.id.seqno, root_hash: hash, file_hash: hash };
+
+            to_bytes_boxed(&LiteServerBlockHeader { id, mode: request.mode, header_proof: vec![] })
+        }).await
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_wait_for_seqno_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_waiting_server().await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let id = client.wait_for_seqno(11, Duration::from_secs(1)).await?;
+
+        assert_eq!((id.workchain, id.shard, id.seqno), (-1, i64::MIN, 11));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_lookup_block_or_wait_test() -> anyhow::Result<()> {
+        // the server is at block 10 and produces 11 within the wait
+        let (addr, server_key) = provided_waiting_server().await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let header = client.lookup_block_or_wait(10 + 1, Duration::from_secs(1)).await?;
+
+        assert_eq!(header.id.seqno, 11);
+        assert_eq!((header.id.root_hash, header.id.file_hash), ([11; 32], [11; 32]));
+        assert_eq!(header.mode, MODE_SEQNO);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_wait_for_seqno_timeout_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_waiting_server().await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        let response = client.wait_for_seqno(1000, Duration::from_secs(1)).await;
+
+        assert!(matches!(response, Err(Error::Timeout)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_wait_seqno_server_timeout_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_waiting_server().await?;
+        // the server answers a wait past block 11 with the 652 of a wait that ran out, a healthy answer the
+        // pool and retries leave alone
+        let client = LiteServerClient::builder(addr, &server_key)
+            .set_request_timeout(Duration::from_millis(100))
+            .await?;
+        let id = TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno: 1000 };
+        let request = WaitSeqno::with_server_timeout(LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None }, 1000, Duration::from_secs(1));
+
+        let response = client.oneshot(request).await;
+
+        assert!(matches!(response, Err(Error::ServerTimeout(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_wait_seqno_server_timeout_keeps_pool_backend_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_waiting_server().await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let pool = LiteServerPool::new(vec![client]);
+        let id = TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno: 1000 };
+        let request = WaitSeqno::with_server_timeout(LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None }, 1000, Duration::from_secs(1));
+
+        let response = pool.clone().oneshot(request).await;
+
+        assert!(matches!(response, Err(Error::ServerTimeout(_))));
+        assert_eq!(pool.available(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    #[ignore]
+    async fn client_wait_for_next_seqno_test() -> anyhow::Result<()> {
+        let client = provided_client().await?;
+        let current = client.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+        let id = client.wait_for_seqno(current.last.seqno + 1, Duration::from_secs(10)).await?;
+
+        assert_eq!(id.seqno, current.last.seqno + 1);
+
+        Ok(())
+    }
+
+    /// Reports liteserver `version` with `capabilities`, answers the other requests with a liteserver error and counts them
+    async fn provided_versioned_server(version: i32, capabilities: i64, requests: Arc<AtomicUsize>) -> anyhow::Result<(SocketAddrV4, ServerKey)> {
+        provided_answering_server(usize::MAX, move |data| {
+            if from_bytes_boxed::<LiteServerGetMasterchainInfoExt>(&data).is_err() {
+                requests.fetch_add(1, Ordering::SeqCst);
+
+                return to_bytes_boxed(&LiteServerError { code: -400, message: "unsupported".to_owned() });
+            }
+
+            let last = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+            to_bytes_boxed(&LiteServerMasterchainInfoExt { mode: 0, version, capabilities, last, last_utime: 1, now: 1, state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } })
+        }).await
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_masterchain_info_cached_test() -> anyhow::Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (addr, server_key) = provided_answering_server(usize::MAX, {
+            let requests = requests.clone();
+            move |_| {
+                let seqno = requests.fetch_add(1, Ordering::SeqCst) as i32 + 1;
+                let last = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] };
+
+                to_bytes_boxed(&LiteServerMasterchainInfo { last, state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } })
+            }
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let max_age = Duration::from_millis(500);
+
+        let concurrent = futures::future::try_join_all((0 .. 5).map(|_| client.get_masterchain_info_cached(max_age))).await?;
+        let later = client.clone().get_masterchain_info_cached(max_age).await?;
+
+        assert!(concurrent.iter().all(|info| info.last.seqno == 1));
+        assert_eq!(later.last.seqno, 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(max_age).await;
+        assert_eq!(client.get_masterchain_info_cached(max_age).await?.last.seqno, 2);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_account_state_at_tip_test() -> anyhow::Result<()> {
+        let block_id = |seqno| TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] };
+        let (addr, server_key) = provided_answering_server(usize::MAX, move |data| match from_bytes_boxed::<LiteServerGetAccountState>(&data) {
+            Ok(request) => to_bytes_boxed(&LiteServerAccountState { id: request.id.clone(), shardblk: request.id, shard_proof: vec![], proof: vec![], state: vec![] }),
+            Err(_) => to_bytes_boxed(&LiteServerMasterchainInfo { last: block_id(7), state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let tracker = MasterchainLastBlockTracker::new(futures::stream::iter([Ok(block_id(9))]));
+        tracker.wait_last().await?;
+
+        let reported = client.account_state_at_tip((-1, [0x11; 32]), None).await?;
+        let tracked = client.account_state_at_tip((-1, [0x11; 32]), Some(&tracker)).await?;
+
+        assert_eq!(reported.block_id, block_id(7));
+        assert_eq!(reported.id, block_id(7));
+        assert_eq!(tracked.block_id, block_id(9));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_healthcheck_test() -> anyhow::Result<()> {
+        for (last_utime, synced) in [(1718000000 - 5, true), (1718000000 - 600, false)] {
+            let (addr, server_key) = provided_answering_server(usize::MAX, move |_| {
+                let last = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 1, root_hash: [0; 32], file_hash: [0; 32] };
+
+                to_bytes_boxed(&LiteServerMasterchainInfoExt { mode: 0, version: 0x101, capabilities: 7, last, last_utime, now: 1718000000, state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } })
+            }).await?;
+            let client = LiteServerClient::connect(addr, &server_key).await?;
+
+            let status = client.healthcheck().await?;
+
+            assert_eq!(status.synced, synced);
+            assert_eq!(status.lag, Duration::from_secs((1718000000 - last_utime) as u64));
+            assert!(status.latency < Duration::from_secs(1));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_capabilities_test() -> anyhow::Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (addr, server_key) = provided_versioned_server(0x101, 7, requests.clone()).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+
+        let capabilities = client.capabilities().await?;
+        let response = client.get_block_proof(id, None).await;
+
+        assert_eq!(capabilities, Capabilities { version: 0x101, capabilities: 7 });
+        assert!(capabilities.has(CAP_RUN_SMC_METHOD));
+        // the request is sent and fails on the server side
+        assert!(matches!(response, Err(Error::LiteServerError { code: -400, .. })));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tl_schema_hash_test() {
+        let hash = LiteServerClient::tl_schema_hash();
+
+        assert_eq!(hash.len(), 64);
+        assert!(hash.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(hash, "0".repeat(64));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_capabilities_error_test() -> anyhow::Result<()> {
+        // the first getMasterchainInfoExt fails with a server error, the next ones are answered
+        let failed = Arc::new(AtomicBool::new(false));
+        let (addr, server_key) = provided_answering_server(usize::MAX, move |_| {
+            if !failed.swap(true, Ordering::SeqCst) {
+                return to_bytes_boxed(&LiteServerError { code: 601, message: "error".to_owned() });
+            }
+
+            let last = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+            to_bytes_boxed(&LiteServerMasterchainInfoExt { mode: 0, version: 0x101, capabilities: 7, last, last_utime: 1, now: 1, state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } })
+        }).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let (old_addr, old_server_key) = provided_answering_server(usize::MAX, |_| to_bytes_boxed(&LiteServerError { code: -400, message: "unknown query".to_owned() })).await?;
+        let old = LiteServerClient::connect(old_addr, &old_server_key).await?;
+
+        let failed = client.capabilities().await;
+        let capabilities = client.capabilities().await?;
+
+        assert!(matches!(failed, Err(Error::LiteServerError { code: 601, .. })));
+        assert_eq!(capabilities, Capabilities { version: 0x101, capabilities: 7 });
+        assert_eq!(old.capabilities().await?, Capabilities::default());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_unsupported_request_test() -> anyhow::Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (addr, server_key) = provided_versioned_server(0x100, CAP_PROOF_CHAINS, requests.clone()).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 7, root_hash: [1; 32], file_hash: [2; 32] };
+
+        let response = client.run_smc_method(0, id.clone(), (-1, [3; 32]), "seqno", vec![]).await;
+        let again = client.run_smc_method(0, id, (-1, [3; 32]), "seqno", vec![]).await;
+
+        assert!(matches!(response, Err(Error::Unsupported("liteServer.runSmcMethod"))));
+        assert!(matches!(again, Err(Error::Unsupported(_))));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_connect_any_test() -> anyhow::Result<()> {
+        let refused = TcpListener::bind("127.0.0.1:0").await?;
+        let SocketAddr::V4(refused_addr) = refused.local_addr()? else { unreachable!() };
+        drop(refused);
+        let (addr, server_key) = provided_server(usize::MAX).await?;
+
+        let client = LiteServerClient::connect_any(&[refused_addr, addr], &server_key).await?;
+        let response = client.oneshot(LiteServerGetTime::default()).await?;
+
+        assert_eq!(response.now, 1);
+        assert!(logs_contain("connect failed, trying the next address"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_local_bind_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_server(usize::MAX).await?;
+        let options = SocketOptions { local_addr: Some(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)), reuse_address: true };
+
+        let connection = connect(addr, &server_key, &options).await?;
+        let local_addr = connection.get_ref().local_addr()?;
+        drop(connection);
+        let client = LiteServerClient::builder(addr, &server_key)
+            .set_socket_options(options)
+            .await?;
+        let response = client.oneshot(LiteServerGetTime::default()).await?;
+
+        assert_eq!(local_addr.ip(), Ipv4Addr::LOCALHOST);
+        assert_ne!(local_addr.port(), 0);
+        assert_eq!(response.now, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_request_timeout_test() -> anyhow::Result<()> {
+        let (addr, server_key) = provided_silent_server().await?;
+        let client = LiteServerClient::builder(addr, &server_key)
+            .set_request_timeout(Duration::from_millis(100))
+            .await?;
+
+        let started = tokio::time::Instant::now();
+        let response = client.oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::Timeout)));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_concurrent_requests_test() -> anyhow::Result<()> {
+        const REQUESTS: usize = 8;
+        let (addr, server_key) = provided_batching_server(REQUESTS).await?;
+        let client = LiteServerClient::connect(addr, &server_key).await?;
+
+        // the server answers only once every request is in, the last one first
+        let lookups = (1 ..= REQUESTS as i32)
+            .map(|seqno| client.lookup_block(TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno }, MODE_SEQNO, None, None));
+        let headers = tokio::time::timeout(Duration::from_secs(5), futures::future::try_join_all(lookups)).await??;
+
+        assert_eq!(headers.iter().map(|header| header.id.seqno).collect::<Vec<_>>(), (1 ..= REQUESTS as i32).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    /// Holds back the answers to `LiteServerLookupBlock` until `batch` queries are in, then answers them in reverse
+    async fn provided_batching_server(batch: usize) -> anyhow::Result<(SocketAddrV4, ServerKey)> {
+        let key = Ed25519Key::generate();
+        let server_key: ServerKey = key.public_key().to_bytes();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let SocketAddr::V4(addr) = listener.local_addr()? else { unreachable!() };
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok((_, mut connection)) = Server::handshake(stream, &key).await else { continue };
+
+                let mut pending = Vec::new();
+                while let Some(Ok(packet)) = connection.next().await {
+                    if is_ping_packet(&packet) {
+                        continue
+                    }
+
+                    let query = from_bytes_boxed::<AdnlMessageQuery>(&packet.data).unwrap();
+                    let request = from_bytes_boxed::<LiteServerQuery>(&query.query).unwrap();
+                    let request = from_bytes_boxed::<LiteServerLookupBlock>(&request.data).unwrap();
+                    let id = TonNodeBlockIdExt { workchain: request.id.workchain, shard: request.id.shard, seqno: request.id.seqno, root_hash: [0; 32], file_hash: [0; 32] };
+                    pending.push(AdnlMessageAnswer { query_id: query.query_id, answer: to_bytes_boxed(&LiteServerBlockHeader { id, mode: 0, header_proof: vec![] }) });
+                    if pending.len() < batch {
+                        continue
+                    }
+
+                    for answer in pending.drain(..).rev() {
+                        connection.send(Packet::new(to_bytes_boxed(&answer))).await.unwrap();
+                    }
+                }
+            }
+        });
+
+        Ok((addr, server_key))
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_identity_mismatch_test() -> anyhow::Result<()> {
+        let (addr, _) = provided_server(usize::MAX).await?;
+        let other_key: ServerKey = Ed25519Key::generate().public_key().to_bytes();
+
+        let error = LiteServerClient::connect(addr, &other_key).await.err().unwrap();
+
+        assert!(matches!(error.downcast_ref::<Error>(), Some(Error::IdentityMismatch)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn client_reset_handshake_test() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let SocketAddr::V4(addr) = listener.local_addr()? else { unreachable!() };
+        let server_key: ServerKey = Ed25519Key::generate().public_key().to_bytes();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut handshake = [0; 256];
+            tokio::io::AsyncReadExt::read_exact(&mut stream, &mut handshake).await.unwrap();
+            // a zero linger resets the connection on drop
+            stream.set_linger(Some(Duration::ZERO)).unwrap();
+        });
+
+        let error = LiteServerClient::connect(addr, &server_key).await.err().unwrap();
+
+        assert!(error.downcast_ref::<Error>().is_none());
+        assert!(error.is::<std::io::Error>());
+
+        Ok(())
+    }
+
+    async fn provided_silent_server() -> anyhow::Result<(SocketAddrV4, ServerKey)> {
+        let key = Ed25519Key::generate();
+        let server_key: ServerKey = key.public_key().to_bytes();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let SocketAddr::V4(addr) = listener.local_addr()? else { unreachable!() };
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok((_, mut connection)) = Server::handshake(stream, &key).await else { continue };
+
+                tokio::spawn(async move { while let Some(Ok(_)) = connection.next().await {} });
+            }
+        });
+
+        Ok((addr, server_key))
+    }
+
+    async fn provided_server(queries_per_connection: usize) -> anyhow::Result<(SocketAddrV4, ServerKey)> {
+        provided_answering_server(queries_per_connection, |_| to_bytes_boxed(&LiteServerCurrentTime { now: 1 })).await
+    }
+
+    /// `respond` gets the serialized request of every query and returns the serialized response
+    async fn provided_answering_server<F>(queries_per_connection: usize, respond: F) -> anyhow::Result<(SocketAddrV4, ServerKey)>
+        where F: Fn(Bytes) -> Bytes + Send + Sync + 'static {
+        let key = Ed25519Key::generate();
+        let server_key: ServerKey = key.public_key().to_bytes();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let SocketAddr::V4(addr) = listener.local_addr()? else { unreachable!() };
+
+        let respond = Arc::new(respond);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok((_, mut connection)) = Server::handshake(stream, &key).await else { continue };
+                let respond = respond.clone();
+
+                // connections are served side by side, e.g. the one of `get_state` next to the client's
+                tokio::spawn(async move {
+                    let mut served = 0;
+                    while served < queries_per_connection {
+                        let Some(Ok(packet)) = connection.next().await else { break };
+                        if is_ping_packet(&packet) {
+                            continue
+                        }
+
+                        let query = from_bytes_boxed::<AdnlMessageQuery>(&packet.data).unwrap();
+                        let request = from_bytes_boxed::<LiteServerQuery>(&query.query).unwrap();
+                        let answer = AdnlMessageAnswer { query_id: query.query_id, answer: respond(request.data) };
+                        connection.send(Packet::new(to_bytes_boxed(&answer))).await.unwrap();
+                        served += 1;
+                    }
+                });
+            }
+        });
+
+        Ok((addr, server_key))
     }
 
     async fn provided_client() -> anyhow::Result<LiteServerClient> {
@@ -0,0 +1,101 @@
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::Path;
+use std::str::FromStr;
+use adnl_tcp::client::ServerKey;
+use anyhow::anyhow;
+use base64::Engine;
+use serde::Deserialize;
+
+/// Subset of the TON `global.config.json` needed to connect to liteservers
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GlobalConfig {
+    liteservers: Vec<LiteServerDesc>
+}
+
+impl GlobalConfig {
+    pub async fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+
+        content.parse()
+    }
+
+    pub fn liteservers(&self) -> &[LiteServerDesc] {
+        &self.liteservers
+    }
+}
+
+impl FromStr for GlobalConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LiteServerId {
+    #[serde(rename = "@type")]
+    pub typ: String,
+    pub key: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LiteServerDesc {
+    pub ip: i32,
+    pub port: u16,
+    pub id: LiteServerId
+}
+
+impl LiteServerDesc {
+    /// `ip` is stored as a signed big-endian integer, so it has to be reinterpreted as u32 first
+    pub fn addr(&self) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::from(self.ip as u32), self.port)
+    }
+
+    pub fn server_key(&self) -> anyhow::Result<ServerKey> {
+        base64::engine::general_purpose::STANDARD.decode(&self.id.key)?
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("server key must be 32 bytes long"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_global_config() -> anyhow::Result<()> {
+        let config: GlobalConfig = r#"{
+            "@type": "config.global",
+            "liteservers": [
+                {
+                    "ip": -2018135749,
+                    "port": 53312,
+                    "id": {
+                        "@type": "pub.ed25519",
+                        "key": "aF91CuUHuuOv9rm2W5+O/4h38M3sRm40DtSdRxQhmtQ="
+                    }
+                }
+            ],
+            "validator": {
+                "@type": "validator.config.global"
+            }
+        }"#.parse()?;
+
+        let liteserver = &config.liteservers()[0];
+
+        assert_eq!(config.liteservers().len(), 1);
+        assert_eq!(liteserver.addr(), SocketAddrV4::new(Ipv4Addr::new(135, 181, 177, 59), 53312));
+        assert_eq!(liteserver.server_key()?.len(), 32);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_server_key() {
+        let liteserver = LiteServerDesc { ip: 0, port: 0, id: LiteServerId { typ: "pub.ed25519".to_owned(), key: "AAAA".to_owned() } };
+
+        assert!(liteserver.server_key().is_err());
+    }
+}
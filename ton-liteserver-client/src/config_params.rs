@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{bail, ensure};
+use tokio::sync::{watch, Mutex};
+use tower::{Service, ServiceExt};
+
+use crate::block::mc_block_extra;
+use crate::cell::{dict_entries, key_to_uint, read_single_root, Cell, CellSlice};
+use crate::client::Error;
+use crate::tl::{Int256, LiteServerConfigInfo, LiteServerGetConfigParams, TonNodeBlockIdExt};
+
+pub const CONFIG_ADDRESS: i32 = 0;
+pub const ELECTOR_ADDRESS: i32 = 1;
+pub const PREVIOUS_VALIDATORS: i32 = 32;
+pub const CURRENT_VALIDATORS: i32 = 34;
+pub const NEXT_VALIDATORS: i32 = 36;
+
+impl LiteServerConfigInfo {
+    pub fn config_params(&self) -> anyhow::Result<ConfigParams> {
+        ConfigParams::from_state_proof(&self.config_proof)
+    }
+}
+
+/// Masterchain config params carried by a `liteServer.configInfo` proof
+#[derive(Debug, Clone)]
+pub struct ConfigParams {
+    address: Int256,
+    params: BTreeMap<i32, Arc<Cell>>,
+}
+
+impl ConfigParams {
+    /// `config_proof` is a merkle proof of the masterchain state, see `LiteServerConfigInfo`
+    pub fn from_state_proof(config_proof: &[u8]) -> anyhow::Result<Self> {
+        let root = read_single_root(config_proof)?;
+
+        Self::from_state(root.proof_root()?)
+    }
+
+    pub fn from_state(state: &Cell) -> anyhow::Result<Self> {
+        let mut slice = mc_state_extra(state)?;
+        if slice.load_bit()? {
+            // shard_hashes
+            slice.load_reference()?;
+        }
+
+        Self::parse(slice)
+    }
+
+    /// Config carried by a masterchain key block, `block` being the root of a block proof
+    pub fn from_key_block(block: &Cell) -> anyhow::Result<Self> {
+        let Some((key_block, mut slice)) = mc_block_extra(block)? else {
+            bail!("not a masterchain block");
+        };
+        ensure!(key_block, "not a key block");
+        // shard_hashes and shard_fees
+        for _ in 0..2 {
+            if slice.load_bit()? {
+                slice.load_reference()?;
+            }
+        }
+        // fees and create of the shard_fees extra
+        for _ in 0..2 {
+            slice.load_var_uint(4)?;
+            if slice.load_bit()? {
+                slice.load_reference()?;
+            }
+        }
+        // prev_blk_signatures, recover_create_msg and mint_msg
+        slice.load_reference()?;
+
+        Self::parse(slice)
+    }
+
+    fn parse(mut slice: CellSlice) -> anyhow::Result<Self> {
+        let address = slice.load_int256()?;
+
+        let mut params = BTreeMap::new();
+        for (key, mut value) in dict_entries(slice.load_reference()?, 32)? {
+            params.insert(key_to_uint(&key) as u32 as i32, value.load_reference()?.clone());
+        }
+
+        Ok(Self { address, params })
+    }
+
+    /// Address of the config smart contract in the masterchain
+    pub fn address(&self) -> Int256 {
+        self.address
+    }
+
+    /// Raw cell of param `index`, `None` when the proof doesn't carry it
+    pub fn param(&self, index: i32) -> Option<&Arc<Cell>> {
+        self.params.get(&index)
+    }
+
+    pub fn indices(&self) -> impl Iterator<Item = i32> + '_ {
+        self.params.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn config_address(&self) -> anyhow::Result<Option<Int256>> {
+        self.address_param(CONFIG_ADDRESS)
+    }
+
+    pub fn elector_address(&self) -> anyhow::Result<Option<Int256>> {
+        self.address_param(ELECTOR_ADDRESS)
+    }
+
+    /// One of [`PREVIOUS_VALIDATORS`], [`CURRENT_VALIDATORS`] or [`NEXT_VALIDATORS`]
+    pub fn validator_set(&self, index: i32) -> anyhow::Result<Option<ValidatorSet>> {
+        self.param(index).map(|cell| ValidatorSet::parse(cell)).transpose()
+    }
+
+    fn address_param(&self, index: i32) -> anyhow::Result<Option<Int256>> {
+        self.param(index).map(|cell| cell.parser().load_int256()).transpose()
+    }
+}
+
+/// `McStateExtra` of a masterchain state, positioned right after its tag
+pub(crate) fn mc_state_extra(state: &Cell) -> anyhow::Result<CellSlice<'_>> {
+    let mut slice = state.parser();
+    ensure!(slice.load_uint(32)? == 0x9023afe2, "not a ShardStateUnsplit");
+    // global_id, shard_id, seq_no, vert_seq_no, gen_utime, gen_lt, min_ref_mc_seqno and before_split
+    slice.skip(32 + 104 + 32 + 32 + 32 + 64 + 32 + 1)?;
+    // out_msg_queue_info, accounts and the cell with libraries and master_ref
+    for _ in 0..3 {
+        slice.load_reference()?;
+    }
+    ensure!(slice.load_bit()?, "not a masterchain state");
+
+    let mut slice = slice.load_reference()?.parser();
+    ensure!(slice.load_uint(16)? == 0xcc26, "not a McStateExtra");
+
+    Ok(slice)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorSet {
+    pub utime_since: u32,
+    pub utime_until: u32,
+    pub total: u16,
+    pub main: u16,
+    pub total_weight: u64,
+    pub list: Vec<ValidatorDescr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorDescr {
+    pub public_key: Int256,
+    pub weight: u64,
+    pub adnl_addr: Option<Int256>,
+}
+
+impl ValidatorSet {
+    fn parse(cell: &Cell) -> anyhow::Result<Self> {
+        let mut slice = cell.parser();
+        let tag = slice.load_uint(8)?;
+        let utime_since = slice.load_uint(32)? as u32;
+        let utime_until = slice.load_uint(32)? as u32;
+        let total = slice.load_uint(16)? as u16;
+        let main = slice.load_uint(16)? as u16;
+
+        let (total_weight, entries) = match tag {
+            0x11 => (None, slice.parse_dict(16)?),
+            0x12 => (Some(slice.load_uint(64)?), slice.load_dict(16)?),
+            tag => bail!("unknown ValidatorSet tag {:#x}", tag),
+        };
+        let list = entries
+            .into_iter()
+            .map(|(_, value)| ValidatorDescr::parse(value))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let total_weight = total_weight.unwrap_or_else(|| list.iter().map(|v| v.weight).sum());
+
+        Ok(Self { utime_since, utime_until, total, main, total_weight, list })
+    }
+}
+
+impl ValidatorDescr {
+    fn parse(mut slice: CellSlice) -> anyhow::Result<Self> {
+        let tag = slice.load_uint(8)?;
+        ensure!(tag == 0x53 || tag == 0x73, "unknown ValidatorDescr tag {:#x}", tag);
+        ensure!(slice.load_uint(32)? == 0x8e81278a, "unknown SigPubKey");
+        let public_key = slice.load_int256()?;
+        let weight = slice.load_uint(64)?;
+        let adnl_addr = if tag == 0x73 { Some(slice.load_int256()?) } else { None };
+
+        Ok(Self { public_key, weight, adnl_addr })
+    }
+}
+
+/// Key block the config params were fetched for and the params
+type CachedConfig = (TonNodeBlockIdExt, Arc<ConfigParams>);
+
+/// Config params of the latest key block. The config only changes in key blocks, so the params are fetched
+/// once per key block `key_block` reports, e.g. [`crate::tracker::KeyBlockTracker::receiver`]. Clones share the cache
+#[derive(Clone)]
+pub struct ConfigCache<S> {
+    service: S,
+    key_block: watch::Receiver<Option<TonNodeBlockIdExt>>,
+    params: Arc<[i32]>,
+    cached: Arc<Mutex<Option<CachedConfig>>>,
+}
+
+impl<S> ConfigCache<S>
+    where S: Service<LiteServerGetConfigParams, Response = LiteServerConfigInfo, Error = Error> + Clone {
+    /// Caches `params`, all of them are fetched by a single `liteServer.getConfigParams`
+    pub fn new(service: S, key_block: watch::Receiver<Option<TonNodeBlockIdExt>>, params: &[i32]) -> Self {
+        Self { service, key_block, params: params.into(), cached: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Params as of the latest key block, waits for the first key block to be found
+    pub async fn config(&self) -> Result<Arc<ConfigParams>, Error> {
+        let id = self.key_block.clone()
+            .wait_for(Option::is_some).await
+            .map_err(|_| Error::NotFound("key block tracker is closed".to_owned()))?
+            .clone()
+            .expect("key block is found");
+
+        // held over the request, so concurrent callers share a single fetch
+        let mut cached = self.cached.lock().await;
+        if let Some((cached_id, config)) = cached.as_ref() {
+            if *cached_id == id {
+                return Ok(config.clone());
+            }
+        }
+
+        let info = self.service.clone()
+            .oneshot(LiteServerGetConfigParams { mode: 0, id: id.clone(), param_list: self.params.to_vec() })
+            .await?;
+        let config = Arc::new(info.config_params().map_err(|_| Error::Decode)?);
+        *cached = Some((id, config.clone()));
+
+        Ok(config)
+    }
+
+    /// `None` when the config doesn't have param `index` or it isn't one of the cached params
+    pub async fn param(&self, index: i32) -> Result<Option<Arc<Cell>>, Error> {
+        Ok(self.config().await?.param(index).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::mock::MockLiteServer;
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    fn fixture() -> Vec<u8> {
+        hex::decode(include_str!("../fixtures/config_all.hex").trim()).unwrap()
+    }
+
+    #[test]
+    fn elector_address_test() {
+        let config = ConfigParams::from_state_proof(&fixture()).unwrap();
+
+        assert_eq!(config.elector_address().unwrap(), Some([0x33; 32]));
+        assert_eq!(config.config_address().unwrap(), Some(config.address()));
+        assert_eq!(config.indices().collect::<Vec<_>>(), vec![0, 1, 34]);
+    }
+
+    #[test]
+    fn validator_set_test() {
+        let config = ConfigParams::from_state_proof(&fixture()).unwrap();
+
+        let validators = config.validator_set(CURRENT_VALIDATORS).unwrap().unwrap();
+
+        assert_eq!(validators.total, 2);
+        assert_eq!(validators.total_weight, 2001);
+        assert_eq!(validators.list.len(), 2);
+        assert_eq!(validators.list[1].public_key, [1; 32]);
+        assert_eq!(validators.list[1].weight, 1001);
+        assert_eq!(config.validator_set(NEXT_VALIDATORS).unwrap(), None);
+    }
+
+    #[test]
+    fn requested_params_test() {
+        let data = hex::decode(include_str!("../fixtures/config_params.hex").trim()).unwrap();
+
+        let config = ConfigParams::from_state_proof(&data).unwrap();
+
+        assert_eq!(config.indices().collect::<Vec<_>>(), vec![0, 34]);
+        assert_eq!(config.elector_address().unwrap(), None);
+        assert!(config.validator_set(CURRENT_VALIDATORS).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn config_cache_refetches_on_key_block_test() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let mock = MockLiteServer::default().on({
+            let fetches = fetches.clone();
+            move |request: LiteServerGetConfigParams| {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                assert_eq!(request.param_list, vec![0, 34]);
+
+                let config_proof = hex::decode(include_str!("../fixtures/config_params.hex").trim()).unwrap();
+                Ok(LiteServerConfigInfo { mode: 0, id: request.id, state_proof: vec![], config_proof })
+            }
+        });
+        let (tx, rx) = watch::channel(Some(block_id(100)));
+        let cache = ConfigCache::new(mock, rx, &[0, 34]);
+
+        // blocks after the key block one by one, the key block stays the same
+        for _ in 0..3 {
+            assert!(cache.param(CURRENT_VALIDATORS).await.unwrap().is_some());
+        }
+        assert_eq!(cache.param(ELECTOR_ADDRESS).await.unwrap(), None);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        tx.send_replace(Some(block_id(110)));
+        assert!(cache.clone().param(CONFIG_ADDRESS).await.unwrap().is_some());
+        assert!(cache.param(CONFIG_ADDRESS).await.unwrap().is_some());
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn not_a_proof_test() {
+        let empty_cell = hex::decode("b5ee9c724101010100020000004cacb9cd").unwrap();
+
+        assert!(ConfigParams::from_state_proof(&empty_cell).is_err());
+    }
+}
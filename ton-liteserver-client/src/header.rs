@@ -0,0 +1,144 @@
+//! Modes of `liteServer.getBlockHeader`, they select the parts of the block the header proof keeps unpruned,
+//! and the decoding of those parts
+
+use anyhow::{bail, ensure};
+use crate::cell::{CellSlice, CellType};
+use crate::client::Error;
+use crate::proof::checked_proof_root;
+use crate::tl::LiteServerBlockHeader;
+
+/// Keep the `state_update` of the block
+pub const WITH_STATE_UPDATE: i32 = 1;
+/// Keep the `value_flow` of the block
+pub const WITH_VALUE_FLOW: i32 = 1 << 1;
+/// Keep the `extra` of the block
+pub const WITH_EXTRA: i32 = 1 << 4;
+/// Keep the `shard_hashes` of a masterchain block
+pub const WITH_SHARD_HASHES: i32 = 1 << 5;
+/// Keep the `prev_blk_signatures` of a masterchain block
+pub const WITH_PREV_BLK_SIGNATURES: i32 = 1 << 6;
+
+const ALL: i32 = WITH_STATE_UPDATE | WITH_VALUE_FLOW | WITH_EXTRA | WITH_SHARD_HASHES | WITH_PREV_BLK_SIGNATURES;
+
+/// Grams of the `ValueFlow` of a block, the extra currencies are left out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueFlow {
+    pub from_prev_blk: u128,
+    pub to_next_blk: u128,
+    pub imported: u128,
+    pub exported: u128,
+    pub fees_collected: u128,
+    /// Only in `value_flow_v2`
+    pub burned: Option<u128>,
+    pub fees_imported: u128,
+    pub recovered: u128,
+    pub created: u128,
+    pub minted: u128,
+}
+
+impl LiteServerBlockHeader {
+    /// Value flow of a header requested with [`WITH_VALUE_FLOW`], the proof is checked to be of the block `id`
+    pub fn value_flow(&self) -> anyhow::Result<ValueFlow> {
+        let block = checked_proof_root(&self.header_proof, &self.id.root_hash)?;
+        ensure!(block.parser().load_uint(32)? == 0x11ef55aa, "not a Block");
+
+        let cell = block.reference(1)?;
+        if cell.cell_type() == CellType::PrunedBranch {
+            bail!("value flow is pruned, request the header with WITH_VALUE_FLOW");
+        }
+        let mut slice = cell.parser();
+        let v2 = match slice.load_uint(32)? {
+            0xb8e48dfb => false,
+            0x3ebf98b7 => true,
+            tag => bail!("unknown ValueFlow tag {:#x}", tag)
+        };
+
+        let mut transfers = slice.load_reference()?.parser();
+        let from_prev_blk = load_grams(&mut transfers)?;
+        let to_next_blk = load_grams(&mut transfers)?;
+        let imported = load_grams(&mut transfers)?;
+        let exported = load_grams(&mut transfers)?;
+
+        let fees_collected = load_grams(&mut slice)?;
+        let burned = if v2 { Some(load_grams(&mut slice)?) } else { None };
+
+        let mut issued = slice.load_reference()?.parser();
+        let fees_imported = load_grams(&mut issued)?;
+        let recovered = load_grams(&mut issued)?;
+        let created = load_grams(&mut issued)?;
+        let minted = load_grams(&mut issued)?;
+
+        Ok(ValueFlow { from_prev_blk, to_next_blk, imported, exported, fees_collected, burned, fees_imported, recovered, created, minted })
+    }
+}
+
+/// Grams of a `CurrencyCollection`, the extra currencies are skipped
+fn load_grams(slice: &mut CellSlice) -> anyhow::Result<u128> {
+    let grams = slice.load_var_uint(4)?;
+    if slice.load_bit()? {
+        slice.load_reference()?;
+    }
+
+    Ok(grams)
+}
+
+pub(crate) fn validate(mode: i32) -> Result<(), Error> {
+    if mode & !ALL != 0 {
+        return Err(Error::InvalidRequest("block header mode has unknown bits set"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tl::TonNodeBlockIdExt;
+    use super::*;
+
+    /// First header of a fixture with lines of `mode seqno root_hash file_hash header_proof`
+    fn header(fixture: &str) -> LiteServerBlockHeader {
+        let fields: Vec<_> = fixture.lines().next().unwrap().split(' ').collect();
+        let id = TonNodeBlockIdExt {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: fields[1].parse().unwrap(),
+            root_hash: hex::decode(fields[2]).unwrap().try_into().unwrap(),
+            file_hash: hex::decode(fields[3]).unwrap().try_into().unwrap()
+        };
+
+        LiteServerBlockHeader { id, mode: fields[0].parse().unwrap(), header_proof: hex::decode(fields[4]).unwrap() }
+    }
+
+    #[test]
+    fn validate_test() {
+        assert!(validate(0).is_ok());
+        assert!(validate(WITH_STATE_UPDATE | WITH_VALUE_FLOW).is_ok());
+        assert!(validate(ALL).is_ok());
+        assert!(matches!(validate(1 << 2), Err(Error::InvalidRequest(_))));
+        assert!(matches!(validate(-1), Err(Error::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn value_flow_test() {
+        // masterchain block 7 with a `value_flow_v2`
+        let flow = header(include_str!("../fixtures/block_header_value_flow.hex")).value_flow().unwrap();
+
+        assert_eq!(flow.fees_collected, 1_500_000_000);
+        assert_eq!(flow.burned, Some(250_000_000));
+        assert_eq!(
+            flow.from_prev_blk + flow.imported + flow.fees_imported + flow.recovered + flow.created + flow.minted,
+            flow.to_next_blk + flow.exported + flow.fees_collected + flow.burned.unwrap()
+        );
+    }
+
+    #[test]
+    fn value_flow_pruned_test() {
+        let pruned = header(include_str!("../fixtures/block_header_modes.hex"));
+        let mut another_block = header(include_str!("../fixtures/block_header_value_flow.hex"));
+        another_block.id.root_hash = [0; 32];
+
+        assert_eq!(pruned.mode, 0);
+        assert!(pruned.value_flow().is_err());
+        assert!(another_block.value_flow().is_err());
+    }
+}
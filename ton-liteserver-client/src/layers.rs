@@ -0,0 +1,9 @@
+pub mod block_cache;
+pub mod cache;
+pub mod coalesce;
+pub mod monotonic;
+pub mod rate_limit;
+pub mod recommended;
+pub mod record;
+pub mod retry;
+pub mod timeout;
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tower::{Layer, Service};
+use crate::client::Error;
+use crate::tl::{LiteServerBlockData, LiteServerGetBlock, TonNodeBlockIdExt};
+
+#[derive(Debug, Clone)]
+pub struct BlockCacheLayer {
+    max_bytes: usize
+}
+
+impl BlockCacheLayer {
+    /// Keeps blocks of up to `max_bytes` of BoCs in total
+    pub fn new(max_bytes: usize) -> Self {
+        Self { max_bytes }
+    }
+}
+
+impl<S> Layer<S> for BlockCacheLayer {
+    type Service = BlockCache<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BlockCache::new(inner, self.max_bytes)
+    }
+}
+
+/// Caches `liteServer.getBlock` answers by block id, the least recently used blocks are evicted once the BoCs
+/// exceed the byte budget. A block larger than the whole budget is handed on without being cached. Clones share the same cache
+#[derive(Clone)]
+pub struct BlockCache<S> {
+    inner: S,
+    cache: Arc<Mutex<Lru>>
+}
+
+impl<S> BlockCache<S> {
+    pub fn new(inner: S, max_bytes: usize) -> Self {
+        Self { inner, cache: Arc::new(Mutex::new(Lru::new(max_bytes))) }
+    }
+
+    /// Total size of the cached BoCs
+    pub fn bytes(&self) -> usize {
+        self.cache.lock().unwrap().bytes
+    }
+}
+
+impl<S> Service<LiteServerGetBlock> for BlockCache<S>
+    where S: Service<LiteServerGetBlock, Response = LiteServerBlockData, Error = Error>,
+          S::Future: Send + 'static {
+    type Response = LiteServerBlockData;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: LiteServerGetBlock) -> Self::Future {
+        if let Some(block) = self.cache.lock().unwrap().get(&req.id) {
+            return futures::future::ready(Ok(block)).boxed()
+        }
+
+        let cache = self.cache.clone();
+        let response = self.inner.call(req);
+
+        async move {
+            let block = response.await?;
+            cache.lock().unwrap().insert(block.clone());
+
+            Ok(block)
+        }.boxed()
+    }
+}
+
+struct Lru {
+    max_bytes: usize,
+    bytes: usize,
+    tick: u64,
+    blocks: HashMap<TonNodeBlockIdExt, (u64, LiteServerBlockData)>,
+    /// Block ids by their last use
+    order: BTreeMap<u64, TonNodeBlockIdExt>
+}
+
+impl Lru {
+    fn new(max_bytes: usize) -> Self {
+        Self { max_bytes, bytes: 0, tick: 0, blocks: HashMap::new(), order: BTreeMap::new() }
+    }
+
+    fn get(&mut self, id: &TonNodeBlockIdExt) -> Option<LiteServerBlockData> {
+        self.tick += 1;
+        let (used, block) = self.blocks.get_mut(id)?;
+        self.order.remove(used);
+        self.order.insert(self.tick, id.clone());
+        *used = self.tick;
+
+        Some(block.clone())
+    }
+
+    fn insert(&mut self, block: LiteServerBlockData) {
+        let size = block.data.len();
+        if size > self.max_bytes || self.blocks.contains_key(&block.id) {
+            return;
+        }
+
+        while self.bytes + size > self.max_bytes {
+            let Some((_, id)) = self.order.pop_first() else { break };
+            if let Some((_, evicted)) = self.blocks.remove(&id) {
+                self.bytes -= evicted.data.len();
+            }
+        }
+
+        self.tick += 1;
+        self.bytes += size;
+        self.order.insert(self.tick, block.id.clone());
+        self.blocks.insert(block.id.clone(), (self.tick, block));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    /// Answers block `seqno` with a BoC of `seqno * 10` bytes
+    fn counting_service(calls: Arc<AtomicUsize>) -> impl Service<LiteServerGetBlock, Response = LiteServerBlockData, Error = Error, Future = impl Send> {
+        service_fn(move |req: LiteServerGetBlock| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let data = vec![0; req.id.seqno as usize * 10];
+
+            futures::future::ready(Ok(LiteServerBlockData { id: req.id, data }))
+        })
+    }
+
+    #[tokio::test]
+    async fn block_cache_evicts_least_recently_used_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = ServiceBuilder::new()
+            .layer(BlockCacheLayer::new(100))
+            .service(counting_service(calls.clone()));
+
+        for seqno in [1, 2, 3, 4] {
+            (&mut svc).oneshot(LiteServerGetBlock { id: block_id(seqno) }).await.unwrap();
+        }
+        assert_eq!(svc.bytes(), 100);
+        // block 1 becomes the most recently used
+        (&mut svc).oneshot(LiteServerGetBlock { id: block_id(1) }).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // evicts blocks 2 and 3
+        (&mut svc).oneshot(LiteServerGetBlock { id: block_id(5) }).await.unwrap();
+        assert_eq!(svc.bytes(), 100);
+
+        for seqno in [1, 4, 5] {
+            (&mut svc).oneshot(LiteServerGetBlock { id: block_id(seqno) }).await.unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+
+        (&mut svc).oneshot(LiteServerGetBlock { id: block_id(2) }).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        assert!(svc.bytes() <= 100);
+    }
+
+    #[tokio::test]
+    async fn block_cache_skips_blocks_over_budget_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = ServiceBuilder::new()
+            .layer(BlockCacheLayer::new(100))
+            .service(counting_service(calls.clone()));
+
+        (&mut svc).oneshot(LiteServerGetBlock { id: block_id(3) }).await.unwrap();
+        let large = (&mut svc).oneshot(LiteServerGetBlock { id: block_id(11) }).await.unwrap();
+        (&mut svc).oneshot(LiteServerGetBlock { id: block_id(11) }).await.unwrap();
+        (&mut svc).oneshot(LiteServerGetBlock { id: block_id(3) }).await.unwrap();
+
+        assert_eq!(large.data.len(), 110);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(svc.bytes(), 30);
+    }
+}
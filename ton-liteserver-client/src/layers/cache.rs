@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tower::{Layer, Service};
+use crate::client::Error;
+use crate::tl::{Int, LiteServerBlockHeader, LiteServerGetBlockHeader, TonNodeBlockIdExt};
+
+type Key = (TonNodeBlockIdExt, Int);
+
+#[derive(Debug, Clone)]
+pub struct CacheLayer {
+    capacity: usize,
+    ttl: Option<Duration>
+}
+
+impl CacheLayer {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, ttl: None }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+
+        self
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = Cache<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Cache::new(inner, self.capacity, self.ttl)
+    }
+}
+
+/// Caches `liteServer.getBlockHeader` answers by block id and mode, headers never change once the block exists.
+/// Clones share the same cache
+#[derive(Clone)]
+pub struct Cache<S> {
+    inner: S,
+    cache: Arc<quick_cache::sync::Cache<Key, (Instant, LiteServerBlockHeader)>>,
+    ttl: Option<Duration>
+}
+
+impl<S> Cache<S> {
+    pub fn new(inner: S, capacity: usize, ttl: Option<Duration>) -> Self {
+        Self { inner, cache: Arc::new(quick_cache::sync::Cache::new(capacity)), ttl }
+    }
+
+    fn get(&self, key: &Key) -> Option<LiteServerBlockHeader> {
+        let (inserted_at, header) = self.cache.get(key)?;
+        if self.ttl.is_some_and(|ttl| inserted_at.elapsed() > ttl) {
+            self.cache.remove(key);
+
+            return None
+        }
+
+        Some(header)
+    }
+}
+
+impl<S> Service<LiteServerGetBlockHeader> for Cache<S>
+    where S: Service<LiteServerGetBlockHeader, Response = LiteServerBlockHeader, Error = Error>,
+          S::Future: Send + 'static {
+    type Response = LiteServerBlockHeader;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: LiteServerGetBlockHeader) -> Self::Future {
+        let key = (req.id.clone(), req.mode);
+        if let Some(header) = self.get(&key) {
+            return futures::future::ready(Ok(header)).boxed()
+        }
+
+        let cache = self.cache.clone();
+        let response = self.inner.call(req);
+
+        async move {
+            let header = response.await?;
+            cache.insert(key, (Instant::now(), header.clone()));
+
+            Ok(header)
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    fn counting_service(calls: Arc<AtomicUsize>) -> impl Service<LiteServerGetBlockHeader, Response = LiteServerBlockHeader, Error = Error, Future = impl Send> {
+        service_fn(move |req: LiteServerGetBlockHeader| {
+            calls.fetch_add(1, Ordering::SeqCst);
+
+            futures::future::ready(Ok(LiteServerBlockHeader { id: req.id, mode: req.mode, header_proof: vec![] }))
+        })
+    }
+
+    #[tokio::test]
+    async fn cache_calls_inner_once_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = ServiceBuilder::new()
+            .layer(CacheLayer::new(16))
+            .service(counting_service(calls.clone()));
+
+        let first = (&mut svc).oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }).await.unwrap();
+        let second = (&mut svc).oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        (&mut svc).oneshot(LiteServerGetBlockHeader { id: block_id(2), mode: 0 }).await.unwrap();
+        (&mut svc).oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 1 }).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_expires_after_ttl_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = ServiceBuilder::new()
+            .layer(CacheLayer::new(16).with_ttl(Duration::from_millis(10)))
+            .service(counting_service(calls.clone()));
+
+        (&mut svc).oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        (&mut svc).oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
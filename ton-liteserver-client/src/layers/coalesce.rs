@@ -0,0 +1,181 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use adnl_tcp::serializer::to_bytes_boxed;
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use tower::{Layer, Service};
+use crate::client::Error;
+use crate::request::Requestable;
+
+type SharedResponse = Shared<BoxFuture<'static, Result<Arc<dyn Any + Send + Sync>, Error>>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct CoalesceLayer;
+
+impl CoalesceLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for CoalesceLayer {
+    type Service = Coalesce<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Coalesce::new(inner)
+    }
+}
+
+/// Shares one call of the inner service among identical requests while it is in flight, requests are identical when
+/// they serialize to the same bytes. Unlike `Cache` the answer is forgotten once it arrives. Clones share the calls
+#[derive(Clone)]
+pub struct Coalesce<S> {
+    inner: S,
+    in_flight: Arc<Mutex<HashMap<Vec<u8>, SharedResponse>>>
+}
+
+impl<S> Coalesce<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, in_flight: Default::default() }
+    }
+}
+
+impl<S, R> Service<R> for Coalesce<S>
+    where R: Requestable + 'static,
+          R::Response: Clone + Sync,
+          S: Service<R, Response = R::Response, Error = Error>,
+          S::Future: Send + 'static {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let key = to_bytes_boxed(&req);
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        let response = match in_flight.get(&key) {
+            Some(response) => response.clone(),
+            None => {
+                let response = self.inner.call(req);
+                let calls = self.in_flight.clone();
+                let response = {
+                    let key = key.clone();
+
+                    async move {
+                        let response = response.await.map(|response| Arc::new(response) as Arc<dyn Any + Send + Sync>);
+                        calls.lock().unwrap().remove(&key);
+
+                        response
+                    }
+                }.boxed().shared();
+                in_flight.insert(key.clone(), response.clone());
+
+                response
+            }
+        };
+        let mut waiter = Waiter { key, in_flight: self.in_flight.clone(), response };
+
+        async move {
+            let response = (&mut waiter.response).await?;
+
+            Ok(response.downcast_ref::<R::Response>().expect("identical requests have the same response type").clone())
+        }.boxed()
+    }
+}
+
+/// Share of a caller in a call in flight. The last caller to drop it before the answer arrives removes the
+/// call from `in_flight`, which drops the call of the inner service too
+struct Waiter {
+    key: Vec<u8>,
+    in_flight: Arc<Mutex<HashMap<Vec<u8>, SharedResponse>>>,
+    response: SharedResponse
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // `in_flight` holds the other share, the count is `None` once the answer arrived
+        let abandoned = in_flight.get(&self.key)
+            .is_some_and(|response| response.ptr_eq(&self.response) && self.response.strong_count() == Some(2));
+        let call = if abandoned { in_flight.remove(&self.key) } else { None };
+        drop(in_flight);
+
+        drop(call);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+    use crate::tl::{LiteServerBlockHeader, LiteServerGetBlockHeader, TonNodeBlockIdExt};
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    fn slow_service(calls: Arc<AtomicUsize>) -> impl Service<LiteServerGetBlockHeader, Response = LiteServerBlockHeader, Error = Error, Future = impl Send> + Clone {
+        service_fn(move |req: LiteServerGetBlockHeader| {
+            calls.fetch_add(1, Ordering::SeqCst);
+
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+
+                Ok(LiteServerBlockHeader { id: req.id, mode: req.mode, header_proof: vec![] })
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn coalesce_concurrent_requests_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(CoalesceLayer::new())
+            .service(slow_service(calls.clone()));
+
+        let responses = futures::future::join_all((0..100)
+            .map(|_| svc.clone().oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 })))
+            .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(responses.iter().all(|response| response.as_ref().unwrap().id == block_id(1)));
+    }
+
+    #[tokio::test]
+    async fn coalesce_forgets_answered_requests_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(CoalesceLayer::new())
+            .service(slow_service(calls.clone()));
+
+        svc.clone().oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }).await.unwrap();
+        svc.clone().oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }).await.unwrap();
+        svc.clone().oneshot(LiteServerGetBlockHeader { id: block_id(2), mode: 0 }).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn coalesce_forgets_cancelled_requests_test() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(CoalesceLayer::new())
+            .service(slow_service(calls.clone()));
+
+        let responses = futures::future::join_all((0..3)
+            .map(|_| tokio::time::timeout(Duration::from_millis(10), svc.clone().oneshot(LiteServerGetBlockHeader { id: block_id(1), mode: 0 }))))
+            .await;
+
+        assert!(responses.iter().all(|response| response.is_err()));
+        assert!(svc.in_flight.lock().unwrap().is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::task::{Context, Poll};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tower::{Layer, Service, ServiceExt};
+use crate::client::Error;
+use crate::request::Requestable;
+
+#[derive(Debug, Clone)]
+pub struct MonotonicLayer {
+    max_attempts: usize
+}
+
+impl MonotonicLayer {
+    pub fn new(max_attempts: usize) -> Self {
+        Self { max_attempts }
+    }
+}
+
+impl<S> Layer<S> for MonotonicLayer {
+    type Service = Monotonic<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Monotonic::new(inner, self.max_attempts)
+    }
+}
+
+/// Keeps a session from going back in time behind a pool. A request for a masterchain block at or below the highest
+/// one answered so far that fails with `Error::NotFound` hit a lagging backend, it is sent again up to `max_attempts`
+/// times so the pool moves it to another backend. Clones share the highest block
+#[derive(Debug, Clone)]
+pub struct Monotonic<S> {
+    inner: S,
+    max_attempts: usize,
+    highest: Arc<AtomicI32>
+}
+
+impl<S> Monotonic<S> {
+    pub fn new(inner: S, max_attempts: usize) -> Self {
+        Self { inner, max_attempts, highest: Default::default() }
+    }
+
+    /// Highest masterchain seqno a request was answered for, 0 before the first one
+    pub fn highest_seqno(&self) -> i32 {
+        self.highest.load(Ordering::Relaxed)
+    }
+}
+
+impl<S, R> Service<R> for Monotonic<S>
+    where R: Requestable + Clone + 'static,
+          S: Service<R, Response = R::Response, Error = Error> + Clone + Send + 'static,
+          S::Future: Send {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<R::Response, Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // every attempt waits for the inner service itself
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let seqno = req.block_id().filter(|id| id.workchain == -1).map(|id| id.seqno);
+        let inner = self.inner.clone();
+        let highest = self.highest.clone();
+        let max_attempts = self.max_attempts.max(1);
+
+        async move {
+            let mut attempt = 1;
+            loop {
+                let response = inner.clone().oneshot(req.clone()).await;
+                let Some(seqno) = seqno else { return response };
+
+                match response {
+                    Ok(_) => { highest.fetch_max(seqno, Ordering::Relaxed); },
+                    Err(Error::NotFound(_)) if seqno <= highest.load(Ordering::Relaxed) && attempt < max_attempts => {
+                        tracing::warn!(seqno, attempt, "seen block not found, backend lags behind");
+                        attempt += 1;
+
+                        continue
+                    },
+                    Err(_) => {}
+                }
+
+                return response;
+            }
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lookup::MODE_SEQNO;
+    use crate::mock::MockLiteServer;
+    use crate::pool::LiteServerPool;
+    use crate::tl::{LiteServerBlockHeader, LiteServerGetMasterchainInfo, LiteServerLookupBlock, LiteServerMasterchainInfo, TonNodeBlockId, TonNodeBlockIdExt, TonNodeZeroStateIdExt};
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    /// Liteserver with masterchain blocks up to `last`
+    fn synced_server(last: i32) -> MockLiteServer {
+        MockLiteServer::default()
+            .on(move |_: LiteServerGetMasterchainInfo| Ok(LiteServerMasterchainInfo { last: block_id(last), state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } }))
+            .on(move |request: LiteServerLookupBlock| match request.id.seqno {
+                seqno if seqno <= last => Ok(LiteServerBlockHeader { id: block_id(seqno), mode: 0, header_proof: vec![] }),
+                _ => Err(Error::NotFound("block not found".to_owned()))
+            })
+    }
+
+    fn lookup(seqno: i32) -> LiteServerLookupBlock {
+        LiteServerLookupBlock { mode: MODE_SEQNO, id: TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno }, lt: None, utime: None }
+    }
+
+    #[tokio::test]
+    async fn monotonic_moves_off_lagging_backend_test() -> anyhow::Result<()> {
+        // the pool takes turns, every other request goes to the backend behind
+        let pool = LiteServerPool::new(vec![synced_server(10), synced_server(5)]);
+        let svc = MonotonicLayer::new(3).layer(pool.clone());
+
+        for _ in 0 .. 4 {
+            assert_eq!(svc.clone().oneshot(lookup(10)).await?.id, block_id(10));
+        }
+        assert_eq!(svc.highest_seqno(), 10);
+
+        // without the layer the lagging backend answers
+        let answers = futures::future::join_all((0 .. 2).map(|_| pool.clone().oneshot(lookup(10)))).await;
+        assert!(answers.iter().any(|answer| matches!(answer, Err(Error::NotFound(_)))));
+
+        // a block nobody has seen yet isn't retried and doesn't move the highest one
+        assert!(matches!(svc.clone().oneshot(lookup(11)).await, Err(Error::NotFound(_))));
+        assert_eq!(svc.highest_seqno(), 10);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::time::Instant;
+use tower::{Layer, Service, ServiceExt};
+use crate::client::Error;
+
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    rate: f64,
+    burst: f64,
+    fail_fast: bool
+}
+
+impl RateLimitLayer {
+    /// Up to `requests_per_second` requests on average, bursts of as many requests pass at once
+    pub fn new(requests_per_second: u32) -> Self {
+        Self { rate: requests_per_second as f64, burst: requests_per_second.max(1) as f64, fail_fast: false }
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1) as f64;
+
+        self
+    }
+
+    /// Fail the requests over the limit with `Error::RateLimited` instead of delaying them
+    pub fn fail_fast(mut self) -> Self {
+        self.fail_fast = true;
+
+        self
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit { inner, bucket: Arc::new(Mutex::new(Bucket::new(self.rate, self.burst))), fail_fast: self.fail_fast }
+    }
+}
+
+/// Token bucket limiting the requests sent to the inner service. Every layered service gets a bucket of its own,
+/// clones share it, so layering each `LiteServerClient` limits them one by one
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    bucket: Arc<Mutex<Bucket>>,
+    fail_fast: bool
+}
+
+impl<S, R> Service<R> for RateLimit<S>
+    where R: Send + 'static,
+          S: Service<R, Error = Error> + Clone + Send + 'static,
+          S::Response: Send,
+          S::Future: Send {
+    type Response = S::Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let delay = {
+            let mut bucket = self.bucket.lock().unwrap();
+            if self.fail_fast && !bucket.has_token(Instant::now()) {
+                return futures::future::ready(Err(Error::RateLimited)).boxed();
+            }
+
+            bucket.reserve(Instant::now())
+        };
+        if delay.is_zero() {
+            return self.inner.call(req).boxed();
+        }
+
+        // the inner service sends the request once called, so the call waits for the token too
+        let inner = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, inner);
+
+        async move {
+            tokio::time::sleep(delay).await;
+
+            inner.oneshot(req).await
+        }.boxed()
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant
+}
+
+impl Bucket {
+    fn new(rate: f64, burst: f64) -> Self {
+        Self { rate, burst, tokens: burst, updated: Instant::now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+    }
+
+    fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+
+        self.tokens >= 1.0
+    }
+
+    /// Takes a token, the tokens can go into debt and the returned delay is how long the debt takes to pay off
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else if self.rate > 0.0 {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        } else {
+            Duration::MAX
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::{service_fn, ServiceBuilder};
+    use crate::tl::{LiteServerCurrentTime, LiteServerGetTime};
+    use super::*;
+
+    fn time_service() -> impl Service<LiteServerGetTime, Response = LiteServerCurrentTime, Error = Error, Future = impl Send> + Clone + Send {
+        service_fn(|_: LiteServerGetTime| futures::future::ready(Ok::<_, Error>(LiteServerCurrentTime { now: 1 })))
+    }
+
+    #[tokio::test]
+    async fn rate_limit_delays_burst_test() {
+        tokio::time::pause();
+        let svc = ServiceBuilder::new()
+            .layer(RateLimitLayer::new(20).with_burst(5))
+            .service(time_service());
+
+        let started = Instant::now();
+        let responses = futures::future::join_all((0..25).map(|_| svc.clone().oneshot(LiteServerGetTime::default()))).await;
+        let elapsed = started.elapsed();
+
+        assert!(responses.iter().all(Result::is_ok));
+        // 5 requests pass at once, the other 20 take a second at 20 requests per second
+        assert!(elapsed >= Duration::from_millis(999), "{:?}", elapsed);
+        assert!(elapsed <= Duration::from_millis(1001), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_fail_fast_test() {
+        let svc = ServiceBuilder::new()
+            .layer(RateLimitLayer::new(1).with_burst(2).fail_fast())
+            .service(time_service());
+
+        let responses = futures::future::join_all((0..3).map(|_| svc.clone().oneshot(LiteServerGetTime::default()))).await;
+
+        assert!(responses[0].is_ok());
+        assert!(responses[1].is_ok());
+        assert!(matches!(responses[2], Err(Error::RateLimited)));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_per_service_test() {
+        let layer = RateLimitLayer::new(1).with_burst(1).fail_fast();
+        let first = ServiceBuilder::new().layer(layer.clone()).service(time_service());
+        let second = ServiceBuilder::new().layer(layer).service(time_service());
+
+        first.clone().oneshot(LiteServerGetTime::default()).await.unwrap();
+
+        assert!(second.oneshot(LiteServerGetTime::default()).await.is_ok());
+        assert!(matches!(first.oneshot(LiteServerGetTime::default()).await, Err(Error::RateLimited)));
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+use tower::retry::Retry;
+use tower::Layer;
+use crate::layers::coalesce::Coalesce;
+use crate::layers::rate_limit::{RateLimit, RateLimitLayer};
+use crate::layers::retry::{RetryPolicy, TransportErrors};
+use crate::layers::timeout::Timeout;
+
+/// Settings of [`RecommendedLayer`], the defaults suit a single public liteserver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecommendedOptions {
+    /// Attempts of a request failing with a transport error or a timeout
+    pub max_attempts: usize,
+    /// Time limit of every attempt, on top of the server wait of `WaitSeqno`
+    pub timeout: Duration,
+    pub requests_per_second: u32,
+    pub burst: u32,
+}
+
+impl Default for RecommendedOptions {
+    fn default() -> Self {
+        Self { max_attempts: 3, timeout: Duration::from_secs(10), requests_per_second: 50, burst: 100 }
+    }
+}
+
+/// Identical requests in flight are coalesced, then retried with a timeout per attempt, every attempt passing
+/// the rate limit first so the wait for a token doesn't count against the timeout. `CacheLayer` and `BlockCacheLayer` serve a single request type, so they are layered on top by hand
+#[derive(Debug, Clone, Default)]
+pub struct RecommendedLayer {
+    options: RecommendedOptions
+}
+
+impl RecommendedLayer {
+    pub fn new(options: RecommendedOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl<S> Layer<S> for RecommendedLayer {
+    type Service = Coalesce<Retry<RetryPolicy, RateLimit<Timeout<S>>>>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let rate_limit = RateLimitLayer::new(self.options.requests_per_second).with_burst(self.options.burst);
+        let attempt = rate_limit.layer(Timeout::new(inner, self.options.timeout));
+
+        Coalesce::new(Retry::new(RetryPolicy::new(self.options.max_attempts, TransportErrors), attempt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::{ServiceBuilder, ServiceExt};
+    use crate::client::{Error, TransportError};
+    use crate::mock::MockLiteServer;
+    use crate::tl::{LiteServerCurrentTime, LiteServerGetTime};
+    use super::*;
+
+    #[tokio::test]
+    async fn recommended_stack_retries_test() -> anyhow::Result<()> {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mock = MockLiteServer::default().on({
+            let calls = calls.clone();
+            move |_: LiteServerGetTime| match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(Error::Transport(TransportError::OneshotClosed)),
+                _ => Ok(LiteServerCurrentTime { now: 1 })
+            }
+        });
+        let svc = ServiceBuilder::new()
+            .layer(RecommendedLayer::new(RecommendedOptions { timeout: Duration::from_secs(1), ..Default::default() }))
+            .service(mock);
+
+        let response = svc.oneshot(LiteServerGetTime::default()).await?;
+
+        assert_eq!(response.now, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recommended_stack_rate_limit_wait_outside_timeout_test() -> anyhow::Result<()> {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mock = MockLiteServer::default().on({
+            let calls = calls.clone();
+            move |_: LiteServerGetTime| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                Ok(LiteServerCurrentTime { now: 1 })
+            }
+        });
+        // the second request waits 100ms for its token, longer than the timeout
+        let svc = ServiceBuilder::new()
+            .layer(RecommendedLayer::new(RecommendedOptions { timeout: Duration::from_millis(50), requests_per_second: 10, burst: 1, ..Default::default() }))
+            .service(mock);
+
+        svc.clone().oneshot(LiteServerGetTime::default()).await?;
+        let response = svc.oneshot(LiteServerGetTime::default()).await?;
+
+        assert_eq!(response.now, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use adnl_tcp::serializer::{to_bytes_boxed, SerializeBoxed};
+use base64::Engine;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tower::{Layer, Service};
+use crate::client::Error;
+use crate::request::Requestable;
+
+type Sink = Arc<Mutex<dyn Write + Send>>;
+
+#[derive(Clone)]
+pub struct RecordLayer {
+    sink: Sink
+}
+
+impl RecordLayer {
+    pub fn new(sink: impl Write + Send + 'static) -> Self {
+        Self { sink: Arc::new(Mutex::new(sink)) }
+    }
+}
+
+impl<S> Layer<S> for RecordLayer {
+    type Service = Record<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Record { inner, sink: self.sink.clone() }
+    }
+}
+
+/// Writes the TL bytes of every answered request and its response, one `request response` line of base64 each.
+/// Failed requests aren't recorded. The lines are served back by `crate::mock::ReplayTransport`
+#[derive(Clone)]
+pub struct Record<S> {
+    inner: S,
+    sink: Sink
+}
+
+impl<S, R> Service<R> for Record<S>
+    where R: Requestable,
+          R::Response: SerializeBoxed,
+          S: Service<R, Response = R::Response, Error = Error>,
+          S::Future: Send + 'static {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let request = to_bytes_boxed(&req);
+        let response = self.inner.call(req);
+        let sink = self.sink.clone();
+
+        async move {
+            let response = response.await?;
+
+            let engine = base64::engine::general_purpose::STANDARD;
+            let line = format!("{} {}\n", engine.encode(request), engine.encode(to_bytes_boxed(&response)));
+            if let Err(error) = sink.lock().unwrap().write_all(line.as_bytes()) {
+                tracing::warn!(error = ?error, "failed to record liteserver request");
+            }
+
+            Ok(response)
+        }.boxed()
+    }
+}
@@ -0,0 +1,188 @@
+use std::time::Duration;
+use futures::future::{ready, Ready};
+use tower::Layer;
+use tower::retry::{Policy, Retry};
+use crate::client::Error;
+use crate::layers::timeout::Timeout;
+use crate::request::Requestable;
+
+/// Decides which errors are worth another attempt
+pub trait Classify {
+    fn is_retryable(&self, error: &Error) -> bool;
+}
+
+/// Retries transport failures only, liteserver errors like "block not found" are returned as is
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransportErrors;
+
+impl Classify for TransportErrors {
+    fn is_retryable(&self, error: &Error) -> bool {
+        matches!(error, Error::Timeout | Error::Transport(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy<C = TransportErrors> {
+    remaining: usize,
+    classify: C
+}
+
+impl<C> RetryPolicy<C> {
+    pub fn new(max_attempts: usize, classify: C) -> Self {
+        Self { remaining: max_attempts.saturating_sub(1), classify }
+    }
+}
+
+impl<R, Res, C> Policy<R, Res, Error> for RetryPolicy<C> where R: Requestable + Clone, C: Classify + Clone {
+    type Future = Ready<Self>;
+
+    fn retry(&self, _: &R, result: Result<&Res, &Error>) -> Option<Self::Future> {
+        match result {
+            Err(error) if self.remaining > 0 && self.classify.is_retryable(error) => {
+                tracing::warn!(error = ?error, remaining = self.remaining, "retry request");
+
+                Some(ready(Self { remaining: self.remaining - 1, classify: self.classify.clone() }))
+            },
+            _ => None
+        }
+    }
+
+    fn clone_request(&self, req: &R) -> Option<R> {
+        req.idempotent().then(|| req.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryLayer<C = TransportErrors> {
+    max_attempts: usize,
+    timeout: Duration,
+    classify: C
+}
+
+impl RetryLayer {
+    pub fn new(max_attempts: usize, timeout: Duration) -> Self {
+        Self { max_attempts, timeout, classify: TransportErrors }
+    }
+}
+
+impl<C> RetryLayer<C> {
+    pub fn with_classify<D: Classify>(self, classify: D) -> RetryLayer<D> {
+        RetryLayer { max_attempts: self.max_attempts, timeout: self.timeout, classify }
+    }
+}
+
+impl<S, C> Layer<S> for RetryLayer<C> where C: Clone {
+    type Service = Retry<RetryPolicy<C>, Timeout<S>>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry::new(RetryPolicy::new(self.max_attempts, self.classify.clone()), Timeout::new(inner, self.timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::{service_fn, Service, ServiceBuilder, ServiceExt};
+    use crate::client::TransportError;
+    use crate::request::WaitSeqno;
+    use crate::tl::{LiteServerCurrentTime, LiteServerError, LiteServerGetTime, LiteServerSendMessage, LiteServerSendMsgStatus};
+    use super::*;
+
+    #[tokio::test]
+    async fn retry_flaky_service() -> anyhow::Result<()> {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(RetryLayer::new(3, Duration::from_secs(1)))
+            .service(flaky_service(calls.clone(), 2, || TransportError::OneshotClosed.into()));
+
+        let response = svc.oneshot(LiteServerGetTime::default()).await?;
+
+        assert_eq!(response.now, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retry_gives_up_after_max_attempts() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(RetryLayer::new(2, Duration::from_secs(1)))
+            .service(flaky_service(calls.clone(), 5, || TransportError::OneshotClosed.into()));
+
+        let response = svc.oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::Transport(TransportError::OneshotClosed))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retry_skips_liteserver_error() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let svc = ServiceBuilder::new()
+            .layer(RetryLayer::new(3, Duration::from_secs(1)))
+            .service(flaky_service(calls.clone(), 1, || LiteServerError { code: 651, message: "block not found".to_owned() }.into()));
+
+        let response = svc.oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::NotFound(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_skips_send_message() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let inner = service_fn({
+            let calls = calls.clone();
+            move |_: LiteServerSendMessage| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                async { Err::<LiteServerSendMsgStatus, _>(TransportError::OneshotClosed.into()) }
+            }
+        });
+        let svc = ServiceBuilder::new()
+            .layer(RetryLayer::new(3, Duration::from_secs(1)))
+            .service(inner);
+
+        let response = svc.oneshot(LiteServerSendMessage { body: vec![] }).await;
+
+        assert!(response.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_skips_wrapped_send_message() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let inner = service_fn({
+            let calls = calls.clone();
+            move |_: WaitSeqno<LiteServerSendMessage>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                async { Err::<LiteServerSendMsgStatus, _>(TransportError::OneshotClosed.into()) }
+            }
+        });
+        let svc = ServiceBuilder::new()
+            .layer(RetryLayer::new(3, Duration::from_secs(1)))
+            .service(inner);
+
+        let response = svc.oneshot(WaitSeqno::new(LiteServerSendMessage { body: vec![] }, 1)).await;
+
+        assert!(response.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    fn flaky_service(calls: Arc<AtomicUsize>, failures: usize, error: fn() -> Error) -> impl Service<LiteServerGetTime, Response = LiteServerCurrentTime, Error = Error> + Clone {
+        service_fn(move |_: LiteServerGetTime| {
+            let calls = calls.clone();
+
+            async move {
+                if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                    Err(error())
+                } else {
+                    Ok(LiteServerCurrentTime { now: 1 })
+                }
+            }
+        })
+    }
+}
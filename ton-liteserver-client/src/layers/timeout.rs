@@ -0,0 +1,130 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use futures::ready;
+use pin_project::pin_project;
+use tokio::time::Sleep;
+use tower::{Layer, Service};
+use crate::client::Error;
+use crate::request::Requestable;
+
+#[derive(Debug, Clone)]
+pub struct TimeoutLayer {
+    timeout: Duration
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout::new(inner, self.timeout)
+    }
+}
+
+/// Unlike `tower::timeout::Timeout` keeps the crate error type, so elapsed requests fail with `Error::Timeout`.
+/// The timeout starts once the server is done waiting, as in [`crate::client::LiteServerClient`]
+#[derive(Debug, Clone)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration
+}
+
+impl<S> Timeout<S> {
+    pub fn new(inner: S, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<S, R> Service<R> for Timeout<S> where S: Service<R, Error = Error>, R: Requestable {
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let timeout = self.timeout + req.server_timeout().unwrap_or_default();
+
+        ResponseFuture { inner: self.inner.call(req), sleep: tokio::time::sleep(timeout) }
+    }
+}
+
+#[pin_project]
+pub struct ResponseFuture<F> {
+    #[pin]
+    inner: F,
+    #[pin]
+    sleep: Sleep
+}
+
+impl<F, T> Future for ResponseFuture<F> where F: Future<Output = Result<T, Error>> {
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Poll::Ready(response) = this.inner.poll(cx) {
+            return Poll::Ready(response)
+        }
+
+        ready!(this.sleep.poll(cx));
+
+        Poll::Ready(Err(Error::Timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+    use crate::request::WaitSeqno;
+    use crate::tl::{LiteServerCurrentTime, LiteServerGetTime};
+    use super::*;
+
+    #[tokio::test]
+    async fn timeout_never_responding_service() {
+        let svc = ServiceBuilder::new()
+            .layer(TimeoutLayer::new(Duration::from_millis(50)))
+            .service(service_fn(|_: LiteServerGetTime| futures::future::pending::<Result<LiteServerCurrentTime, Error>>()));
+
+        let started = tokio::time::Instant::now();
+        let response = svc.oneshot(LiteServerGetTime::default()).await;
+
+        assert!(matches!(response, Err(Error::Timeout)));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn timeout_passes_response_through() {
+        let svc = ServiceBuilder::new()
+            .layer(TimeoutLayer::new(Duration::from_millis(50)))
+            .service(service_fn(|_: LiteServerGetTime| futures::future::ready(Ok::<_, Error>(LiteServerCurrentTime { now: 1 }))));
+
+        let response = svc.oneshot(LiteServerGetTime::default()).await.unwrap();
+
+        assert_eq!(response.now, 1);
+    }
+
+    #[tokio::test]
+    async fn timeout_waits_for_server_timeout() {
+        let svc = ServiceBuilder::new()
+            .layer(TimeoutLayer::new(Duration::from_millis(50)))
+            .service(service_fn(|_: WaitSeqno<LiteServerGetTime>| async {
+                tokio::time::sleep(Duration::from_millis(150)).await;
+
+                Ok::<_, Error>(LiteServerCurrentTime { now: 1 })
+            }));
+
+        let response = svc.oneshot(WaitSeqno::with_server_timeout(LiteServerGetTime::default(), 1, Duration::from_millis(200))).await.unwrap();
+
+        assert_eq!(response.now, 1);
+    }
+}
@@ -1,3 +1,24 @@
 pub mod client;
+pub mod capabilities;
 pub mod tl;
 pub mod request;
+pub mod answered;
+pub mod layers;
+pub mod pool;
+pub mod config;
+pub mod smc;
+pub mod cell;
+pub mod shards;
+pub mod validator_stats;
+pub mod config_params;
+pub mod lookup;
+pub mod paging;
+pub mod header;
+pub mod block;
+pub mod proof;
+pub mod block_stream;
+pub mod tracker;
+pub mod account;
+pub mod address;
+#[cfg(any(test, feature = "test-mock"))]
+pub mod mock;
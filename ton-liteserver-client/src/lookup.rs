@@ -0,0 +1,158 @@
+use std::time::Duration;
+use tokio::time::Instant;
+use tower::{Service, ServiceExt};
+use crate::client::Error;
+use crate::request::WaitSeqno;
+use crate::tl::{LiteServerBlockHeader, LiteServerGetMasterchainInfo, LiteServerLookupBlock, LiteServerMasterchainInfo, TonNodeBlockId};
+
+/// Look the block up by `seqno` of the passed block id
+pub const MODE_SEQNO: i32 = 1;
+/// Look up the block containing the logical time `lt`
+pub const MODE_LT: i32 = 1 << 1;
+/// Look up the block that was active at the unix time `utime`
+pub const MODE_UTIME: i32 = 1 << 2;
+
+pub(crate) fn validate(mode: i32, lt: Option<i64>, utime: Option<i32>) -> Result<(), Error> {
+    if (mode & (MODE_SEQNO | MODE_LT | MODE_UTIME)).count_ones() != 1 {
+        return Err(Error::InvalidRequest("lookup mode must select exactly one of seqno, lt or utime"));
+    }
+    if (mode & MODE_LT != 0) != lt.is_some() {
+        return Err(Error::InvalidRequest("lt must be set iff lookup mode selects lt"));
+    }
+    if (mode & MODE_UTIME != 0) != utime.is_some() {
+        return Err(Error::InvalidRequest("utime must be set iff lookup mode selects utime"));
+    }
+
+    Ok(())
+}
+
+/// Pause between the waits for a seqno the server has reached but can't look up yet
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Looks the block up by `id.seqno`. When the server doesn't have a masterchain block and `id.seqno` is ahead of
+/// its last block, waits for the seqno with `WaitSeqno` up to `wait` and fails with `Error::Timeout` after that.
+/// A block below the last one has been pruned or never existed, it fails with `Error::NotFound` right away
+pub async fn lookup_seqno_or_wait<S>(service: S, id: TonNodeBlockId, wait: Duration) -> Result<LiteServerBlockHeader, Error>
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error>
+            + Service<WaitSeqno<LiteServerLookupBlock>, Response = LiteServerBlockHeader, Error = Error>
+            + Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+            + Clone {
+    let seqno = id.seqno;
+    let workchain = id.workchain;
+    let request = LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None };
+
+    let error = match ServiceExt::<LiteServerLookupBlock>::oneshot(service.clone(), request.clone()).await {
+        // `WaitSeqno` waits for masterchain seqnos only
+        Err(error @ Error::NotFound(_)) if workchain == -1 => error,
+        response => return response
+    };
+    let last = ServiceExt::<LiteServerGetMasterchainInfo>::oneshot(service.clone(), LiteServerGetMasterchainInfo::default()).await?.last;
+    if seqno <= last.seqno {
+        tracing::debug!(seqno, last = last.seqno, "seqno is below the first available block");
+
+        return Err(error);
+    }
+
+    tracing::debug!(seqno, last = last.seqno, "waiting for a future seqno");
+    let deadline = Instant::now() + wait;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::Timeout);
+        }
+
+        let request = WaitSeqno::with_server_timeout(request.clone(), seqno, remaining);
+        // the server gives up after `remaining` itself, the local timeout is for a server that doesn't answer at all
+        let response = tokio::time::timeout(remaining + Duration::from_secs(1), service.clone().oneshot(request))
+            .await
+            .map_err(|_| Error::Timeout)?;
+
+        match response {
+            Err(Error::NotFound(_)) => tokio::time::sleep(RETRY_DELAY.min(remaining)).await,
+            Err(Error::ServerTimeout(_)) => return Err(Error::Timeout),
+            response => return response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::mock::MockLiteServer;
+    use crate::tl::{TonNodeBlockIdExt, TonNodeZeroStateIdExt};
+    use super::*;
+
+    #[test]
+    fn validate_test() {
+        assert!(validate(MODE_SEQNO, None, None).is_ok());
+        assert!(validate(MODE_LT, Some(1), None).is_ok());
+        assert!(validate(MODE_UTIME, None, Some(1)).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_modes_test() {
+        assert!(matches!(validate(0, None, None), Err(Error::InvalidRequest(_))));
+        assert!(matches!(validate(MODE_SEQNO | MODE_LT, Some(1), None), Err(Error::InvalidRequest(_))));
+        assert!(matches!(validate(MODE_LT, None, None), Err(Error::InvalidRequest(_))));
+        assert!(matches!(validate(MODE_SEQNO, None, Some(1)), Err(Error::InvalidRequest(_))));
+    }
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    /// Liteserver with masterchain blocks `first..=last` that answers waits with not found `not_found` times first
+    fn lagging_server(first: i32, last: i32, not_found: usize, waits: Arc<AtomicUsize>) -> MockLiteServer {
+        MockLiteServer::default()
+            .on(move |_: LiteServerGetMasterchainInfo| Ok(LiteServerMasterchainInfo { last: block_id(last), state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } }))
+            .on(move |request: LiteServerLookupBlock| match request.id.seqno {
+                seqno if (first ..= last).contains(&seqno) => Ok(LiteServerBlockHeader { id: block_id(seqno), mode: 0, header_proof: vec![] }),
+                _ => Err(Error::NotFound("block not found".to_owned()))
+            })
+            .on(move |_: WaitSeqno<LiteServerLookupBlock>| match waits.fetch_add(1, Ordering::SeqCst) {
+                wait if wait < not_found => Err(Error::NotFound("block not found".to_owned())),
+                _ => Ok(LiteServerBlockHeader { id: block_id(last + 1), mode: 0, header_proof: vec![] })
+            })
+    }
+
+    fn masterchain(seqno: i32) -> TonNodeBlockId {
+        TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno }
+    }
+
+    #[tokio::test]
+    async fn lookup_waits_for_future_seqno_test() {
+        let waits = Arc::new(AtomicUsize::new(0));
+        let server = lagging_server(3, 9, 1, waits.clone());
+
+        let header = lookup_seqno_or_wait(server, masterchain(10), Duration::from_secs(1)).await.unwrap();
+
+        // the lookup and the first wait aren't found
+        assert_eq!(header.id.seqno, 10);
+        assert_eq!(waits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn lookup_doesnt_wait_for_pruned_seqno_test() {
+        let waits = Arc::new(AtomicUsize::new(0));
+        let server = lagging_server(3, 9, 0, waits.clone());
+
+        let response = lookup_seqno_or_wait(server.clone(), masterchain(2), Duration::from_secs(1)).await;
+        let found = lookup_seqno_or_wait(server, masterchain(5), Duration::from_secs(1)).await.unwrap();
+
+        assert!(matches!(response, Err(Error::NotFound(_))));
+        assert_eq!(found.id.seqno, 5);
+        assert_eq!(waits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn lookup_wait_times_out_test() {
+        let waits = Arc::new(AtomicUsize::new(0));
+        let server = lagging_server(3, 9, usize::MAX, waits.clone());
+
+        let response = lookup_seqno_or_wait(server, masterchain(10), Duration::from_millis(250)).await;
+
+        assert!(matches!(response, Err(Error::Timeout)));
+        assert!(waits.load(Ordering::SeqCst) > 1);
+    }
+}
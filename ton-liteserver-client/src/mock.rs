@@ -0,0 +1,171 @@
+//! In-memory liteserver with canned responses, for tests without network access
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use adnl_tcp::deserializer::from_bytes_boxed;
+use adnl_tcp::serializer::to_bytes_boxed;
+use anyhow::Context as _;
+use base64::Engine;
+use futures::future::{ready, Ready};
+use tower::Service;
+use crate::client::Error;
+use crate::request::Requestable;
+
+type Handler = Arc<dyn Fn(Box<dyn Any>) -> Result<Box<dyn Any + Send>, Error> + Send + Sync>;
+
+/// Answers requests with the handler registered for their type, the other requests fail with `Error::InvalidRequest`
+#[derive(Clone, Default)]
+pub struct MockLiteServer {
+    handlers: HashMap<TypeId, Handler>
+}
+
+impl MockLiteServer {
+    /// Answers every `R` with `respond`, replacing the previous handler of `R`
+    pub fn on<R, F>(mut self, respond: F) -> Self
+        where R: Requestable + 'static,
+              F: Fn(R) -> Result<R::Response, Error> + Send + Sync + 'static {
+        let handler: Handler = Arc::new(move |request| {
+            let request = request.downcast::<R>().expect("handler is registered by request type");
+
+            respond(*request).map(|response| Box::new(response) as Box<dyn Any + Send>)
+        });
+        self.handlers.insert(TypeId::of::<R>(), handler);
+
+        self
+    }
+}
+
+impl<R> Service<R> for MockLiteServer where R: Requestable + 'static {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = Ready<Result<R::Response, Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let Some(handler) = self.handlers.get(&TypeId::of::<R>()) else {
+            return ready(Err(Error::InvalidRequest("no mock response for the request")));
+        };
+
+        ready(handler(Box::new(req)).map(|response| *response.downcast::<R::Response>().expect("handler answers with the response of its request")))
+    }
+}
+
+/// Serves responses recorded by `crate::layers::record::RecordLayer` to the requests with the same TL bytes,
+/// the other requests fail with `Error::InvalidRequest`
+#[derive(Clone, Default)]
+pub struct ReplayTransport {
+    responses: Arc<HashMap<Vec<u8>, Vec<u8>>>
+}
+
+impl ReplayTransport {
+    /// Reads `request response` lines of base64, a later response to the same request replaces the earlier one
+    pub fn from_reader(reader: impl BufRead) -> anyhow::Result<Self> {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let mut responses = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            let (request, response) = line.split_once(' ').context("expected a request and a response")?;
+
+            responses.insert(engine.decode(request)?, engine.decode(response)?);
+        }
+
+        Ok(Self { responses: Arc::new(responses) })
+    }
+}
+
+impl<R> Service<R> for ReplayTransport where R: Requestable {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = Ready<Result<R::Response, Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let Some(response) = self.responses.get(&to_bytes_boxed(&req)) else {
+            return ready(Err(Error::InvalidRequest("no recorded response for the request")));
+        };
+
+        ready(from_bytes_boxed::<R::Response>(response).map_err(|_| Error::Decode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Mutex;
+    use tower::{ServiceBuilder, ServiceExt};
+    use crate::layers::record::RecordLayer;
+    use crate::tl::{LiteServerBlockHeader, LiteServerGetBlockHeader, LiteServerGetMasterchainInfo, LiteServerLookupBlock, LiteServerMasterchainInfo, TonNodeBlockIdExt, TonNodeZeroStateIdExt};
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    #[tokio::test]
+    async fn answers_by_request_type_test() {
+        let mock = MockLiteServer::default()
+            .on(|_: LiteServerGetMasterchainInfo| Ok(LiteServerMasterchainInfo { last: block_id(7), state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } }))
+            .on(|request: LiteServerLookupBlock| Ok(LiteServerBlockHeader { id: block_id(request.id.seqno), mode: 0, header_proof: vec![] }))
+            .on(|request: LiteServerGetBlockHeader| Ok(LiteServerBlockHeader { id: request.id, mode: request.mode, header_proof: vec![1] }));
+
+        let info = mock.clone().oneshot(LiteServerGetMasterchainInfo::default()).await.unwrap();
+        let header = mock.clone().oneshot(LiteServerGetBlockHeader { id: info.last.clone(), mode: 0 }).await.unwrap();
+
+        assert_eq!(info.last.seqno, 7);
+        assert_eq!(header.id, info.last);
+        assert_eq!(header.header_proof, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn fails_without_handler_test() {
+        let response = MockLiteServer::default().oneshot(LiteServerGetMasterchainInfo::default()).await;
+
+        assert!(matches!(response, Err(Error::InvalidRequest(_))));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_responses_test() {
+        let buffer = SharedBuffer::default();
+        let mock = MockLiteServer::default()
+            .on(|request: LiteServerGetBlockHeader| Ok(LiteServerBlockHeader { id: request.id, mode: request.mode, header_proof: vec![1, 2, 3] }));
+        let recorder = ServiceBuilder::new()
+            .layer(RecordLayer::new(buffer.clone()))
+            .service(mock);
+
+        let request = LiteServerGetBlockHeader { id: block_id(7), mode: 0 };
+        let recorded = recorder.clone().oneshot(request.clone()).await.unwrap();
+        // not recorded
+        recorder.oneshot(LiteServerGetMasterchainInfo::default()).await.unwrap_err();
+
+        let records = buffer.0.lock().unwrap().clone();
+        let replay = ReplayTransport::from_reader(records.as_slice()).unwrap();
+        let replayed = replay.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(records.iter().filter(|byte| **byte == b'\n').count(), 1);
+        assert_eq!(replayed, recorded);
+        assert!(matches!(replay.clone().oneshot(LiteServerGetBlockHeader { id: block_id(8), mode: 0 }).await, Err(Error::InvalidRequest(_))));
+        assert!(matches!(replay.oneshot(LiteServerGetMasterchainInfo::default()).await, Err(Error::InvalidRequest(_))));
+    }
+}
@@ -0,0 +1,121 @@
+//! Streams over list-style requests that answer a page at a time and continue from a cursor
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use crate::client::Error;
+
+/// Items of an answer along with the cursor of the page after it, `None` on the last page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T, C> {
+    pub items: Vec<T>,
+    pub next: Option<C>,
+}
+
+/// Items of every page of a list-style request, pages are only requested as the stream is polled
+pub struct Pager<T> {
+    inner: BoxStream<'static, Result<T, Error>>
+}
+
+impl<T> Pager<T> where T: Send + 'static {
+    /// `fetch` requests the page after a cursor, the first page with `None`. `extract` splits an answer into a [`Page`],
+    /// it is given the cursor the answer was requested with
+    pub fn new<C, R, F, Fut, E>(mut fetch: F, mut extract: E) -> Self
+        where C: Clone + Send + 'static,
+              R: Send,
+              F: FnMut(Option<C>) -> Fut + Send + 'static,
+              Fut: Future<Output = Result<R, Error>> + Send,
+              E: FnMut(Option<&C>, R) -> Result<Page<T, C>, Error> + Send + 'static {
+        let inner = try_stream! {
+            let mut cursor: Option<C> = None;
+            loop {
+                let answer = fetch(cursor.clone()).await?;
+                let Page { items, next } = extract(cursor.as_ref(), answer)?;
+
+                for item in items {
+                    yield item;
+                }
+                match next {
+                    Some(next) => cursor = Some(next),
+                    None => break
+                }
+            }
+        };
+
+        Self { inner: inner.boxed() }
+    }
+}
+
+impl<T> Stream for Pager<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use futures::TryStreamExt;
+    use tower::ServiceExt;
+    use crate::mock::MockLiteServer;
+    use crate::tl::{LiteServerBlockTransactions, LiteServerListBlockTransactions, LiteServerTransactionId, LiteServerTransactionId3, TonNodeBlockIdExt};
+    use super::*;
+
+    #[tokio::test]
+    async fn pager_list_block_transactions_test() -> anyhow::Result<()> {
+        // a block with transactions at lt 1 to 5
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let mock = MockLiteServer::default().on({
+            let requests = requests.clone();
+            move |request: LiteServerListBlockTransactions| {
+                let start = request.after.as_ref().map(|after| after.lt).unwrap_or_default();
+                let end = (start + request.count as i64).min(5);
+                requests.lock().unwrap().push(start);
+
+                Ok(LiteServerBlockTransactions {
+                    id: request.id,
+                    req_count: request.count,
+                    incomplete: (end < 5).into(),
+                    ids: (start + 1 ..= end).map(|lt| LiteServerTransactionId { mode: 7, account: Some([1; 32]), lt: Some(lt), hash: Some([lt as u8; 32]) }).collect(),
+                    proof: vec![]
+                })
+            }
+        });
+        let id = TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 1, root_hash: [0; 32], file_hash: [0; 32] };
+
+        let pager = Pager::new(
+            move |after| mock.clone().oneshot(LiteServerListBlockTransactions { id: id.clone(), mode: 7 | (after.is_some() as i32) << 7, count: 2, after, reverse_order: None, want_proof: None }),
+            |_, page: LiteServerBlockTransactions| {
+                let next = page.ids.last()
+                    .and_then(|tx| Some(LiteServerTransactionId3 { account: tx.account?, lt: tx.lt? }))
+                    .filter(|_| bool::from(&page.incomplete));
+
+                Ok(Page { items: page.ids, next })
+            }
+        );
+        let ids: Vec<_> = pager.try_collect().await?;
+
+        assert_eq!(ids.iter().map(|tx| tx.lt.unwrap()).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(*requests.lock().unwrap(), vec![0, 2, 4]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pager_stops_on_error_test() {
+        let pager: Pager<i32> = Pager::new(
+            |page: Option<i32>| async move { if page == Some(1) { Err(Error::Timeout) } else { Ok(vec![1, 2]) } },
+            |page, items| Ok(Page { items, next: Some(page.map_or(1, |page| page + 1)) })
+        );
+
+        let items: Vec<_> = pager.collect().await;
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[2], Err(Error::Timeout)));
+    }
+}
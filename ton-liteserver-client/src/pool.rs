@@ -0,0 +1,760 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddrV4;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use adnl_tcp::client::ServerKey;
+use anyhow::{bail, ensure};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::select;
+use tokio::sync::Notify;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tower::{Service, ServiceExt};
+use crate::client::{Error, LiteServerClient};
+use crate::config::GlobalConfig;
+use crate::layers::retry::{Classify, TransportErrors};
+use crate::request::Requestable;
+use crate::tl::{LiteServerGetMasterchainInfo, LiteServerGetVersion, LiteServerMasterchainInfo, LiteServerVersion};
+
+/// The probe interval of a backend that keeps failing doubles up to `probe_interval * 2^MAX_BACKOFF_EXPONENT`
+const MAX_BACKOFF_EXPONENT: u32 = 6;
+
+/// Circuit breaker of a backend: closed while it serves requests, open while it is ejected and half-open while it is probed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breaker {
+    Closed,
+    /// `failures` probes in a row have failed, the next one is due at `retry_at`
+    Open { failures: u32, retry_at: Instant },
+    HalfOpen { failures: u32 }
+}
+
+struct Backend<S> {
+    service: S,
+    weight: u32,
+    probe_interval: Duration,
+    breaker: Mutex<Breaker>
+}
+
+impl<S> Backend<S> {
+    fn new(service: S, weight: u32, probe_interval: Duration) -> Self {
+        Self { service, weight: weight.max(1), probe_interval, breaker: Mutex::new(Breaker::Closed) }
+    }
+
+    fn is_ejected(&self) -> bool {
+        *self.breaker.lock().unwrap() != Breaker::Closed
+    }
+
+    fn eject(&self, now: Instant) {
+        let mut breaker = self.breaker.lock().unwrap();
+        if *breaker == Breaker::Closed {
+            *breaker = Breaker::Open { failures: 0, retry_at: now + self.probe_interval };
+        }
+    }
+
+    /// Moves an open breaker whose probe is due to half-open, the caller probes the backend then
+    fn start_probe(&self, now: Instant) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+        match *breaker {
+            Breaker::Open { failures, retry_at } if retry_at <= now => {
+                *breaker = Breaker::HalfOpen { failures };
+
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn restore(&self) {
+        *self.breaker.lock().unwrap() = Breaker::Closed
+    }
+
+    /// Opens the breaker again, the wait for the next probe doubles with every failed one
+    fn probe_failed(&self, now: Instant) -> Duration {
+        let mut breaker = self.breaker.lock().unwrap();
+        let failures = match *breaker {
+            Breaker::HalfOpen { failures } => failures + 1,
+            _ => 1
+        };
+        let backoff = self.probe_interval * 2_u32.pow(failures.min(MAX_BACKOFF_EXPONENT));
+        *breaker = Breaker::Open { failures, retry_at: now + backoff };
+
+        backoff
+    }
+}
+
+/// Levels of [`LiteServerPool::with_priority`], once the pool is at its concurrency limit the waiting requests
+/// of a higher level are dispatched first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Background work, e.g. trackers polling the chain
+    Low,
+    #[default]
+    Normal,
+    /// Interactive requests, e.g. the balance lookup of a user
+    High,
+}
+
+/// Limit of the requests in flight over every backend of a pool
+struct Queue {
+    limit: usize,
+    state: Mutex<QueueState>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    in_flight: usize,
+    /// Requests waiting for a slot by priority and then by arrival, the first one is let in next
+    waiting: BTreeSet<(Reverse<Priority>, u64)>,
+    arrivals: u64,
+}
+
+impl Queue {
+    fn new(limit: usize) -> Self {
+        Self { limit: limit.max(1), state: Default::default(), released: Notify::new() }
+    }
+
+    async fn acquire(self: Arc<Self>, priority: Priority) -> Permit {
+        let key = {
+            let mut state = self.state.lock().unwrap();
+            let key = (Reverse(priority), state.arrivals);
+            state.arrivals += 1;
+            state.waiting.insert(key);
+
+            key
+        };
+        let _waiting = Waiting { queue: &self, key };
+
+        loop {
+            // created before the check, so a release in between isn't missed
+            let released = self.released.notified();
+            if self.admit(key) {
+                return Permit { queue: self.clone() };
+            }
+
+            released.await;
+        }
+    }
+
+    /// Takes a slot when one is free and `key` is the first one waiting
+    fn admit(&self, key: (Reverse<Priority>, u64)) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= self.limit || state.waiting.first() != Some(&key) {
+            return false;
+        }
+        state.waiting.remove(&key);
+        state.in_flight += 1;
+
+        // the next one may fit as well
+        if state.in_flight < self.limit && !state.waiting.is_empty() {
+            self.released.notify_waiters();
+        }
+
+        true
+    }
+}
+
+/// Leaves the queue when the request is dropped while it waits
+struct Waiting<'a> {
+    queue: &'a Queue,
+    key: (Reverse<Priority>, u64),
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.queue.state.lock().unwrap().waiting.remove(&self.key) {
+            self.queue.released.notify_waiters();
+        }
+    }
+}
+
+/// Slot of a request in flight, freed on drop
+struct Permit {
+    queue: Arc<Queue>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().in_flight -= 1;
+        self.queue.released.notify_waiters();
+    }
+}
+
+/// Weighted round-robin over several liteservers, a backend with weight 3 gets three times the requests of one with weight 1.
+/// A pool made by [`LiteServerPool::with_affinity`] sends every request to the backend its key maps to instead.
+///
+/// A backend failing with a transport error is ejected and the request is sent to the next healthy one.
+/// Ejected backends are probed with `LiteServerGetMasterchainInfo` after `probe_interval` and restored on success,
+/// every failed probe doubles the wait for the next one so a backend that is down for long is rarely probed.
+pub struct LiteServerPool<S = LiteServerClient> {
+    backends: Arc<Vec<Backend<S>>>,
+    /// Backend indices in round-robin order, each backend is there as many times as its weight
+    slots: Arc<Vec<usize>>,
+    next: Arc<AtomicUsize>,
+    affinity: Option<u64>,
+    priority: Priority,
+    queue: Option<Arc<Queue>>,
+    _drop_guard: Arc<DropGuard>
+}
+
+impl<S> Clone for LiteServerPool<S> {
+    fn clone(&self) -> Self {
+        Self { backends: self.backends.clone(), slots: self.slots.clone(), next: self.next.clone(), affinity: self.affinity, priority: self.priority, queue: self.queue.clone(), _drop_guard: self._drop_guard.clone() }
+    }
+}
+
+impl LiteServerPool {
+    pub async fn connect(endpoints: &[(SocketAddrV4, ServerKey)]) -> anyhow::Result<Self> {
+        let mut clients = Vec::with_capacity(endpoints.len());
+        for (addr, server_key) in endpoints {
+            match LiteServerClient::connect(*addr, server_key).await {
+                Ok(client) => clients.push(client),
+                Err(error) => tracing::warn!(addr = ?addr, error = ?error, "cannot connect to liteserver")
+            }
+        }
+
+        if clients.is_empty() {
+            bail!("no liteserver available")
+        }
+
+        Ok(Self::new(clients))
+    }
+
+    pub async fn from_config(config: &GlobalConfig) -> anyhow::Result<Self> {
+        let endpoints = config.liteservers()
+            .iter()
+            .map(|liteserver| liteserver.server_key().map(|server_key| (liteserver.addr(), server_key)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Self::connect(&endpoints).await
+    }
+}
+
+impl<S> LiteServerPool<S>
+    where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error> + Clone + Send + Sync + 'static,
+          S::Future: Send {
+    pub fn new(services: Vec<S>) -> Self {
+        Self::with_probe_interval(services, Duration::from_secs(5))
+    }
+
+    pub fn with_probe_interval(services: Vec<S>, probe_interval: Duration) -> Self {
+        Self::with_weights(services.into_iter().map(|service| (service, 1)).collect(), probe_interval)
+    }
+
+    /// Backends along with their weights, a weight of 0 counts as 1
+    pub fn with_weights(services: Vec<(S, u32)>, probe_interval: Duration) -> Self {
+        let health = |service: S| async move {
+            service.oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+            Ok(())
+        };
+
+        Self::with_health(services, probe_interval, health, false)
+    }
+
+    /// Probes ejected backends with `health` and, when `check_healthy` is set, ejects the healthy ones it fails for
+    fn with_health<F, Fut>(services: Vec<(S, u32)>, probe_interval: Duration, health: F, check_healthy: bool) -> Self
+        where F: Fn(S) -> Fut + Send + 'static,
+              Fut: Future<Output = anyhow::Result<()>> + Send + 'static {
+        let backends = Arc::new(services.into_iter().map(|(service, weight)| Backend::new(service, weight, probe_interval)).collect::<Vec<_>>());
+        let slots = Arc::new(slots(&backends.iter().map(|backend| backend.weight).collect::<Vec<_>>()));
+        let cancellation_token = CancellationToken::new();
+
+        tokio::spawn(probe(backends.clone(), probe_interval, health, check_healthy, cancellation_token.clone()));
+
+        Self { backends, slots, next: Arc::new(AtomicUsize::new(0)), affinity: None, priority: Priority::default(), queue: None, _drop_guard: Arc::new(cancellation_token.drop_guard()) }
+    }
+}
+
+impl<S> LiteServerPool<S>
+    where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+            + Service<LiteServerGetVersion, Response = LiteServerVersion, Error = Error>
+            + Clone + Send + Sync + 'static,
+          <S as Service<LiteServerGetMasterchainInfo>>::Future: Send,
+          <S as Service<LiteServerGetVersion>>::Future: Send {
+    /// Same as [`Self::with_probe_interval`], except every backend is also checked every `probe_interval` with
+    /// `liteServer.getVersion`. A backend whose `now` is more than `max_lag` behind the local clock is ejected
+    /// as a stalled one, it's restored once it catches up
+    pub fn with_max_lag(services: Vec<S>, probe_interval: Duration, max_lag: Duration) -> Self {
+        let health = move |service: S| async move {
+            let version = ServiceExt::<LiteServerGetVersion>::oneshot(service, LiteServerGetVersion::default()).await?;
+            let local = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+            let lag = local - version.now as i64;
+            ensure!(lag <= max_lag.as_secs() as i64, "liteserver time is {}s behind", lag);
+
+            Ok(())
+        };
+
+        Self::with_health(services.into_iter().map(|service| (service, 1)).collect(), probe_interval, health, true)
+    }
+}
+
+impl<S> LiteServerPool<S> {
+    pub fn available(&self) -> usize {
+        self.backends.iter().filter(|backend| !backend.is_ejected()).count()
+    }
+
+    /// Pool over the same backends that sends requests to the backend `key` maps to, e.g. an account address, so
+    /// they hit its warm caches. The key moves to another backend only while its backend is ejected
+    pub fn with_affinity(&self, key: impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+
+        Self { affinity: Some(hasher.finish()), ..self.clone() }
+    }
+
+    /// At most `limit` requests are in flight over every backend, the others wait and are let in by their
+    /// [`Priority`]. Set it before the pool is cloned, clones made earlier aren't limited
+    pub fn with_concurrency_limit(mut self, limit: usize) -> Self {
+        self.queue = Some(Arc::new(Queue::new(limit)));
+
+        self
+    }
+
+    /// Pool over the same backends whose requests wait for a slot with `priority`, it only matters with
+    /// [`Self::with_concurrency_limit`]
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        Self { priority, ..self.clone() }
+    }
+
+    fn pick(&self) -> Option<usize> {
+        match self.affinity {
+            Some(key) => self.pick_sticky(key),
+            None => self.pick_next()
+        }
+    }
+
+    fn pick_next(&self) -> Option<usize> {
+        let len = self.slots.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+
+        (0 .. len)
+            .map(|offset| self.slots[(start + offset) % len])
+            .find(|index| !self.backends[*index].is_ejected())
+    }
+
+    /// Rendezvous hashing, the healthy backend with the highest weighted score for `key` wins
+    fn pick_sticky(&self, key: u64) -> Option<usize> {
+        self.backends.iter()
+            .enumerate()
+            .filter(|(_, backend)| !backend.is_ejected())
+            .map(|(index, backend)| (index, score(key, index, backend.weight)))
+            .max_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Spreads the slots of every backend evenly over the round, e.g. weights 2 and 1 give `0, 1, 0`
+fn slots(weights: &[u32]) -> Vec<usize> {
+    let mut slots = weights.iter()
+        .enumerate()
+        .flat_map(|(index, weight)| (0 .. *weight).map(move |slot| ((slot as f64 + 0.5) / *weight as f64, index)))
+        .collect::<Vec<_>>();
+    slots.sort_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs));
+
+    slots.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Weighted rendezvous score, a backend wins a share of the keys proportional to its weight
+fn score(key: u64, index: usize, weight: u32) -> f64 {
+    let mut hasher = DefaultHasher::new();
+    (key, index).hash(&mut hasher);
+    // uniform in (0, 1)
+    let unit = ((hasher.finish() >> 11) as f64 + 0.5) / (1_u64 << 53) as f64;
+
+    -(weight as f64) / unit.ln()
+}
+
+async fn probe<S, F, Fut>(backends: Arc<Vec<Backend<S>>>, probe_interval: Duration, health: F, check_healthy: bool, cancellation_token: CancellationToken)
+    where S: Clone,
+          F: Fn(S) -> Fut,
+          Fut: Future<Output = anyhow::Result<()>> {
+    let mut interval = tokio::time::interval(probe_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        select! {
+            _ = cancellation_token.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if check_healthy {
+            for (index, backend) in backends.iter().enumerate().filter(|(_, backend)| !backend.is_ejected()) {
+                match tokio::time::timeout(probe_interval, health(backend.service.clone())).await {
+                    Ok(Ok(())) => {},
+                    Ok(Err(error)) => {
+                        tracing::warn!(index, error = ?error, "unhealthy liteserver ejected");
+                        backend.eject(Instant::now())
+                    },
+                    Err(_) => {
+                        tracing::warn!(index, "liteserver health check timeout, ejected");
+                        backend.eject(Instant::now())
+                    }
+                }
+            }
+        }
+
+        for (index, backend) in backends.iter().enumerate().filter(|(_, backend)| backend.start_probe(Instant::now())) {
+            let response = tokio::time::timeout(probe_interval, health(backend.service.clone())).await;
+
+            match response {
+                Ok(Ok(())) => {
+                    tracing::info!(index, "liteserver restored");
+
+                    backend.restore()
+                },
+                Ok(Err(error)) => {
+                    let backoff = backend.probe_failed(Instant::now());
+                    tracing::warn!(index, error = ?error, backoff = ?backoff, "liteserver probe failed")
+                },
+                Err(_) => {
+                    let backoff = backend.probe_failed(Instant::now());
+                    tracing::warn!(index, backoff = ?backoff, "liteserver probe timeout")
+                }
+            }
+        }
+    }
+
+    tracing::trace!("pool probe closed");
+}
+
+impl<S, R> Service<R> for LiteServerPool<S>
+    where R: Requestable + Clone + 'static,
+          S: Service<R, Response = R::Response, Error = Error> + Clone + Send + Sync + 'static,
+          S::Future: Send {
+    type Response = R::Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<R::Response, Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let pool = self.clone();
+
+        async move {
+            let _permit = match pool.queue.clone() {
+                Some(queue) => Some(queue.acquire(pool.priority).await),
+                None => None
+            };
+
+            let mut last_error = Error::NoAvailableBackend;
+            while let Some(index) = pool.pick() {
+                let backend = &pool.backends[index];
+
+                match backend.service.clone().oneshot(req.clone()).await {
+                    Err(error) if TransportErrors.is_retryable(&error) => {
+                        tracing::warn!(index, error = ?error, "liteserver ejected");
+                        backend.eject(Instant::now());
+
+                        last_error = error;
+                    },
+                    response => return response
+                }
+            }
+
+            Err(last_error)
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::service_fn;
+    use crate::client::TransportError;
+    use crate::mock::MockLiteServer;
+    use crate::request::WaitSeqno;
+    use crate::tl::{TonNodeBlockIdExt, TonNodeZeroStateIdExt};
+    use super::*;
+
+    #[tokio::test]
+    async fn pool_skips_down_backend() -> anyhow::Result<()> {
+        let pool = LiteServerPool::new(vec![backend(usize::MAX), backend(0), backend(usize::MAX)]);
+
+        for _ in 0 .. 6 {
+            let response = pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+            assert_eq!(response.last.seqno, 1);
+        }
+        assert_eq!(pool.available(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pool_fails_when_all_backends_down() {
+        let pool = LiteServerPool::new(vec![backend(usize::MAX), backend(usize::MAX)]);
+
+        let first = pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await;
+        let second = pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await;
+
+        assert!(matches!(first, Err(Error::Transport(TransportError::ChannelClosed))));
+        assert!(matches!(second, Err(Error::NoAvailableBackend)));
+    }
+
+    #[tokio::test]
+    async fn pool_keeps_backend_on_wait_timeout() -> anyhow::Result<()> {
+        let waiting = MockLiteServer::default()
+            .on(|_: LiteServerGetMasterchainInfo| Ok(masterchain_info(1)))
+            .on(|_: WaitSeqno<LiteServerGetMasterchainInfo>| Err(Error::ServerTimeout("timeout".to_owned())));
+        let pool = LiteServerPool::new(vec![waiting]);
+
+        let response = pool.clone().oneshot(WaitSeqno::new(LiteServerGetMasterchainInfo::default(), 1000)).await;
+
+        // the seqno isn't there yet, the backend is fine
+        assert!(matches!(response, Err(Error::ServerTimeout(_))));
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pool_restores_backend_after_probe() {
+        let pool = LiteServerPool::with_probe_interval(vec![backend(1)], Duration::from_millis(10));
+
+        let response = pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await;
+        assert!(response.is_err());
+        assert_eq!(pool.available(), 0);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[tokio::test]
+    async fn pool_ejects_stalled_backend() -> anyhow::Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i32;
+        let pool = LiteServerPool::with_max_lag(vec![versioned_backend(0, now), versioned_backend(1, now - 3600)], Duration::from_millis(10), Duration::from_secs(60));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(pool.available(), 1);
+        for _ in 0 .. 4 {
+            assert_eq!(pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno, 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn breaker_test() {
+        let backend = Backend::new((), 1, Duration::from_secs(1));
+        let now = Instant::now();
+
+        backend.eject(now);
+        assert!(backend.is_ejected());
+        assert!(!backend.start_probe(now));
+
+        let mut backoffs = vec![];
+        let mut at = now + Duration::from_secs(1);
+        for _ in 0 .. 8 {
+            assert!(backend.start_probe(at));
+            assert!(matches!(*backend.breaker.lock().unwrap(), Breaker::HalfOpen { .. }));
+            // already probing
+            assert!(!backend.start_probe(at));
+
+            let backoff = backend.probe_failed(at);
+            assert!(!backend.start_probe(at + backoff - Duration::from_millis(1)));
+            backoffs.push(backoff.as_secs());
+            at += backoff;
+        }
+        assert_eq!(backoffs, vec![2, 4, 8, 16, 32, 64, 64, 64]);
+
+        assert!(backend.start_probe(at));
+        backend.restore();
+        assert_eq!(*backend.breaker.lock().unwrap(), Breaker::Closed);
+        assert!(!backend.is_ejected());
+    }
+
+    #[tokio::test]
+    async fn pool_probes_down_backend_less_often() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let down = service_fn({
+            let calls = calls.clone();
+
+            move |_: LiteServerGetMasterchainInfo| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                futures::future::ready(Err::<LiteServerMasterchainInfo, _>(Error::Transport(TransportError::ChannelClosed)))
+            }
+        });
+        let pool = LiteServerPool::with_probe_interval(vec![down], Duration::from_millis(10));
+
+        assert!(pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await.is_err());
+        tokio::time::sleep(Duration::from_millis(400)).await;
+
+        // probes after 10, 30, 70, 150 and 310ms instead of every 10ms
+        let probes = calls.load(Ordering::SeqCst) - 1;
+        assert!((3 ..= 8).contains(&probes), "{} probes", probes);
+        assert!(matches!(*pool.backends[0].breaker.lock().unwrap(), Breaker::Open { failures, .. } | Breaker::HalfOpen { failures } if failures >= 3));
+    }
+
+    #[tokio::test]
+    async fn pool_closes_breaker_after_recovery() {
+        // the request and the first two probes fail
+        let pool = LiteServerPool::with_probe_interval(vec![backend(3)], Duration::from_millis(10));
+
+        assert!(pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await.is_err());
+        assert_eq!(pool.available(), 0);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(*pool.backends[0].breaker.lock().unwrap(), Breaker::Closed);
+        assert_eq!(pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await.unwrap().last.seqno, 1);
+    }
+
+    #[test]
+    fn slots_test() {
+        assert_eq!(slots(&[1, 1, 1]), vec![0, 1, 2]);
+        assert_eq!(slots(&[2, 1]), vec![0, 1, 0]);
+        assert_eq!(slots(&[3, 1]).iter().filter(|index| **index == 0).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn pool_follows_weights() -> anyhow::Result<()> {
+        let pool = LiteServerPool::with_weights(vec![(numbered_backend(0, 0), 3), (numbered_backend(1, 0), 1)], Duration::from_secs(5));
+
+        let mut counts = [0; 2];
+        for _ in 0 .. 8 {
+            let response = pool.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?;
+
+            counts[response.last.seqno as usize] += 1;
+        }
+
+        assert_eq!(counts, [6, 2]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pool_sticks_to_backend_by_affinity() -> anyhow::Result<()> {
+        let pool = LiteServerPool::new((0 .. 4).map(|index| numbered_backend(index, 0)).collect());
+
+        let mut picked = Vec::new();
+        for key in 0 .. 32 {
+            let sticky = pool.with_affinity(format!("account-{}", key));
+            let first = sticky.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno;
+            for _ in 0 .. 3 {
+                assert_eq!(sticky.clone().oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno, first);
+            }
+
+            picked.push(first);
+        }
+
+        // the keys are spread over the backends
+        assert!((0 .. 4).all(|index| picked.contains(&index)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pool_moves_affinity_off_ejected_backend() -> anyhow::Result<()> {
+        // backend 2 is down
+        let pool = LiteServerPool::new((0 .. 4).map(|index| numbered_backend(index, if index == 2 { usize::MAX } else { 0 })).collect());
+        let healthy = LiteServerPool::new((0 .. 4).map(|index| numbered_backend(index, 0)).collect());
+
+        for key in 0 .. 32 {
+            let expected = healthy.with_affinity(key).oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno;
+            let first = pool.with_affinity(key).oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno;
+            let second = pool.with_affinity(key).oneshot(LiteServerGetMasterchainInfo::default()).await?.last.seqno;
+
+            assert_ne!(first, 2);
+            assert_eq!(first, second);
+            // only the keys of the ejected backend move
+            if expected != 2 {
+                assert_eq!(first, expected);
+            }
+        }
+        assert_eq!(pool.available(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pool_dispatches_high_priority_first() {
+        let slow = service_fn(|_: LiteServerGetMasterchainInfo| async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            Ok::<_, Error>(masterchain_info(1))
+        }.boxed());
+        let pool = LiteServerPool::new(vec![slow]).with_concurrency_limit(1);
+        let done = Arc::new(Mutex::new(Vec::new()));
+
+        let mut requests = Vec::new();
+        for (name, priority) in [("first", Priority::Low), ("low 1", Priority::Low), ("low 2", Priority::Low), ("high", Priority::High)] {
+            let pool = pool.with_priority(priority);
+            let done = done.clone();
+            requests.push(tokio::spawn(async move {
+                pool.oneshot(LiteServerGetMasterchainInfo::default()).await.unwrap();
+                done.lock().unwrap().push(name);
+            }));
+            // the requests queue up in this order
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        futures::future::join_all(requests).await;
+
+        assert_eq!(*done.lock().unwrap(), vec!["first", "high", "low 1", "low 2"]);
+    }
+
+    #[tokio::test]
+    async fn pool_queue_skips_dropped_requests() -> anyhow::Result<()> {
+        let queue = Arc::new(Queue::new(1));
+        let permit = queue.clone().acquire(Priority::Normal).await;
+
+        // gives up while waiting
+        assert!(tokio::time::timeout(Duration::from_millis(10), queue.clone().acquire(Priority::High)).await.is_err());
+        drop(permit);
+
+        tokio::time::timeout(Duration::from_millis(100), queue.clone().acquire(Priority::Low)).await?;
+        assert!(queue.state.lock().unwrap().waiting.is_empty());
+
+        Ok(())
+    }
+
+    fn backend(failures: usize) -> impl Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error, Future = BoxFuture<'static, Result<LiteServerMasterchainInfo, Error>>> + Clone + Send + Sync + 'static {
+        numbered_backend(1, failures)
+    }
+
+    /// Answers with the masterchain block `seqno` after failing `failures` times
+    fn numbered_backend(seqno: i32, failures: usize) -> impl Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error, Future = BoxFuture<'static, Result<LiteServerMasterchainInfo, Error>>> + Clone + Send + Sync + 'static {
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        service_fn(move |_: LiteServerGetMasterchainInfo| {
+            let calls = calls.clone();
+
+            async move {
+                if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                    Err(Error::Transport(TransportError::ChannelClosed))
+                } else {
+                    Ok(masterchain_info(seqno))
+                }
+            }.boxed()
+        })
+    }
+
+    /// Answers with the masterchain block `seqno` and reports the time `now`
+    fn versioned_backend(seqno: i32, now: i32) -> MockLiteServer {
+        MockLiteServer::default()
+            .on(move |_: LiteServerGetMasterchainInfo| Ok(masterchain_info(seqno)))
+            .on(move |_: LiteServerGetVersion| Ok(LiteServerVersion { mode: 0, version: 0x200, capabilities: 7, now }))
+    }
+
+    fn masterchain_info(seqno: i32) -> LiteServerMasterchainInfo {
+        LiteServerMasterchainInfo {
+            last: TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] },
+            state_root_hash: [0; 32],
+            init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] }
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! Verification of `liteServer.partialBlockProof` chains between masterchain blocks.
+//!
+//! A forward link is trusted through the signatures of the validators listed in the config of
+//! its `from` key block, a backward link through the `prev_blocks` of the `from` block state.
+//! A `liteServer.shardBlockProof` links a shard block to a masterchain block through its `shard_hashes`.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, ensure};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use tower::{Service, ServiceExt};
+
+use crate::account::{load_account, shard_accounts};
+use crate::block::{mc_block_extra, parse_info};
+use crate::cell::{bytes_to_key, dict_get, key_to_uint, read_boc, read_single_root, Cell, CellType};
+use crate::client::Error;
+use crate::config_params::{mc_state_extra, ConfigParams, ValidatorSet, CURRENT_VALIDATORS};
+use crate::shards::load_shard_hashes;
+use crate::tl::{Int256, LiteServerAccountId, LiteServerAccountState, LiteServerBlockLinkBack, LiteServerBlockLinkForward, LiteServerBoxedBlockLink, LiteServerGetBlockProof, LiteServerPartialBlockProof, LiteServerShardBlockProof, LiteServerSignatureSet, TonNodeBlockIdExt};
+
+/// Requests proofs from the trusted `known` block to `target` until the chain is complete,
+/// every page continues from the block the previous one stopped at
+pub async fn verify_proof_chain<S>(service: S, known: TonNodeBlockIdExt, target: TonNodeBlockIdExt) -> anyhow::Result<()>
+    where S: Service<LiteServerGetBlockProof, Response = LiteServerPartialBlockProof, Error = Error> + Clone {
+    let mut known = known;
+    loop {
+        let proof = service.clone()
+            .oneshot(LiteServerGetBlockProof { mode: 1, known_block: known.clone(), target_block: Some(target.clone()) })
+            .await?;
+        verify_partial_proof(&known, &proof)?;
+
+        if bool::from(&proof.complete) {
+            ensure!(proof.to == target, "proof ends at block {} instead of {}", proof.to.seqno, target.seqno);
+
+            return Ok(());
+        }
+        ensure!(proof.to != known, "incomplete proof made no progress from block {}", known.seqno);
+
+        known = proof.to;
+    }
+}
+
+/// Checks every step of `proof` and that the steps chain from `known` to `proof.to`
+pub fn verify_partial_proof(known: &TonNodeBlockIdExt, proof: &LiteServerPartialBlockProof) -> anyhow::Result<()> {
+    ensure!(&proof.from == known, "proof starts at block {} instead of {}", proof.from.seqno, known.seqno);
+
+    let mut current = known;
+    for step in &proof.steps {
+        let (from, to) = match step {
+            LiteServerBoxedBlockLink::LiteServerBlockLinkBack(link) => {
+                verify_back_link(link)?;
+
+                (&link.from, &link.to)
+            },
+            LiteServerBoxedBlockLink::LiteServerBlockLinkForward(link) => {
+                verify_forward_link(link)?;
+
+                (&link.from, &link.to)
+            }
+        };
+        ensure!(from == current, "proof step from block {} doesn't continue block {}", from.seqno, current.seqno);
+
+        current = to;
+    }
+    ensure!(current == &proof.to, "proof steps end at block {} instead of {}", current.seqno, proof.to.seqno);
+
+    Ok(())
+}
+
+/// Checks that `state.proof` links the account `address` to the block `block_id`, the block the state is taken from,
+/// i.e. `state.shardblk`. The block proof gives the state hash, the state proof the `ShardAccount` whose account
+/// cell is the root of `state.state`, an empty `state` is checked to be an account missing from the state
+pub fn verify_account_proof(block_id: &TonNodeBlockIdExt, address: &LiteServerAccountId, state: &LiteServerAccountState) -> anyhow::Result<()> {
+    ensure!(&state.shardblk == block_id, "state is proven in block {} instead of {}", state.shardblk.seqno, block_id.seqno);
+    ensure!(address.workchain == block_id.workchain, "account of workchain {} in a block of workchain {}", address.workchain, block_id.workchain);
+
+    let roots = read_boc(&state.proof)?;
+    ensure!(roots.len() == 2, "account state proof has {} roots instead of 2", roots.len());
+
+    let block = checked_root(&roots[0], &block_id.root_hash)?;
+    let state_update = block.reference(2)?;
+    ensure!(state_update.cell_type() == CellType::MerkleUpdate, "block {} has no state update", block_id.seqno);
+    // type byte and the old state hash come first
+    let state_hash: Int256 = state_update.data()[33..65].try_into()?;
+
+    let shard_state = checked_root(&roots[1], &state_hash)?;
+    let shard_account = match shard_accounts(&shard_state)? {
+        Some(accounts) => dict_get(accounts, &bytes_to_key(&address.id))?,
+        None => None
+    };
+
+    match shard_account {
+        Some(mut shard_account) => {
+            let account = load_account(&mut shard_account)?;
+            ensure!(!state.state.is_empty(), "account is in the state proof but no state is given");
+            ensure!(read_single_root(&state.state)?.hash() == account.virtual_hash(), "account state doesn't match the proof");
+        },
+        None => ensure!(state.state.is_empty(), "account is missing from the state proof")
+    }
+
+    Ok(())
+}
+
+/// Checks that `proof` commits the shard block `id` to `proof.masterchain_id`, a block trusted by other means,
+/// e.g. [`verify_proof_chain`]. The first link is a top block listed in `shard_hashes` of the masterchain block,
+/// every next one a block the previous one was built on
+pub fn verify_shard_block_proof(id: &TonNodeBlockIdExt, proof: &LiteServerShardBlockProof) -> anyhow::Result<()> {
+    ensure_masterchain(&proof.masterchain_id)?;
+
+    let mut current = &proof.masterchain_id;
+    for link in &proof.links {
+        let linked = if current.workchain == -1 {
+            let root = checked_proof_root(&link.proof, &current.root_hash)?;
+            shard_hashes(&root)?
+        } else {
+            BlockHeader::from_proof(&link.proof, current)?.prev_blocks(current)?
+        };
+        ensure!(linked.contains(&link.id), "block {} isn't linked from block {}", link.id.seqno, current.seqno);
+
+        current = &link.id;
+    }
+    ensure!(current == id, "proof links end at block {} instead of {}", current.seqno, id.seqno);
+
+    Ok(())
+}
+
+/// Top shard blocks in `shard_hashes` of the masterchain block `block`
+fn shard_hashes(block: &Cell) -> anyhow::Result<Vec<TonNodeBlockIdExt>> {
+    let (_, mut custom) = mc_block_extra(block)?.ok_or_else(|| anyhow!("block has no McBlockExtra"))?;
+
+    load_shard_hashes(&mut custom)
+}
+
+/// `to` is older than `from` and listed in `prev_blocks` of the state `from` produced
+fn verify_back_link(link: &LiteServerBlockLinkBack) -> anyhow::Result<()> {
+    ensure_masterchain(&link.from)?;
+    ensure_masterchain(&link.to)?;
+    ensure!(link.to.seqno < link.from.seqno, "backward link from block {} to {}", link.from.seqno, link.to.seqno);
+
+    let block = checked_proof_root(&link.proof, &link.from.root_hash)?;
+    let state_update = block.reference(2)?;
+    ensure!(state_update.cell_type() == CellType::MerkleUpdate, "block {} has no state update", link.from.seqno);
+    // type byte and the old state hash come first
+    let state_hash: Int256 = state_update.data()[33..65].try_into()?;
+
+    let state = checked_proof_root(&link.state_proof, &state_hash)?;
+    let (key_block, root_hash, file_hash) = prev_block(&state, link.to.seqno)?;
+    ensure!(root_hash == link.to.root_hash && file_hash == link.to.file_hash, "block {} doesn't match prev_blocks", link.to.seqno);
+    ensure!(key_block == bool::from(&link.to_key_block), "to_key_block doesn't match prev_blocks");
+
+    if key_block {
+        let header = BlockHeader::from_proof(&link.dest_proof, &link.to)?;
+        ensure!(header.key_block, "block {} isn't a key block", link.to.seqno);
+    }
+
+    Ok(())
+}
+
+/// `to` is newer than the key block `from` and signed by the validators from its config
+fn verify_forward_link(link: &LiteServerBlockLinkForward) -> anyhow::Result<()> {
+    ensure_masterchain(&link.from)?;
+    ensure_masterchain(&link.to)?;
+    ensure!(link.to.seqno > link.from.seqno, "forward link from block {} to {}", link.from.seqno, link.to.seqno);
+
+    let header = BlockHeader::from_proof(&link.dest_proof, &link.to)?;
+    ensure!(header.key_block == bool::from(&link.to_key_block), "to_key_block doesn't match block {}", link.to.seqno);
+    ensure!(header.gen_catchain_seqno == link.signatures.catchain_seqno as u32, "signatures of another catchain");
+    ensure!(header.gen_validator_list_hash_short == link.signatures.validator_set_hash as u32, "signatures of another validator set");
+
+    let config_root = checked_proof_root(&link.config_proof, &link.from.root_hash)?;
+    let config = ConfigParams::from_key_block(&config_root)?;
+    let validators = config.validator_set(CURRENT_VALIDATORS)?
+        .ok_or_else(|| anyhow!("key block {} has no validator set", link.from.seqno))?;
+
+    verify_signatures(&validators, &link.to, &link.signatures)
+}
+
+/// Masterchain blocks are signed by the first `main` validators of the set, more than 2/3 of their weight is required
+fn verify_signatures(validators: &ValidatorSet, block: &TonNodeBlockIdExt, signatures: &LiteServerSignatureSet) -> anyhow::Result<()> {
+    let nodes: Vec<_> = validators.list.iter()
+        .take(validators.main as usize)
+        .map(|node| (node_id_short(&node.public_key), node))
+        .collect();
+    let total_weight: u128 = nodes.iter().map(|(_, node)| node.weight as u128).sum();
+
+    // ton.blockId root_cell_hash:int256 file_hash:int256
+    let mut message = 0xc50b6e70_u32.to_le_bytes().to_vec();
+    message.extend_from_slice(&block.root_hash);
+    message.extend_from_slice(&block.file_hash);
+
+    let mut signed = HashSet::new();
+    let mut signed_weight: u128 = 0;
+    for signature in &signatures.signatures {
+        let Some((_, node)) = nodes.iter().find(|(id, _)| id == &signature.node_id_short) else {
+            bail!("signature of a validator outside of the set")
+        };
+        ensure!(signed.insert(signature.node_id_short), "duplicate signature");
+
+        VerifyingKey::from_bytes(&node.public_key)?
+            .verify(&message, &Signature::from_slice(&signature.signature)?)?;
+
+        signed_weight += node.weight as u128;
+    }
+    ensure!(signed_weight * 3 > total_weight * 2, "block {} is signed by {} of {} weight", block.seqno, signed_weight, total_weight);
+
+    Ok(())
+}
+
+/// Fields of `BlockInfo` a block proof is checked against
+pub(crate) struct BlockHeader {
+    pub(crate) key_block: bool,
+    after_merge: bool,
+    after_split: bool,
+    gen_validator_list_hash_short: u32,
+    gen_catchain_seqno: u32,
+    pub(crate) prev_key_block_seqno: i32,
+    /// `BlkPrevInfo`, decoded on demand as header proofs of masterchain blocks may prune it
+    prev_ref: Arc<Cell>,
+}
+
+impl BlockHeader {
+    pub(crate) fn from_proof(proof: &[u8], id: &TonNodeBlockIdExt) -> anyhow::Result<Self> {
+        let block = checked_proof_root(proof, &id.root_hash)?;
+        ensure!(block.parser().load_uint(32)? == 0x11ef55aa, "not a Block");
+
+        let info = block.reference(0)?;
+        let fields = parse_info(info)?;
+        ensure!(fields.info.seqno == id.seqno && fields.info.workchain == id.workchain && fields.info.shard == id.shard, "proof of another block");
+        // master_ref of shard blocks comes first
+        let prev_ref = info.reference(fields.not_master as usize)?.clone();
+
+        Ok(Self {
+            key_block: fields.info.key_block,
+            after_merge: fields.after_merge,
+            after_split: fields.after_split,
+            gen_validator_list_hash_short: fields.gen_validator_list_hash_short,
+            gen_catchain_seqno: fields.gen_catchain_seqno,
+            prev_key_block_seqno: fields.prev_key_block_seqno,
+            prev_ref,
+        })
+    }
+
+    /// Blocks the block `id` was built on, two after a merge
+    pub(crate) fn prev_blocks(&self, id: &TonNodeBlockIdExt) -> anyhow::Result<Vec<TonNodeBlockIdExt>> {
+        let shard = id.shard as u64;
+        let low_bit = shard & shard.wrapping_neg();
+
+        if self.after_merge {
+            ensure!(low_bit > 1, "block {} of the deepest shard is after a merge", id.seqno);
+            let mut slice = self.prev_ref.parser();
+            let left = load_ext_blk_ref(slice.load_reference()?, id.workchain, shard - low_bit / 2)?;
+            let right = load_ext_blk_ref(slice.load_reference()?, id.workchain, shard + low_bit / 2)?;
+
+            return Ok(vec![left, right]);
+        }
+        let shard = if self.after_split {
+            ensure!(low_bit != 1 << 63, "block {} of the whole workchain is after a split", id.seqno);
+
+            (shard - low_bit) | low_bit << 1
+        } else {
+            shard
+        };
+
+        Ok(vec![load_ext_blk_ref(&self.prev_ref, id.workchain, shard)?])
+    }
+}
+
+/// Id of the block in shard `workchain:shard` an `ExtBlkRef` refers to
+fn load_ext_blk_ref(cell: &Cell, workchain: i32, shard: u64) -> anyhow::Result<TonNodeBlockIdExt> {
+    ensure!(cell.cell_type() == CellType::Ordinary, "prev_ref is pruned");
+    let mut slice = cell.parser();
+    // end_lt
+    slice.skip(64)?;
+    let seqno = slice.load_uint(32)? as i32;
+
+    Ok(TonNodeBlockIdExt { workchain, shard: shard as i64, seqno, root_hash: slice.load_int256()?, file_hash: slice.load_int256()? })
+}
+
+/// `key_block`, `root_hash` and `file_hash` of block `seqno` from `prev_blocks` of a masterchain state
+fn prev_block(state: &Cell, seqno: i32) -> anyhow::Result<(bool, Int256, Int256)> {
+    let mut slice = mc_state_extra(state)?;
+    if slice.load_bit()? {
+        // shard_hashes
+        slice.load_reference()?;
+    }
+    // config
+    slice.skip(256)?;
+    slice.load_reference()?;
+
+    let mut slice = slice.load_reference()?.parser();
+    // flags and validator_info
+    slice.skip(16 + 65)?;
+    for (key, mut value) in slice.load_dict(32)? {
+        if key_to_uint(&key) != seqno as u64 {
+            continue;
+        }
+        // KeyMaxLt extra
+        value.skip(1 + 64)?;
+        let key_block = value.load_bit()?;
+        // end_lt
+        value.skip(64)?;
+        ensure!(value.load_uint(32)? == seqno as u64, "prev_blocks entry of another block");
+
+        return Ok((key_block, value.load_int256()?, value.load_int256()?));
+    }
+
+    bail!("block {} isn't in prev_blocks", seqno)
+}
+
+/// Root of merkle proof `boc`, checked to be a proof of the cell with `hash`
+pub(crate) fn checked_proof_root(boc: &[u8], hash: &Int256) -> anyhow::Result<Arc<Cell>> {
+    let root = read_single_root(boc)?;
+
+    checked_root(&root, hash)
+}
+
+fn checked_root(root: &Cell, hash: &Int256) -> anyhow::Result<Arc<Cell>> {
+    let proof_root = root.proof_root()?;
+    ensure!(&proof_root.virtual_hash() == hash, "proof hash mismatch");
+
+    Ok(proof_root.clone())
+}
+
+fn ensure_masterchain(id: &TonNodeBlockIdExt) -> anyhow::Result<()> {
+    ensure!(id.workchain == -1 && id.shard == i64::MIN, "block {}:{} isn't in the masterchain", id.workchain, id.shard);
+
+    Ok(())
+}
+
+/// Short id of the validator `pub.ed25519` key, the one signatures refer to
+fn node_id_short(public_key: &Int256) -> Int256 {
+    let mut hasher = Sha256::new();
+    hasher.update(0x4813b4c6_u32.to_le_bytes());
+    hasher.update(public_key);
+
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use adnl_tcp::deserializer::from_bytes_boxed;
+    use tower::service_fn;
+    use super::*;
+
+    /// Masterchain key block 100 to key block 103 signed by two of three validators, then 103 to 105
+    fn forward_pages() -> Vec<LiteServerPartialBlockProof> {
+        include_str!("../fixtures/block_proof_forward.hex")
+            .lines()
+            .map(|line| from_bytes_boxed(&hex::decode(line).unwrap()).unwrap())
+            .collect()
+    }
+
+    /// Block 105 back to block 101
+    fn back_page() -> LiteServerPartialBlockProof {
+        from_bytes_boxed(&hex::decode(include_str!("../fixtures/block_proof_back.hex").trim()).unwrap()).unwrap()
+    }
+
+    fn forward_link(proof: &mut LiteServerPartialBlockProof) -> &mut LiteServerBlockLinkForward {
+        match proof.steps.first_mut() {
+            Some(LiteServerBoxedBlockLink::LiteServerBlockLinkForward(link)) => link,
+            _ => unreachable!()
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_proof_chain_test() {
+        let pages = forward_pages();
+        let known = pages[0].from.clone();
+        let target = pages[1].to.clone();
+        let pages = Arc::new(Mutex::new(VecDeque::from(pages)));
+        let requested = Arc::new(Mutex::new(Vec::new()));
+
+        let service = service_fn({
+            let requested = requested.clone();
+            move |request: LiteServerGetBlockProof| {
+                requested.lock().unwrap().push(request.known_block.seqno);
+                let page = pages.lock().unwrap().pop_front().ok_or(Error::NoAvailableBackend);
+
+                async move { page }
+            }
+        });
+
+        verify_proof_chain(service, known, target.clone()).await.unwrap();
+
+        assert_eq!(target.seqno, 105);
+        assert_eq!(*requested.lock().unwrap(), vec![100, 103]);
+    }
+
+    #[test]
+    fn verify_back_link_test() {
+        let proof = back_page();
+
+        verify_partial_proof(&proof.from, &proof).unwrap();
+
+        assert_eq!(proof.from.seqno, 105);
+        assert_eq!(proof.to.seqno, 101);
+    }
+
+    #[test]
+    fn forged_signature_test() {
+        let mut proof = forward_pages().remove(0);
+        forward_link(&mut proof).signatures.signatures[0].signature[0] ^= 1;
+
+        assert!(verify_partial_proof(&proof.from.clone(), &proof).is_err());
+    }
+
+    #[test]
+    fn not_enough_signatures_test() {
+        let mut proof = forward_pages().remove(0);
+        forward_link(&mut proof).signatures.signatures.pop();
+
+        assert!(verify_partial_proof(&proof.from.clone(), &proof).is_err());
+    }
+
+    /// Account state of masterchain account 0x11..11 on block 200, the neighbour 0x22..22 is pruned
+    fn account_fixture() -> (TonNodeBlockIdExt, LiteServerAccountState) {
+        let fields: Vec<_> = include_str!("../fixtures/account_proof.hex").split_whitespace().collect();
+        let id = TonNodeBlockIdExt {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: fields[0].parse().unwrap(),
+            root_hash: hex::decode(fields[1]).unwrap().try_into().unwrap(),
+            file_hash: hex::decode(fields[2]).unwrap().try_into().unwrap(),
+        };
+        let state = LiteServerAccountState { id: id.clone(), shardblk: id.clone(), shard_proof: vec![], proof: hex::decode(fields[3]).unwrap(), state: hex::decode(fields[4]).unwrap() };
+
+        (id, state)
+    }
+
+    #[test]
+    fn verify_account_proof_test() {
+        let (id, state) = account_fixture();
+        let missing = LiteServerAccountState { state: vec![], ..state.clone() };
+
+        verify_account_proof(&id, &(-1, [0x11; 32]).into(), &state).unwrap();
+        // 0x10..10 would be next to 0x11..11, the branch isn't pruned
+        verify_account_proof(&id, &(-1, [0x10; 32]).into(), &missing).unwrap();
+        assert!(verify_account_proof(&id, &(-1, [0x11; 32]).into(), &missing).is_err());
+        assert!(verify_account_proof(&id, &(-1, [0x22; 32]).into(), &missing).is_err());
+    }
+
+    #[test]
+    fn tampered_account_proof_test() {
+        let (id, state) = account_fixture();
+        let mut tampered_state = state.clone();
+        *tampered_state.state.last_mut().unwrap() ^= 1;
+        let mut tampered_proof = state.clone();
+        *tampered_proof.proof.last_mut().unwrap() ^= 1;
+        let another_block = TonNodeBlockIdExt { root_hash: [0; 32], ..id.clone() };
+
+        assert!(verify_account_proof(&id, &(-1, [0x11; 32]).into(), &tampered_state).is_err());
+        assert!(verify_account_proof(&id, &(-1, [0x11; 32]).into(), &tampered_proof).is_err());
+        assert!(verify_account_proof(&another_block, &(-1, [0x11; 32]).into(), &LiteServerAccountState { shardblk: another_block.clone(), ..state }).is_err());
+    }
+
+    /// Shard block 0:8000000000000000 1001, its successor 1002 and the masterchain block 300 listing 1002 as the top block
+    fn shard_block_proof() -> LiteServerShardBlockProof {
+        from_bytes_boxed(&hex::decode(include_str!("../fixtures/shard_block_proof.hex").trim()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn verify_shard_block_proof_test() {
+        let proof = shard_block_proof();
+        let id = proof.links[1].id.clone();
+
+        verify_shard_block_proof(&id, &proof).unwrap();
+
+        assert_eq!(proof.masterchain_id.seqno, 300);
+        assert_eq!((id.workchain, id.shard, id.seqno), (0, i64::MIN, 1001));
+        // the top block itself is proven by the first link alone
+        let top = LiteServerShardBlockProof { links: proof.links[..1].to_vec(), ..proof.clone() };
+        verify_shard_block_proof(&proof.links[0].id, &top).unwrap();
+    }
+
+    #[test]
+    fn shard_block_proof_mismatch_test() {
+        let proof = shard_block_proof();
+        let id = proof.links[1].id.clone();
+        let another_masterchain_block = LiteServerShardBlockProof { masterchain_id: TonNodeBlockIdExt { root_hash: [0; 32], ..proof.masterchain_id.clone() }, ..proof.clone() };
+        let mut tampered_link = proof.clone();
+        tampered_link.links[1].id.root_hash[0] ^= 1;
+
+        assert!(verify_shard_block_proof(&TonNodeBlockIdExt { seqno: 1000, ..id.clone() }, &proof).is_err());
+        assert!(verify_shard_block_proof(&id, &another_masterchain_block).is_err());
+        assert!(verify_shard_block_proof(&tampered_link.links[1].id.clone(), &tampered_link).is_err());
+    }
+
+    #[test]
+    fn prev_blocks_mismatch_test() {
+        let mut proof = back_page();
+        proof.to.root_hash[0] ^= 1;
+        if let Some(LiteServerBoxedBlockLink::LiteServerBlockLinkBack(link)) = proof.steps.first_mut() {
+            link.to.root_hash[0] ^= 1;
+        }
+
+        assert!(verify_partial_proof(&proof.from.clone(), &proof).is_err());
+    }
+}
@@ -1,18 +1,57 @@
+use std::time::Duration;
 use adnl_tcp::deserializer::DeserializeBoxed;
 use adnl_tcp::serializer::{SerializeBoxed, Serializer};
 use adnl_tcp::types::Functional;
-use crate::tl::LiteServerWaitMasterchainSeqno;
+use crate::tl::{LiteServerWaitMasterchainSeqno, TonNodeBlockId};
 
 pub trait Requestable: SerializeBoxed + Send {
     type Response: DeserializeBoxed + Send + 'static;
+
+    /// TL name of the request, recorded in the request span
+    fn name(&self) -> &'static str;
+
+    /// Block the request is about, recorded in the request span
+    fn block_id(&self) -> Option<TonNodeBlockId>;
+
+    /// Time the server holds the request before answering, the wait of [`WaitSeqno`]
+    fn server_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether sending the request twice is harmless, so it may be retried
+    fn idempotent(&self) -> bool {
+        true
+    }
+}
+
+/// Implemented for every TL function by the generator
+pub trait Described {
+    const NAME: &'static str;
+    /// `false` for `liteServer.sendMessage`, a message sent again is broadcast again
+    const IDEMPOTENT: bool = true;
+
+    fn block_id(&self) -> Option<TonNodeBlockId>;
 }
 
 impl<T> Requestable for T
-    where T : Functional + SerializeBoxed + Send,
+    where T : Functional + Described + SerializeBoxed + Send,
         T::Result: DeserializeBoxed + Send + 'static {
     type Response = T::Result;
+
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn block_id(&self) -> Option<TonNodeBlockId> {
+        Described::block_id(self)
+    }
+
+    fn idempotent(&self) -> bool {
+        T::IDEMPOTENT
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct WaitSeqno<R> {
     prefix: LiteServerWaitMasterchainSeqno,
     request: R,
@@ -26,6 +65,16 @@ impl<R> WaitSeqno<R> where R: Requestable {
     pub fn with_timeout(request: R, seqno: i32, timeout_ms: i32) -> Self {
         Self { prefix: LiteServerWaitMasterchainSeqno { seqno, timeout_ms }, request }
     }
+
+    /// The server gives up waiting after `timeout` and answers with `Error::ServerTimeout`, the request timeout
+    /// of the client counts from there
+    pub fn with_server_timeout(request: R, seqno: i32, timeout: Duration) -> Self {
+        Self::with_timeout(request, seqno, timeout.as_millis().min(i32::MAX as u128) as i32)
+    }
+
+    pub fn seqno(&self) -> i32 {
+        self.prefix.seqno
+    }
 }
 
 impl<R> SerializeBoxed for WaitSeqno<R> where R: Requestable {
@@ -37,4 +86,38 @@ impl<R> SerializeBoxed for WaitSeqno<R> where R: Requestable {
 
 impl<R> Requestable for WaitSeqno<R> where R: Requestable {
     type Response = R::Response;
+
+    fn name(&self) -> &'static str {
+        self.request.name()
+    }
+
+    fn block_id(&self) -> Option<TonNodeBlockId> {
+        self.request.block_id()
+    }
+
+    fn server_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_millis(self.prefix.timeout_ms.max(0) as u64))
+    }
+
+    fn idempotent(&self) -> bool {
+        self.request.idempotent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use adnl_tcp::serializer::to_bytes_boxed;
+    use crate::tl::LiteServerGetMasterchainInfo;
+    use super::*;
+
+    #[test]
+    fn wait_seqno_server_timeout_test() {
+        let request = WaitSeqno::with_server_timeout(LiteServerGetMasterchainInfo::default(), 11, Duration::from_millis(2500));
+
+        // waitMasterchainSeqno with seqno 11 and 2500 ms, then getMasterchainInfo
+        assert_eq!(to_bytes_boxed(&request), hex::decode("92b8eaba0b000000c40900002ee6b589").unwrap());
+        assert_eq!(request.server_timeout(), Some(Duration::from_millis(2500)));
+        assert_eq!(request.seqno(), 11);
+        assert_eq!(LiteServerGetMasterchainInfo::default().server_timeout(), None);
+    }
 }
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use anyhow::{bail, ensure};
+
+use crate::cell::{key_to_uint, read_single_root, Cell, CellSlice};
+use crate::tl::{LiteServerAllShardsInfo, TonNodeBlockIdExt};
+
+const SHARD_FULL: u64 = 0x8000_0000_0000_0000;
+
+impl LiteServerAllShardsInfo {
+    /// Top blocks of every shard, see [`parse_shard_hashes`]
+    pub fn shards(&self) -> anyhow::Result<Vec<TonNodeBlockIdExt>> {
+        parse_shard_hashes(&self.data)
+    }
+}
+
+/// Decodes the `ShardHashes` BoC from `liteServer.allShardsInfo` into the top block of every shard
+pub fn parse_shard_hashes(data: &[u8]) -> anyhow::Result<Vec<TonNodeBlockIdExt>> {
+    let root = read_single_root(data)?;
+
+    load_shard_hashes(&mut root.parser())
+}
+
+/// `ShardHashes` stored at the current position of `slice`, e.g. in `McBlockExtra`
+pub(crate) fn load_shard_hashes(slice: &mut CellSlice) -> anyhow::Result<Vec<TonNodeBlockIdExt>> {
+    let mut shards = Vec::new();
+    for (key, mut value) in slice.load_dict(32)? {
+        let workchain = key_to_uint(&key) as u32 as i32;
+        let tree = value.load_reference()?;
+
+        walk_bin_tree(tree, workchain, SHARD_FULL, 0, &mut shards)?;
+    }
+
+    Ok(shards)
+}
+
+fn walk_bin_tree(cell: &Arc<Cell>, workchain: i32, shard: u64, depth: u32, shards: &mut Vec<TonNodeBlockIdExt>) -> anyhow::Result<()> {
+    let mut slice = cell.parser();
+    if slice.load_bit()? {
+        ensure!(depth < 60, "shard tree is too deep");
+        let step = SHARD_FULL >> (depth + 1);
+        let prefix = shard - (SHARD_FULL >> depth);
+
+        walk_bin_tree(slice.load_reference()?, workchain, prefix | step, depth + 1, shards)?;
+        walk_bin_tree(slice.load_reference()?, workchain, prefix | (SHARD_FULL >> depth) | step, depth + 1, shards)?;
+
+        return Ok(());
+    }
+
+    match slice.load_uint(4)? {
+        // shard_descr#b and shard_descr_new#a share the leading fields
+        0xa | 0xb => {},
+        tag => bail!("unknown shard_descr tag {:#x}", tag),
+    }
+    let seqno = slice.load_uint(32)? as i32;
+    let _reg_mc_seqno = slice.load_uint(32)?;
+    let _start_lt = slice.load_uint(64)?;
+    let _end_lt = slice.load_uint(64)?;
+    let root_hash = slice.load_int256()?;
+    let file_hash = slice.load_int256()?;
+
+    shards.push(TonNodeBlockIdExt { workchain, shard: shard as i64, seqno, root_hash, file_hash });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_shard_hashes_test() {
+        let data = hex::decode(include_str!("../fixtures/all_shards_info.hex").trim()).unwrap();
+
+        let shards = parse_shard_hashes(&data).unwrap();
+
+        assert_eq!(shards.len(), 2);
+        assert!(shards.iter().all(|shard| shard.workchain == 0));
+        assert_eq!(shards[0].shard, 0x4000_0000_0000_0000);
+        assert_eq!(shards[0].seqno, 45000001);
+        assert_eq!(shards[0].root_hash, [0x11; 32]);
+        assert_eq!(shards[1].shard, 0xc000_0000_0000_0000_u64 as i64);
+        assert_eq!(shards[1].seqno, 45000002);
+        assert_eq!(shards[1].file_hash, [0x44; 32]);
+    }
+
+    #[test]
+    fn parse_empty_shard_hashes_test() {
+        // a single cell with the `hme_empty$0` bit
+        let data = hex::decode("b5ee9c7201010101000300000140").unwrap();
+
+        assert!(parse_shard_hashes(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_garbage_test() {
+        assert!(parse_shard_hashes(&[0, 1, 2, 3]).is_err());
+    }
+}
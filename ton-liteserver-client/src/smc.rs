@@ -0,0 +1,64 @@
+use crc::Crc;
+
+/// Include `shard_proof` and `proof` in the result
+pub const MODE_PROOFS: i32 = 1;
+/// Include `state_proof` in the result
+pub const MODE_STATE_PROOF: i32 = 1 << 1;
+/// Include the serialized result stack
+pub const MODE_RESULT: i32 = 1 << 2;
+/// Include the `c7` the method was executed with
+pub const MODE_INIT_C7: i32 = 1 << 3;
+/// Include the libraries used during execution
+pub const MODE_LIB_EXTRAS: i32 = 1 << 4;
+
+const CRC16: Crc<u16> = Crc::<u16>::new(&crc::CRC_16_XMODEM);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MethodId {
+    Id(i64),
+    Name(String)
+}
+
+impl MethodId {
+    /// Numeric id as computed by FunC: `crc16(name) | 0x10000`
+    pub fn value(&self) -> i64 {
+        match self {
+            MethodId::Id(id) => *id,
+            MethodId::Name(name) => (CRC16.checksum(name.as_bytes()) as i64) | 0x10000
+        }
+    }
+}
+
+impl From<i64> for MethodId {
+    fn from(id: i64) -> Self {
+        MethodId::Id(id)
+    }
+}
+
+impl From<&str> for MethodId {
+    fn from(name: &str) -> Self {
+        MethodId::Name(name.to_owned())
+    }
+}
+
+impl From<String> for MethodId {
+    fn from(name: String) -> Self {
+        MethodId::Name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_id_from_name() {
+        assert_eq!(MethodId::from("seqno").value(), 85143);
+        assert_eq!(MethodId::from("get_jetton_data").value(), 106029);
+    }
+
+    #[test]
+    fn method_id_from_id() {
+        assert_eq!(MethodId::from(85143).value(), 85143);
+    }
+}
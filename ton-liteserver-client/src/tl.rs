@@ -2,6 +2,9 @@
 #![allow(unused_mut)]
 
 use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use anyhow::{anyhow, Context};
+use std::time::{SystemTime, UNIX_EPOCH};
 use adnl_tcp::deserializer::{Deserialize, DeserializeBoxed, Deserializer, DeserializerBoxedError};
 use adnl_tcp::serializer::{Serialize, SerializeBoxed, Serializer};
 pub use adnl_tcp::types::*;
@@ -16,6 +19,125 @@ impl Display for LiteServerError {
 
 impl std::error::Error for LiteServerError {}
 
+impl From<(Int, Int256)> for LiteServerAccountId {
+    fn from((workchain, id): (Int, Int256)) -> Self {
+        Self { workchain, id }
+    }
+}
+
+impl From<&BoxedBool> for bool {
+    fn from(value: &BoxedBool) -> Self {
+        matches!(value, BoxedBool::BoolTrue(_))
+    }
+}
+
+impl From<bool> for BoxedBool {
+    fn from(value: bool) -> Self {
+        if value { BoxedBool::BoolTrue(BoolTrue::default()) } else { BoxedBool::BoolFalse(BoolFalse::default()) }
+    }
+}
+
+impl From<&TonNodeBlockIdExt> for TonNodeBlockId {
+    fn from(id: &TonNodeBlockIdExt) -> Self {
+        Self { workchain: id.workchain, shard: id.shard, seqno: id.seqno }
+    }
+}
+
+/// `(workchain,shard,seqno)` as printed by lite-client, the shard is 16 hex digits
+impl Display for TonNodeBlockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({},{:016X},{})", self.workchain, self.shard as u64, self.seqno)
+    }
+}
+
+impl FromStr for TonNodeBlockId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.strip_prefix('(')
+            .and_then(|s| s.strip_suffix(')'))
+            .ok_or_else(|| anyhow!("block id {:?} isn't in parentheses", s))?;
+        let mut fields = fields.split(',');
+        let mut next = |name: &str| fields.next().with_context(|| format!("block id {:?} has no {}", s, name));
+
+        let workchain = next("workchain")?.parse()?;
+        let shard = u64::from_str_radix(next("shard")?, 16)? as i64;
+        let seqno = next("seqno")?.parse()?;
+        if fields.next().is_some() {
+            return Err(anyhow!("block id {:?} has extra fields", s));
+        }
+
+        Ok(Self { workchain, shard, seqno })
+    }
+}
+
+/// `(workchain,shard,seqno):root_hash:file_hash` as printed by lite-client, the hashes are uppercase hex
+impl Display for TonNodeBlockIdExt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", TonNodeBlockId::from(self), hex::encode_upper(self.root_hash), hex::encode_upper(self.file_hash))
+    }
+}
+
+/// Parses the form of `Display`, the hashes are hex of either case
+impl FromStr for TonNodeBlockIdExt {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.rsplitn(3, ':');
+        let (Some(file_hash), Some(root_hash), Some(id)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(anyhow!("block id {:?} has no root and file hash", s));
+        };
+        let id: TonNodeBlockId = id.parse()?;
+
+        Ok(Self {
+            workchain: id.workchain,
+            shard: id.shard,
+            seqno: id.seqno,
+            root_hash: parse_hash(root_hash).context("invalid root hash")?,
+            file_hash: parse_hash(file_hash).context("invalid file hash")?
+        })
+    }
+}
+
+fn parse_hash(hash: &str) -> anyhow::Result<Int256> {
+    let mut bytes = [0; 32];
+    hex::decode_to_slice(hash, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Root hash of the mainnet zero state, `init` of every mainnet liteserver
+pub const MAINNET_ZERO_STATE_ROOT_HASH: Int256 = [0x17, 0xa3, 0xa9, 0x29, 0x92, 0xaa, 0xbe, 0xa7, 0x85, 0xa7, 0xa0, 0x90, 0x98, 0x5a, 0x26, 0x5c, 0xd3, 0x1f, 0x32, 0x3d, 0x84, 0x9d, 0xa5, 0x12, 0x39, 0x73, 0x7e, 0x32, 0x1f, 0xb0, 0x55, 0x69];
+/// File hash of the mainnet zero state
+pub const MAINNET_ZERO_STATE_FILE_HASH: Int256 = [0x5e, 0x99, 0x4f, 0xcf, 0x4d, 0x42, 0x5c, 0x0a, 0x6c, 0xe6, 0xa7, 0x92, 0x59, 0x4b, 0x71, 0x73, 0x20, 0x5f, 0x74, 0x0a, 0x39, 0xcd, 0x56, 0xf5, 0x37, 0xde, 0xfd, 0x28, 0xb4, 0x8a, 0x0f, 0x6e];
+
+impl LiteServerMasterchainInfo {
+    pub fn last_seqno(&self) -> i32 {
+        self.last.seqno
+    }
+
+    /// Whether the server follows mainnet, the zero state it started from is the mainnet one
+    pub fn is_mainnet_genesis(&self) -> bool {
+        self.init.workchain == -1 && self.init.root_hash == MAINNET_ZERO_STATE_ROOT_HASH && self.init.file_hash == MAINNET_ZERO_STATE_FILE_HASH
+    }
+}
+
+/// Last block in the form of [`TonNodeBlockIdExt`] along with the state root hash, e.g. for logs
+impl Display for LiteServerMasterchainInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} state {}", self.last, hex::encode_upper(self.state_root_hash))
+    }
+}
+
+impl LiteServerMasterchainInfoExt {
+    /// Seconds the server clock is ahead of `local`, negative when it's behind
+    pub fn clock_skew(&self, local: SystemTime) -> i64 {
+        let local = local.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+
+        self.now as i64 - local
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use base64::Engine;
@@ -55,6 +177,18 @@ mod tests {
         assert_eq!(bytes, hex::decode("2ee6b589").unwrap())
     }
 
+    #[test]
+    fn send_message_round_trip_test() {
+        let request = LiteServerSendMessage { body: hex::decode("b5ee9c724101010100020000004cacb9cd").unwrap() };
+        let response = LiteServerSendMsgStatus { status: 1 };
+
+        let request_bytes = to_bytes_boxed(&request);
+        let response_bytes = to_bytes_boxed(&response);
+
+        assert_eq!(from_bytes_boxed::<LiteServerSendMessage>(&request_bytes).unwrap(), request);
+        assert_eq!(from_bytes_boxed::<LiteServerSendMsgStatus>(&response_bytes).unwrap(), response);
+    }
+
     #[test]
     fn deserialize_adnl_query_test() {
         let bytes = hex::decode("7af98bb477c1545b96fa136b8e01cc08338bec47e8a43215492dda6d4d7e286382bb00c40cdf068c79042ee6b589000000000000").unwrap();
@@ -67,6 +201,80 @@ mod tests {
         })
     }
 
+    #[test]
+    fn deserialize_masterchain_info_ext_test() {
+        let bytes = hex::decode("f5e0cca800000000010100000700000000000000ffffffff000000000000008027405801e585a47bd5978f6a4fb2b56aa2082ec9deac33aaae19e78241b97522e1fb43d4876851b60521311853f59c002d46b0bd80054af4bce340787a00bd04e012351780996666839966668b4d3b38b06bb484015faf9821c3ba1c609a25b74f30e1e585b8c8e820ef0976ffffffff17a3a92992aabea785a7a090985a265cd31f323d849da51239737e321fb055695e994fcf4d425c0a6ce6a792594b7173205f740a39cd56f537defd28b48a0f6e").unwrap();
+
+        let info = from_bytes_boxed::<LiteServerMasterchainInfoExt>(&bytes).unwrap();
+
+        assert_eq!(info.mode, 0);
+        assert_eq!(info.version, 0x101);
+        assert_eq!(info.capabilities, 7);
+        assert_eq!(info.last.workchain, -1);
+        assert_eq!(info.last.seqno, 22560807);
+        assert_eq!(info.now - info.last_utime, 3);
+        assert_eq!(info.clock_skew(UNIX_EPOCH + std::time::Duration::from_secs(1718000000)), 3);
+    }
+
+    #[test]
+    fn block_id_ext_parse_known_test() {
+        let id: TonNodeBlockIdExt = "(-1,8000000000000000,22560807):E585A47BD5978F6A4FB2B56AA2082EC9DEAC33AAAE19E78241B97522E1FB43D4:876851b60521311853f59c002d46b0bd80054af4bce340787a00bd04e0123517".parse().unwrap();
+
+        assert_eq!(id, TonNodeBlockIdExt {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: 22560807,
+            root_hash: hex::decode("e585a47bd5978f6a4fb2b56aa2082ec9deac33aaae19e78241b97522e1fb43d4").unwrap().try_into().unwrap(),
+            file_hash: hex::decode("876851b60521311853f59c002d46b0bd80054af4bce340787a00bd04e0123517").unwrap().try_into().unwrap(),
+        });
+    }
+
+    #[test]
+    fn block_id_round_trip_test() {
+        let ids = [
+            TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno: 22560807, root_hash: [0xab; 32], file_hash: [1; 32] },
+            TonNodeBlockIdExt { workchain: 0, shard: 0x2000000000000000, seqno: 1, root_hash: [0; 32], file_hash: [0xff; 32] },
+        ];
+
+        for id in ids {
+            let short = TonNodeBlockId::from(&id);
+
+            assert_eq!(id.to_string().parse::<TonNodeBlockIdExt>().unwrap(), id);
+            assert_eq!(short.to_string().parse::<TonNodeBlockId>().unwrap(), short);
+        }
+        assert_eq!(TonNodeBlockId { workchain: 0, shard: 0x2000000000000000, seqno: 1 }.to_string(), "(0,2000000000000000,1)");
+    }
+
+    #[test]
+    fn block_id_parse_invalid_test() {
+        for invalid in ["", "(-1,8000000000000000)", "-1,8000000000000000,1", "(-1,8000000000000000,1,2)", "(-1,8000000000000000,1):00:00", "(a,8000000000000000,1)"] {
+            assert!(invalid.parse::<TonNodeBlockIdExt>().is_err(), "{}", invalid);
+        }
+        assert!("(-1,8000000000000000)".parse::<TonNodeBlockId>().is_err());
+    }
+
+    #[test]
+    fn deserialize_version_test() {
+        let bytes = hex::decode("e591045a0000000001010000070000000000000080996666").unwrap();
+
+        let version = from_bytes_boxed::<LiteServerVersion>(&bytes).unwrap();
+
+        assert_eq!(version, LiteServerVersion { mode: 0, version: 0x101, capabilities: 7, now: 1718000000 });
+    }
+
+    #[test]
+    fn masterchain_info_accessors_test() {
+        let bytes = hex::decode("81288385ffffffff000000000000008027405801e585a47bd5978f6a4fb2b56aa2082ec9deac33aaae19e78241b97522e1fb43d4876851b60521311853f59c002d46b0bd80054af4bce340787a00bd04e01235178b4d3b38b06bb484015faf9821c3ba1c609a25b74f30e1e585b8c8e820ef0976ffffffff17a3a92992aabea785a7a090985a265cd31f323d849da51239737e321fb055695e994fcf4d425c0a6ce6a792594b7173205f740a39cd56f537defd28b48a0f6e").unwrap();
+        let mut info = from_bytes_boxed::<LiteServerMasterchainInfo>(&bytes).unwrap();
+
+        assert_eq!(info.last_seqno(), 22560807);
+        assert!(info.is_mainnet_genesis());
+        assert_eq!(info.to_string(), "(-1,8000000000000000,22560807):E585A47BD5978F6A4FB2B56AA2082EC9DEAC33AAAE19E78241B97522E1FB43D4:876851B60521311853F59C002D46B0BD80054AF4BCE340787A00BD04E0123517 state 8B4D3B38B06BB484015FAF9821C3BA1C609A25B74F30E1E585B8C8E820EF0976");
+
+        info.init.root_hash[0] ^= 1;
+        assert!(!info.is_mainnet_genesis());
+    }
+
     #[test]
     fn deserialize_masterchain_info_test() {
         let bytes = hex::decode("81288385ffffffff000000000000008027405801e585a47bd5978f6a4fb2b56aa2082ec9deac33aaae19e78241b97522e1fb43d4876851b60521311853f59c002d46b0bd80054af4bce340787a00bd04e01235178b4d3b38b06bb484015faf9821c3ba1c609a25b74f30e1e585b8c8e820ef0976ffffffff17a3a92992aabea785a7a090985a265cd31f323d849da51239737e321fb055695e994fcf4d425c0a6ce6a792594b7173205f740a39cd56f537defd28b48a0f6e").unwrap();
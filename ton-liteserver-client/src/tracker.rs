@@ -0,0 +1,1455 @@
+//! Trackers keeping the latest state of the chain in a `watch` channel
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use futures::{Stream, StreamExt};
+use tokio::select;
+use tokio::sync::{mpsc, watch};
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::WatchStream;
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tokio_util::task::TaskTracker;
+use tower::{Service, ServiceExt};
+use crate::block_stream::poll_last_block;
+use crate::client::{Error, LiteServerClient, TransportError};
+use crate::lookup::MODE_SEQNO;
+use crate::pool::LiteServerPool;
+use crate::proof::BlockHeader;
+use crate::tl::{LiteServerAllShardsInfo, LiteServerBlockHeader, LiteServerGetAllShardsInfo, LiteServerGetMasterchainInfo, LiteServerLookupBlock, LiteServerMasterchainInfo, LiteServerNonfinalGetValidatorGroups, LiteServerNonfinalValidatorGroups, TonNodeBlockId, TonNodeBlockIdExt};
+
+/// Follows the last masterchain block, the tracked block only moves forward so a lagging liteserver can't take it back.
+/// Blocks behind the tracked one or forking off it aren't emitted, they are logged and counted in [`Self::gap_count`]
+pub struct MasterchainLastBlockTracker {
+    rx: watch::Receiver<Option<TonNodeBlockIdExt>>,
+    gaps: Arc<AtomicUsize>
+}
+
+impl MasterchainLastBlockTracker {
+    /// `last` yields the last masterchain block as reported by one or more liteservers, e.g. [`poll_last_block`]
+    pub fn new<L>(last: L) -> Self where L: Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static {
+        Self::with_tasks(last, &TaskTracker::new())
+    }
+
+    /// Same as [`Self::new`] with the tracking task spawned on `tasks`
+    pub fn with_tasks<L>(last: L, tasks: &TaskTracker) -> Self where L: Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static {
+        let (tx, rx) = watch::channel(None);
+        let gaps = Arc::new(AtomicUsize::new(0));
+
+        tasks.spawn({
+            let gaps = gaps.clone();
+
+            async move {
+                select! {
+                    _ = tx.closed() => {},
+                    _ = track_last(last, &tx, &gaps) => {}
+                }
+            }
+        });
+
+        Self { rx, gaps }
+    }
+
+    /// Same as [`Self::new`] resuming from the block `hint` of a previous run, e.g. its [`Self::current_seqno`].
+    /// Blocks behind it are left out
+    pub fn with_hint<L>(last: L, hint: i32) -> Self where L: Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static {
+        let last = last.filter(move |id| futures::future::ready(!matches!(id, Ok(id) if id.seqno < hint)));
+
+        Self::new(last)
+    }
+
+    /// Polls every client each `interval` and tracks the highest tip any of them reports
+    pub fn from_clients<S>(clients: impl IntoIterator<Item = S>, interval: Duration) -> Self
+        where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error> + Clone + Send + 'static,
+              S::Future: Send {
+        let last = futures::stream::select_all(clients.into_iter().map(|client| poll_last_block(client, interval).boxed()));
+
+        Self::new(last)
+    }
+
+    pub fn receiver(&self) -> watch::Receiver<Option<TonNodeBlockIdExt>> {
+        self.rx.clone()
+    }
+
+    /// Tracked blocks starting with the current one, the stream ends with the `last` stream. Blocks the tracker
+    /// moved past before the stream is polled again are skipped
+    pub fn receiver_stream(&self) -> impl Stream<Item = TonNodeBlockIdExt> + Send + Unpin + 'static {
+        blocks(self.rx.clone())
+    }
+
+    /// Same as [`Self::receiver_stream`], consuming the tracker
+    pub fn into_stream(self) -> impl Stream<Item = TonNodeBlockIdExt> + Send + Unpin + 'static {
+        blocks(self.rx)
+    }
+
+    pub fn last(&self) -> Option<TonNodeBlockIdExt> {
+        self.rx.borrow().clone()
+    }
+
+    /// Seqno of the tracked block, to be persisted and passed to [`Self::with_hint`] on restart
+    pub fn current_seqno(&self) -> Option<i32> {
+        self.rx.borrow().as_ref().map(|id| id.seqno)
+    }
+
+    /// Whether the tracked block moved since the last [`Self::poll`], it doesn't wait.
+    /// A tracker whose `last` stream has ended never changes again
+    pub fn has_changed(&self) -> bool {
+        self.rx.has_changed().unwrap_or(false)
+    }
+
+    /// The tracked block when it moved since the last call, `None` otherwise. For event loops that can't await the receiver
+    pub fn poll(&mut self) -> Option<TonNodeBlockIdExt> {
+        if !self.has_changed() {
+            return None;
+        }
+
+        self.rx.borrow_and_update().clone()
+    }
+
+    /// Observed blocks that were behind the tracked one or had its seqno with another hash
+    pub fn gap_count(&self) -> usize {
+        self.gaps.load(Ordering::Relaxed)
+    }
+
+    /// The tracked block once there is one, `TransportError::ChannelClosed` when `last` ends before yielding a block
+    pub async fn wait_last(&self) -> Result<TonNodeBlockIdExt, Error> {
+        wait_for_block(&mut self.rx.clone()).await
+    }
+
+    /// Same as [`Self::wait_last`], `Error::Timeout` when no block arrives within `timeout`. Dropping the future is safe
+    pub async fn wait_last_timeout(&self, timeout: Duration) -> Result<TonNodeBlockIdExt, Error> {
+        tokio::time::timeout(timeout, self.wait_last())
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+}
+
+fn blocks(rx: watch::Receiver<Option<TonNodeBlockIdExt>>) -> impl Stream<Item = TonNodeBlockIdExt> {
+    WatchStream::new(rx).filter_map(futures::future::ready)
+}
+
+async fn wait_for_block(rx: &mut watch::Receiver<Option<TonNodeBlockIdExt>>) -> Result<TonNodeBlockIdExt, Error> {
+    let id = rx.wait_for(Option::is_some)
+        .await
+        .map_err(|_| TransportError::ChannelClosed)?
+        .clone();
+
+    Ok(id.expect("waited for a block"))
+}
+
+async fn track_last<L>(last: L, tx: &watch::Sender<Option<TonNodeBlockIdExt>>, gaps: &AtomicUsize) where L: Stream<Item = Result<TonNodeBlockIdExt, Error>> {
+    let mut last = std::pin::pin!(last);
+
+    while let Some(next) = last.next().await {
+        match next {
+            Ok(id) => {
+                tx.send_if_modified(|current| {
+                    if is_out_of_order(current, &id) {
+                        gaps.fetch_add(1, Ordering::Relaxed);
+                        tracing::warn!(seqno = id.seqno, last = current.as_ref().map(|current| current.seqno), "last block out of order");
+                    }
+
+                    advance(current, id)
+                });
+            },
+            Err(error) => tracing::warn!(error = ?error, "last block poll failed")
+        }
+    }
+}
+
+/// `id` is behind `current` or has its seqno but is another block, the same block polled again is in order
+fn is_out_of_order(current: &Option<TonNodeBlockIdExt>, id: &TonNodeBlockIdExt) -> bool {
+    current.as_ref().is_some_and(|current| id.seqno < current.seqno || (id.seqno == current.seqno && id != current))
+}
+
+/// Replaces `current` with `id` when `id` is ahead of it
+fn advance(current: &mut Option<TonNodeBlockIdExt>, id: TonNodeBlockIdExt) -> bool {
+    if current.as_ref().is_some_and(|current| current.seqno >= id.seqno) {
+        return false;
+    }
+    current.replace(id);
+
+    true
+}
+
+/// Move of the first available block, `previous` is `None` for the first block found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirstBlockChange {
+    pub first: TonNodeBlockIdExt,
+    pub previous: Option<TonNodeBlockIdExt>,
+}
+
+impl FirstBlockChange {
+    /// Seqnos the first block moved by, positive when history was trimmed
+    pub fn delta(&self) -> i32 {
+        self.previous.as_ref().map_or(0, |previous| self.first.seqno - previous.seqno)
+    }
+
+    pub fn is_trimmed(&self) -> bool {
+        self.delta() > 0
+    }
+}
+
+/// Longest wait of [`MasterchainFirstBlockTracker`] for the first last block before it warns again
+const MAX_STARTUP_WAIT: Duration = Duration::from_secs(60);
+
+/// Default of [`TrackerConfig::first_block_rechecks`]
+const FIRST_BLOCK_RECHECKS: usize = 3;
+
+/// Alarm of [`MasterchainFirstBlockTracker::with_watchdog`] for a tracker whose checks keep failing, it would
+/// otherwise hold on to a stale first block while looking alive
+#[derive(Clone)]
+pub struct Watchdog {
+    /// Time without a successful check after which the tracker is stuck
+    pub stale_after: Duration,
+    /// Called every `stale_after` while the tracker is stuck, e.g. to reconnect the client it runs over
+    pub on_stale: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+/// Follows the first masterchain block the liteserver still has, it moves forward as the server prunes old blocks
+pub struct MasterchainFirstBlockTracker {
+    rx: watch::Receiver<Option<TonNodeBlockIdExt>>,
+    changes: watch::Receiver<Option<FirstBlockChange>>,
+    last_success: Arc<Mutex<Option<Instant>>>
+}
+
+impl MasterchainFirstBlockTracker {
+    /// Checks every `interval` whether the first block is still available and searches up to the block `last` holds when it isn't
+    pub fn new<S>(service: S, last: watch::Receiver<Option<TonNodeBlockIdExt>>, interval: Duration) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send {
+        Self::with_tasks(service, last, interval, &TaskTracker::new())
+    }
+
+    /// Same as [`Self::new`] with the tracking task spawned on `tasks`
+    pub fn with_tasks<S>(service: S, last: watch::Receiver<Option<TonNodeBlockIdExt>>, interval: Duration, tasks: &TaskTracker) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send {
+        Self::spawn(service, last, interval, None, None, tasks)
+    }
+
+    /// Same as [`Self::new`] resuming from the first block `hint` of a previous run, e.g. its [`Self::current_seqno`].
+    /// The hint is taken for the first block while the server has it, the search only runs once it is pruned
+    pub fn with_hint<S>(service: S, last: watch::Receiver<Option<TonNodeBlockIdExt>>, interval: Duration, hint: i32) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send {
+        Self::spawn(service, last, interval, Some(hint), None, &TaskTracker::new())
+    }
+
+    /// Same as [`Self::new`] raising `watchdog` when no check succeeds for `watchdog.stale_after`, counting from
+    /// the start. Every failure is only logged as a warning otherwise
+    pub fn with_watchdog<S>(service: S, last: watch::Receiver<Option<TonNodeBlockIdExt>>, interval: Duration, watchdog: Watchdog) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send {
+        Self::spawn(service, last, interval, None, Some(watchdog), &TaskTracker::new())
+    }
+
+    fn spawn<S>(service: S, last: watch::Receiver<Option<TonNodeBlockIdExt>>, interval: Duration, hint: Option<i32>, watchdog: Option<Watchdog>, tasks: &TaskTracker) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send {
+        let (tx, rx) = watch::channel(None);
+        let (changes_tx, changes) = watch::channel(None);
+        let last_success = Arc::new(Mutex::new(None));
+
+        tasks.spawn({
+            let last_success = last_success.clone();
+
+            async move {
+                select! {
+                    _ = tx.closed() => {},
+                    _ = track_first(service, last, interval, hint, FIRST_BLOCK_RECHECKS, &tx, &changes_tx, &last_success) => {},
+                    _ = watch_stale(&last_success, Instant::now(), watchdog.as_ref()) => {}
+                }
+            }
+        });
+
+        Self { rx, changes, last_success }
+    }
+
+    pub fn receiver(&self) -> watch::Receiver<Option<TonNodeBlockIdExt>> {
+        self.rx.clone()
+    }
+
+    /// Every move of the first block along with the block it moved from
+    pub fn changes(&self) -> watch::Receiver<Option<FirstBlockChange>> {
+        self.changes.clone()
+    }
+
+    pub fn first(&self) -> Option<TonNodeBlockIdExt> {
+        self.rx.borrow().clone()
+    }
+
+    /// Seqno of the first block, to be persisted and passed to [`Self::with_hint`] on restart
+    pub fn current_seqno(&self) -> Option<i32> {
+        self.rx.borrow().as_ref().map(|id| id.seqno)
+    }
+
+    /// When a check or a search of the first block last succeeded, `None` before the first one
+    pub fn last_success(&self) -> Option<Instant> {
+        *self.last_success.lock().unwrap()
+    }
+}
+
+/// Raises `watchdog` every `stale_after` while `last_success`, or `started` before the first success, is older than that
+async fn watch_stale(last_success: &Mutex<Option<Instant>>, started: Instant, watchdog: Option<&Watchdog>) {
+    let Some(watchdog) = watchdog else {
+        return futures::future::pending().await
+    };
+
+    loop {
+        let since = last_success.lock().unwrap().unwrap_or(started);
+        let due = since + watchdog.stale_after;
+        if Instant::now() < due {
+            tokio::time::sleep_until(due.into()).await;
+            continue
+        }
+
+        tracing::error!(since = ?since.elapsed(), "first block tracker is stuck");
+        if let Some(on_stale) = &watchdog.on_stale {
+            on_stale();
+        }
+        tokio::time::sleep(watchdog.stale_after).await;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn track_first<S>(service: S, mut last: watch::Receiver<Option<TonNodeBlockIdExt>>, interval: Duration, hint: Option<i32>, rechecks: usize, tx: &watch::Sender<Option<TonNodeBlockIdExt>>, changes: &watch::Sender<Option<FirstBlockChange>>, last_success: &Mutex<Option<Instant>>)
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone {
+    let succeeded = || *last_success.lock().unwrap() = Some(Instant::now());
+
+    // the last block may take a while at startup, the wait for it doubles up to `MAX_STARTUP_WAIT`
+    let mut wait = interval;
+    loop {
+        match tokio::time::timeout(wait, wait_for_block(&mut last)).await {
+            Ok(Ok(_)) => break,
+            Ok(Err(_)) => return,
+            Err(_) => {
+                tracing::warn!(wait = ?wait, "no last block to search the first block up to");
+                wait = (wait * 2).min(MAX_STARTUP_WAIT);
+            }
+        }
+    }
+
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let Some(last) = last.borrow().clone() else { continue };
+        let current = tx.borrow().as_ref().map(|id| id.seqno);
+        let from = match current {
+            Some(seqno) => match lookup(&service, &last, seqno).await {
+                Ok(_) => {
+                    succeeded();
+                    continue
+                },
+                Err(Error::NotFound(_)) => seqno + 1,
+                Err(error) => {
+                    tracing::warn!(error = ?error, "first block check failed");
+                    continue
+                }
+            },
+            None => match hint {
+                Some(hint) => match lookup(&service, &last, hint).await {
+                    Ok(header) => {
+                        publish_first(header.id, tx, changes);
+                        succeeded();
+                        continue
+                    },
+                    Err(Error::NotFound(_)) => hint + 1,
+                    Err(error) => {
+                        tracing::warn!(error = ?error, "first block hint check failed");
+                        continue
+                    }
+                },
+                None => 1
+            }
+        };
+
+        match find_first_block(&service, &last, from, rechecks).await {
+            Ok(header) => {
+                publish_first(header.id, tx, changes);
+                succeeded();
+            },
+            Err(error) => tracing::warn!(error = ?error, "first block search failed")
+        }
+    }
+}
+
+fn publish_first(first: TonNodeBlockIdExt, tx: &watch::Sender<Option<TonNodeBlockIdExt>>, changes: &watch::Sender<Option<FirstBlockChange>>) {
+    let previous = tx.send_replace(Some(first.clone()));
+    if previous.as_ref() != Some(&first) {
+        changes.send_replace(Some(FirstBlockChange { first, previous }));
+    }
+}
+
+/// First available block in `from..=last.seqno`. The server may prune blocks during the search, so the block
+/// found is looked up again and the search goes on past it when it's gone, up to `rechecks` times
+async fn find_first_block<S>(service: &S, last: &TonNodeBlockIdExt, from: i32, rechecks: usize) -> Result<LiteServerBlockHeader, Error>
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone {
+    let mut header = search_first_block(service, last, from).await?;
+    for _ in 0 .. rechecks {
+        match lookup(service, last, header.id.seqno).await {
+            Ok(_) => break,
+            Err(Error::NotFound(_)) => {
+                tracing::debug!(seqno = header.id.seqno, "first block pruned during the search");
+
+                header = search_first_block(service, last, header.id.seqno + 1).await?;
+            },
+            Err(error) => return Err(error)
+        }
+    }
+
+    Ok(header)
+}
+
+/// Binary search of the first available block in `from..=last.seqno`, missing blocks answer `Error::NotFound`
+async fn search_first_block<S>(service: &S, last: &TonNodeBlockIdExt, from: i32) -> Result<LiteServerBlockHeader, Error>
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone {
+    let (mut lhs, mut rhs) = (from.min(last.seqno), last.seqno);
+    let mut found = None;
+
+    while lhs < rhs {
+        let mid = lhs + (rhs - lhs) / 2;
+        match lookup(service, last, mid).await {
+            Ok(header) => {
+                rhs = mid;
+                found = Some(header);
+            },
+            Err(Error::NotFound(_)) => lhs = mid + 1,
+            Err(error) => return Err(error)
+        }
+    }
+
+    match found {
+        Some(header) if header.id.seqno == lhs => Ok(header),
+        _ => lookup(service, last, lhs).await
+    }
+}
+
+async fn lookup<S>(service: &S, last: &TonNodeBlockIdExt, seqno: i32) -> Result<LiteServerBlockHeader, Error>
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone {
+    let id = TonNodeBlockId { workchain: last.workchain, shard: last.shard, seqno };
+
+    service.clone()
+        .oneshot(LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None })
+        .await
+}
+
+/// Poll intervals of the trackers in [`MasterchainTracker`] and the task set they run on, a private one when `None`.
+/// A shared set lets the application `wait()` for the tracker tasks on shutdown
+#[derive(Debug, Clone)]
+pub struct TrackerConfig {
+    pub last_block_interval: Duration,
+    pub first_block_interval: Duration,
+    pub tasks: Option<TaskTracker>,
+    /// See [`MasterchainFirstBlockTracker::with_hint`]
+    pub first_block_hint: Option<i32>,
+    /// Lookups of the first block found, a server pruning meanwhile moves the search forward
+    pub first_block_rechecks: usize,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self { last_block_interval: Duration::from_secs(1), first_block_interval: Duration::from_secs(30), tasks: None, first_block_hint: None, first_block_rechecks: FIRST_BLOCK_RECHECKS }
+    }
+}
+
+/// First and last masterchain block trackers over one client, dropping it stops both
+pub struct MasterchainTracker {
+    first: watch::Receiver<Option<TonNodeBlockIdExt>>,
+    first_changes: watch::Receiver<Option<FirstBlockChange>>,
+    last: watch::Receiver<Option<TonNodeBlockIdExt>>,
+    gaps: Arc<AtomicUsize>,
+    _drop_guard: DropGuard
+}
+
+impl MasterchainTracker {
+    pub fn new(client: LiteServerClient, config: TrackerConfig) -> Self {
+        Self::with_service(client, config)
+    }
+
+    /// Both trackers over the backend of `pool` that `key` maps to, see [`LiteServerPool::with_affinity`], so the first
+    /// and the last block come from one server. They move to another backend together while it is ejected
+    pub fn pinned<S>(pool: &LiteServerPool<S>, key: impl Hash, config: TrackerConfig) -> Self
+        where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+                + Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error>
+                + Clone + Send + Sync + 'static,
+              <S as Service<LiteServerGetMasterchainInfo>>::Future: Send,
+              <S as Service<LiteServerLookupBlock>>::Future: Send {
+        Self::with_service(pool.with_affinity(key), config)
+    }
+
+    fn with_service<S>(service: S, config: TrackerConfig) -> Self
+        where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+                + Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error>
+                + Clone + Send + Sync + 'static,
+              <S as Service<LiteServerGetMasterchainInfo>>::Future: Send,
+              <S as Service<LiteServerLookupBlock>>::Future: Send {
+        let token = CancellationToken::new();
+        let (last_tx, last) = watch::channel(None);
+        let (first_tx, first) = watch::channel(None);
+        let (first_changes_tx, first_changes) = watch::channel(None);
+        let gaps = Arc::new(AtomicUsize::new(0));
+        let tasks = config.tasks.unwrap_or_default();
+
+        tasks.spawn({
+            let token = token.clone();
+            let polls = poll_last_block(service.clone(), config.last_block_interval);
+            let gaps = gaps.clone();
+
+            async move {
+                select! {
+                    _ = token.cancelled() => {},
+                    _ = track_last(polls, &last_tx, &gaps) => {}
+                }
+            }
+        });
+        tasks.spawn({
+            let token = token.clone();
+            let last = last.clone();
+
+            async move {
+                let last_success = Mutex::default();
+
+                select! {
+                    _ = token.cancelled() => {},
+                    _ = track_first(service, last, config.first_block_interval, config.first_block_hint, config.first_block_rechecks, &first_tx, &first_changes_tx, &last_success) => {}
+                }
+            }
+        });
+
+        Self { first, first_changes, last, gaps, _drop_guard: token.drop_guard() }
+    }
+
+    pub fn first_receiver(&self) -> watch::Receiver<Option<TonNodeBlockIdExt>> {
+        self.first.clone()
+    }
+
+    /// See [`MasterchainFirstBlockTracker::changes`]
+    pub fn first_changes_receiver(&self) -> watch::Receiver<Option<FirstBlockChange>> {
+        self.first_changes.clone()
+    }
+
+    pub fn last_receiver(&self) -> watch::Receiver<Option<TonNodeBlockIdExt>> {
+        self.last.clone()
+    }
+
+    /// See [`MasterchainLastBlockTracker::gap_count`]
+    pub fn gap_count(&self) -> usize {
+        self.gaps.load(Ordering::Relaxed)
+    }
+}
+
+/// Masterchain block as tracked by [`SoftMasterchainTracker`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MasterchainBlock {
+    /// Signed by the validators and part of the chain
+    Final(TonNodeBlockIdExt),
+    /// Candidate the validators are working on, it may be replaced by another block with its seqno
+    Soft(TonNodeBlockIdExt),
+}
+
+impl MasterchainBlock {
+    pub fn id(&self) -> &TonNodeBlockIdExt {
+        match self {
+            MasterchainBlock::Final(id) | MasterchainBlock::Soft(id) => id
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, MasterchainBlock::Final(_))
+    }
+}
+
+/// Follows the last masterchain block ahead of finalization: the candidate of the next block once the validators
+/// have one, the final block otherwise. A final block replaces the soft one with its seqno. Liteservers that don't
+/// answer `liteServer.nonfinal.getValidatorGroups` only give final blocks
+pub struct SoftMasterchainTracker {
+    rx: watch::Receiver<Option<MasterchainBlock>>
+}
+
+impl SoftMasterchainTracker {
+    /// Polls the last block and the candidates every `interval`
+    pub fn new<S>(service: S, interval: Duration) -> Self
+        where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+                + Service<LiteServerNonfinalGetValidatorGroups, Response = LiteServerNonfinalValidatorGroups, Error = Error>
+                + Clone + Send + Sync + 'static,
+              <S as Service<LiteServerGetMasterchainInfo>>::Future: Send,
+              <S as Service<LiteServerNonfinalGetValidatorGroups>>::Future: Send {
+        let (tx, rx) = watch::channel(None);
+
+        tokio::spawn(async move {
+            select! {
+                _ = tx.closed() => {},
+                _ = track_soft(service, interval, &tx) => {}
+            }
+        });
+
+        Self { rx }
+    }
+
+    pub fn receiver(&self) -> watch::Receiver<Option<MasterchainBlock>> {
+        self.rx.clone()
+    }
+
+    pub fn last(&self) -> Option<MasterchainBlock> {
+        self.rx.borrow().clone()
+    }
+}
+
+async fn track_soft<S>(service: S, interval: Duration, tx: &watch::Sender<Option<MasterchainBlock>>)
+    where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+            + Service<LiteServerNonfinalGetValidatorGroups, Response = LiteServerNonfinalValidatorGroups, Error = Error>
+            + Clone {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        match last_soft_block(&service).await {
+            Ok(block) => { tx.send_if_modified(|current| advance_soft(current, block)); },
+            Err(error) => tracing::warn!(error = ?error, "soft block poll failed")
+        }
+    }
+}
+
+/// The available candidate ahead of the last final block with the most signed weight, the final block when there is none
+async fn last_soft_block<S>(service: &S) -> Result<MasterchainBlock, Error>
+    where S: Service<LiteServerGetMasterchainInfo, Response = LiteServerMasterchainInfo, Error = Error>
+            + Service<LiteServerNonfinalGetValidatorGroups, Response = LiteServerNonfinalValidatorGroups, Error = Error>
+            + Clone {
+    let last = ServiceExt::<LiteServerGetMasterchainInfo>::oneshot(service.clone(), LiteServerGetMasterchainInfo::default())
+        .await?
+        .last;
+    let request = LiteServerNonfinalGetValidatorGroups { mode: 0b11, wc: Some(last.workchain), shard: Some(last.shard) };
+    let groups = match ServiceExt::<LiteServerNonfinalGetValidatorGroups>::oneshot(service.clone(), request).await {
+        Ok(groups) => groups,
+        // the server doesn't follow non-final blocks
+        Err(Error::LiteServerError { .. }) => return Ok(MasterchainBlock::Final(last)),
+        Err(error) => return Err(error)
+    };
+
+    let candidate = groups.groups
+        .into_iter()
+        .flat_map(|group| group.candidates)
+        .filter(|candidate| bool::from(&candidate.available) && candidate.id.block_id.seqno > last.seqno)
+        .max_by_key(|candidate| (candidate.id.block_id.seqno, candidate.signed_weight, candidate.approved_weight));
+
+    Ok(match candidate {
+        Some(candidate) => MasterchainBlock::Soft(candidate.id.block_id),
+        None => MasterchainBlock::Final(last)
+    })
+}
+
+/// Replaces `current` with `block` when it is ahead, or has the same seqno and is final while `current` isn't
+fn advance_soft(current: &mut Option<MasterchainBlock>, block: MasterchainBlock) -> bool {
+    let key = |block: &MasterchainBlock| (block.id().seqno, block.is_final());
+    if current.as_ref().is_some_and(|current| key(current) >= key(&block)) {
+        return false;
+    }
+    current.replace(block);
+
+    true
+}
+
+/// Follows the latest masterchain key block, the trust anchor of a light client
+pub struct KeyBlockTracker {
+    rx: watch::Receiver<Option<TonNodeBlockIdExt>>
+}
+
+impl KeyBlockTracker {
+    /// `headers` yields masterchain block headers in seqno order, e.g. [`crate::block_stream::MasterchainBlockStream`].
+    /// The key block preceding the first header is looked up through `service`, the headers are checked against their proofs
+    pub fn new<S, H>(service: S, headers: H) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send,
+              H: Stream<Item = Result<LiteServerBlockHeader, Error>> + Send + 'static {
+        Self::with_tasks(service, headers, &TaskTracker::new())
+    }
+
+    /// Same as [`Self::new`] with the tracking task spawned on `tasks`
+    pub fn with_tasks<S, H>(service: S, headers: H, tasks: &TaskTracker) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send,
+              H: Stream<Item = Result<LiteServerBlockHeader, Error>> + Send + 'static {
+        let (tx, rx) = watch::channel(None);
+
+        tasks.spawn(async move {
+            select! {
+                _ = tx.closed() => {},
+                _ = track(service, headers, &tx) => {}
+            }
+        });
+
+        Self { rx }
+    }
+
+    pub fn receiver(&self) -> watch::Receiver<Option<TonNodeBlockIdExt>> {
+        self.rx.clone()
+    }
+
+    pub fn last(&self) -> Option<TonNodeBlockIdExt> {
+        self.rx.borrow().clone()
+    }
+}
+
+async fn track<S, H>(service: S, headers: H, tx: &watch::Sender<Option<TonNodeBlockIdExt>>)
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone,
+          H: Stream<Item = Result<LiteServerBlockHeader, Error>> {
+    let mut headers = std::pin::pin!(headers);
+
+    while let Some(header) = headers.next().await {
+        let backfill = tx.borrow().is_none();
+        let result = match header {
+            Ok(header) => next_key_block(&service, header, backfill).await,
+            Err(error) => Err(error.into())
+        };
+
+        match result {
+            Ok(Some(id)) => { tx.send_replace(Some(id)); },
+            Ok(None) => {},
+            Err(error) => tracing::warn!(error = ?error, "key block tracking failed")
+        }
+    }
+}
+
+/// `header` itself when it is a key block, otherwise the key block before it when there is no key block yet
+async fn next_key_block<S>(service: &S, header: LiteServerBlockHeader, backfill: bool) -> anyhow::Result<Option<TonNodeBlockIdExt>>
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone {
+    let info = BlockHeader::from_proof(&header.header_proof, &header.id)?;
+    if info.key_block {
+        return Ok(Some(header.id));
+    }
+    if !backfill {
+        return Ok(None);
+    }
+
+    let id = TonNodeBlockId { workchain: header.id.workchain, shard: header.id.shard, seqno: info.prev_key_block_seqno };
+    let key_block = service.clone()
+        .oneshot(LiteServerLookupBlock { mode: MODE_SEQNO, id, lt: None, utime: None })
+        .await?;
+
+    Ok(Some(key_block.id))
+}
+
+/// Capacity of the channel of [`NetworkTracker`], the trackers wait while it is full
+const NETWORK_BLOCKS_CAPACITY: usize = 256;
+
+/// Every masterchain block and every block of the shards it refers to. Each shard is followed from the top block
+/// it first appears with, until a masterchain block no longer lists it after a split or a merge. Blocks of one chain
+/// come in seqno order, blocks of different chains aren't ordered
+pub struct NetworkTracker {
+    rx: mpsc::Receiver<TonNodeBlockIdExt>,
+    shards: watch::Receiver<Vec<TonNodeBlockIdExt>>
+}
+
+impl NetworkTracker {
+    /// `last` yields the last masterchain block, e.g. [`poll_last_block`], the shards are asked for on every new one
+    pub fn new<S, L>(service: S, last: L) -> Self
+        where S: Service<LiteServerGetAllShardsInfo, Response = LiteServerAllShardsInfo, Error = Error>
+                + Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error>
+                + Clone + Send + Sync + 'static,
+              <S as Service<LiteServerGetAllShardsInfo>>::Future: Send,
+              <S as Service<LiteServerLookupBlock>>::Future: Send,
+              L: Stream<Item = Result<TonNodeBlockIdExt, Error>> + Send + 'static {
+        let updates = {
+            let service = service.clone();
+
+            async_stream::stream! {
+                let mut current = None;
+                for await last in last {
+                    match last {
+                        Ok(last) if advance(&mut current, last.clone()) => yield shards_of(&service, last).await,
+                        Ok(_) => {},
+                        Err(error) => yield Err(error)
+                    }
+                }
+            }
+        };
+
+        Self::with_updates(service, updates)
+    }
+
+    /// `updates` yields new masterchain blocks in seqno order along with the top blocks of their shards
+    pub fn with_updates<S, U>(service: S, updates: U) -> Self
+        where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+              S::Future: Send,
+              U: Stream<Item = Result<(TonNodeBlockIdExt, Vec<TonNodeBlockIdExt>), Error>> + Send + 'static {
+        let (tx, rx) = mpsc::channel(NETWORK_BLOCKS_CAPACITY);
+        let (shards_tx, shards) = watch::channel(Vec::new());
+
+        tokio::spawn(async move {
+            select! {
+                _ = tx.closed() => {},
+                _ = track_network(service, updates, &tx, &shards_tx) => {}
+            }
+        });
+
+        Self { rx, shards }
+    }
+
+    /// Top blocks of the followed shards as of the last masterchain block
+    pub fn shards_receiver(&self) -> watch::Receiver<Vec<TonNodeBlockIdExt>> {
+        self.shards.clone()
+    }
+
+    pub fn shards(&self) -> Vec<TonNodeBlockIdExt> {
+        self.shards.borrow().clone()
+    }
+}
+
+impl Stream for NetworkTracker {
+    type Item = TonNodeBlockIdExt;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+async fn shards_of<S>(service: &S, last: TonNodeBlockIdExt) -> Result<(TonNodeBlockIdExt, Vec<TonNodeBlockIdExt>), Error>
+    where S: Service<LiteServerGetAllShardsInfo, Response = LiteServerAllShardsInfo, Error = Error> + Clone {
+    let info = ServiceExt::<LiteServerGetAllShardsInfo>::oneshot(service.clone(), LiteServerGetAllShardsInfo { id: last.clone() })
+        .await?;
+    let shards = info.shards().map_err(|_| Error::Decode)?;
+
+    Ok((last, shards))
+}
+
+async fn track_network<S, U>(service: S, updates: U, tx: &mpsc::Sender<TonNodeBlockIdExt>, shards_tx: &watch::Sender<Vec<TonNodeBlockIdExt>>)
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone + Send + Sync + 'static,
+          S::Future: Send,
+          U: Stream<Item = Result<(TonNodeBlockIdExt, Vec<TonNodeBlockIdExt>), Error>> {
+    let mut updates = std::pin::pin!(updates);
+    // top block senders of the shard trackers, dropping one stops its tracker once it has caught up
+    let mut trackers: HashMap<(i32, i64), watch::Sender<TonNodeBlockIdExt>> = HashMap::new();
+
+    while let Some(update) = updates.next().await {
+        let (last, shards) = match update {
+            Ok(update) => update,
+            Err(error) => {
+                tracing::warn!(error = ?error, "shards poll failed");
+                continue
+            }
+        };
+        if tx.send(last).await.is_err() {
+            return;
+        }
+
+        // a split or merged shard is replaced by new ones in the config
+        trackers.retain(|(workchain, shard), _| shards.iter().any(|top| top.workchain == *workchain && top.shard == *shard));
+        for top in &shards {
+            match trackers.entry((top.workchain, top.shard)) {
+                Entry::Occupied(entry) => {
+                    entry.get().send_if_modified(|current| {
+                        if current.seqno >= top.seqno {
+                            return false;
+                        }
+                        *current = top.clone();
+
+                        true
+                    });
+                },
+                Entry::Vacant(entry) => {
+                    let (top_tx, top_rx) = watch::channel(top.clone());
+                    tokio::spawn(track_shard(service.clone(), top.clone(), top_rx, tx.clone()));
+
+                    entry.insert(top_tx);
+                }
+            }
+        }
+        shards_tx.send_replace(shards);
+    }
+}
+
+/// Emits `first` and then every block of its shard up to each new top block
+async fn track_shard<S>(service: S, first: TonNodeBlockIdExt, mut top: watch::Receiver<TonNodeBlockIdExt>, tx: mpsc::Sender<TonNodeBlockIdExt>)
+    where S: Service<LiteServerLookupBlock, Response = LiteServerBlockHeader, Error = Error> + Clone {
+    // the receiver may already hold a later top block, that one is pending as a change
+    let mut last = first;
+    if tx.send(last.clone()).await.is_err() {
+        return;
+    }
+
+    while top.changed().await.is_ok() {
+        let next = top.borrow_and_update().clone();
+        for seqno in last.seqno + 1 .. next.seqno {
+            match lookup(&service, &last, seqno).await {
+                Ok(header) => if tx.send(header.id).await.is_err() { return },
+                Err(error) => tracing::warn!(error = ?error, workchain = last.workchain, shard = last.shard, seqno, "shard block lookup failed")
+            }
+        }
+        if tx.send(next.clone()).await.is_err() {
+            return;
+        }
+        last = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicI32};
+    use futures::channel::mpsc;
+    use tower::service_fn;
+    use crate::mock::MockLiteServer;
+    use crate::tl::{LiteServerNonfinalCandidateId, LiteServerNonfinalCandidateInfo, LiteServerNonfinalValidatorGroupInfo, TonNodeZeroStateIdExt};
+    use super::*;
+
+    fn block_id(seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: -1, shard: i64::MIN, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    #[test]
+    fn advance_only_moves_forward_test() {
+        let mut current = None;
+
+        let seqnos: Vec<_> = [5, 3, 7, 6, 7, 8, 1]
+            .into_iter()
+            .map(|seqno| {
+                advance(&mut current, block_id(seqno));
+
+                current.as_ref().unwrap().seqno
+            })
+            .collect();
+
+        assert_eq!(seqnos, vec![5, 5, 7, 7, 7, 8, 8]);
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_never_goes_back_test() {
+        let last = futures::stream::iter([5, 3, 7, 6, 2].map(|seqno| Ok(block_id(seqno))));
+        let tracker = MasterchainLastBlockTracker::new(last);
+        let mut rx = tracker.receiver();
+
+        let mut seen = vec![];
+        while rx.changed().await.is_ok() {
+            seen.push(rx.borrow_and_update().as_ref().unwrap().seqno);
+        }
+
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(tracker.last().unwrap().seqno, 7);
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_poll_test() {
+        let (last_tx, last) = mpsc::unbounded();
+        let mut tracker = MasterchainLastBlockTracker::new(last);
+
+        assert!(!tracker.has_changed());
+        assert_eq!(tracker.poll(), None);
+
+        last_tx.unbounded_send(Ok(block_id(5))).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async { while !tracker.has_changed() { tokio::task::yield_now().await } }).await.unwrap();
+        assert_eq!(tracker.poll(), Some(block_id(5)));
+        assert!(!tracker.has_changed());
+        assert_eq!(tracker.poll(), None);
+
+        // a block behind isn't an update
+        last_tx.unbounded_send(Ok(block_id(3))).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!tracker.has_changed());
+
+        last_tx.unbounded_send(Ok(block_id(6))).unwrap();
+        last_tx.unbounded_send(Ok(block_id(7))).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async { while !tracker.has_changed() { tokio::task::yield_now().await } }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // both updates are seen at once
+        assert_eq!(tracker.poll(), Some(block_id(7)));
+        assert!(!tracker.has_changed());
+    }
+
+    #[test]
+    fn advance_soft_test() {
+        let mut current = None;
+
+        assert!(advance_soft(&mut current, MasterchainBlock::Final(block_id(5))));
+        assert!(advance_soft(&mut current, MasterchainBlock::Soft(block_id(6))));
+        // another candidate of the same seqno
+        assert!(!advance_soft(&mut current, MasterchainBlock::Soft(TonNodeBlockIdExt { root_hash: [1; 32], ..block_id(6) })));
+        assert!(!advance_soft(&mut current, MasterchainBlock::Final(block_id(5))));
+        assert!(advance_soft(&mut current, MasterchainBlock::Final(block_id(6))));
+        assert!(!advance_soft(&mut current, MasterchainBlock::Soft(block_id(6))));
+        assert_eq!(current, Some(MasterchainBlock::Final(block_id(6))));
+    }
+
+    #[tokio::test]
+    async fn soft_tracker_emits_candidate_then_final_test() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let mock = MockLiteServer::default()
+            .on({
+                let polls = polls.clone();
+
+                move |_: LiteServerGetMasterchainInfo| {
+                    let seqno = if polls.fetch_add(1, Ordering::SeqCst) < 3 { 5 } else { 6 };
+
+                    Ok(LiteServerMasterchainInfo { last: block_id(seqno), state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } })
+                }
+            })
+            .on(|request: LiteServerNonfinalGetValidatorGroups| {
+                assert_eq!((request.wc, request.shard), (Some(-1), Some(i64::MIN)));
+                let candidate = |seqno: i32, signed_weight: i64| LiteServerNonfinalCandidateInfo {
+                    id: LiteServerNonfinalCandidateId { block_id: TonNodeBlockIdExt { root_hash: [signed_weight as u8; 32], ..block_id(seqno) }, creator: [0; 32], collated_data_hash: [0; 32] },
+                    available: true.into(),
+                    approved_weight: signed_weight,
+                    signed_weight,
+                    total_weight: 100
+                };
+                let group = LiteServerNonfinalValidatorGroupInfo {
+                    next_block_id: TonNodeBlockId { workchain: -1, shard: i64::MIN, seqno: 6 },
+                    cc_seqno: 1,
+                    prev: vec![block_id(5)],
+                    // the candidates of a block that became final are outdated
+                    candidates: vec![candidate(6, 10), candidate(6, 70), candidate(5, 100)]
+                };
+
+                Ok(LiteServerNonfinalValidatorGroups { groups: vec![group] })
+            });
+        let tracker = SoftMasterchainTracker::new(mock, Duration::from_millis(10));
+        let mut rx = tracker.receiver();
+
+        rx.changed().await.unwrap();
+        let soft = rx.borrow_and_update().clone().unwrap();
+        rx.changed().await.unwrap();
+        let finalized = rx.borrow_and_update().clone().unwrap();
+
+        assert_eq!(soft, MasterchainBlock::Soft(TonNodeBlockIdExt { root_hash: [70; 32], ..block_id(6) }));
+        assert!(!soft.is_final());
+        assert_eq!(finalized, MasterchainBlock::Final(block_id(6)));
+        assert!(finalized.is_final());
+    }
+
+    #[tokio::test]
+    async fn soft_tracker_without_nonfinal_support_test() {
+        let mock = pruned_server(1)
+            .on(|_: LiteServerNonfinalGetValidatorGroups| Err(Error::LiteServerError { code: -400, message: "unknown query".to_owned() }));
+        let tracker = SoftMasterchainTracker::new(mock, Duration::from_millis(10));
+        let mut rx = tracker.receiver();
+
+        rx.changed().await.unwrap();
+
+        assert_eq!(tracker.last(), Some(MasterchainBlock::Final(block_id(9))));
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_counts_gaps_test() {
+        let fork = TonNodeBlockIdExt { root_hash: [1; 32], ..block_id(4) };
+        // 4 polled again is in order, 2, the fork of 4 and 3 aren't
+        let last = futures::stream::iter([block_id(1), block_id(2), block_id(4), block_id(4), block_id(2), fork.clone(), block_id(5), block_id(3)].map(Ok));
+        let tracker = MasterchainLastBlockTracker::new(last);
+        let mut rx = tracker.receiver();
+
+        let mut seen = vec![];
+        while rx.changed().await.is_ok() {
+            seen.push(rx.borrow_and_update().clone().unwrap());
+        }
+
+        assert_eq!(tracker.gap_count(), 3);
+        assert_eq!(tracker.last(), Some(block_id(5)));
+        assert!(!seen.contains(&fork));
+    }
+
+    #[test]
+    fn is_out_of_order_test() {
+        assert!(!is_out_of_order(&None, &block_id(1)));
+        assert!(!is_out_of_order(&Some(block_id(1)), &block_id(3)));
+        assert!(!is_out_of_order(&Some(block_id(3)), &block_id(3)));
+        assert!(is_out_of_order(&Some(block_id(3)), &block_id(2)));
+        assert!(is_out_of_order(&Some(block_id(3)), &TonNodeBlockIdExt { file_hash: [1; 32], ..block_id(3) }));
+    }
+
+    /// Liteserver with masterchain blocks `first..=9`
+    fn pruned_server(first: i32) -> MockLiteServer {
+        synced_server(first, 9)
+    }
+
+    /// Liteserver with masterchain blocks `first..=last`
+    fn synced_server(first: i32, last: i32) -> MockLiteServer {
+        MockLiteServer::default()
+            .on(move |_: LiteServerGetMasterchainInfo| Ok(LiteServerMasterchainInfo { last: block_id(last), state_root_hash: [0; 32], init: TonNodeZeroStateIdExt { workchain: -1, root_hash: [0; 32], file_hash: [0; 32] } }))
+            .on(move |request: LiteServerLookupBlock| match request.id.seqno {
+                seqno if (first ..= last).contains(&seqno) => Ok(LiteServerBlockHeader { id: block_id(seqno), mode: 0, header_proof: vec![] }),
+                _ => Err(Error::NotFound("block not found".to_owned()))
+            })
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_wait_timeout_test() {
+        let tracker = MasterchainLastBlockTracker::new(futures::stream::pending());
+
+        let started = tokio::time::Instant::now();
+        let response = tracker.wait_last_timeout(Duration::from_millis(50)).await;
+
+        assert!(matches!(response, Err(Error::Timeout)));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(tracker.last(), None);
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_wait_test() {
+        let tracker = MasterchainLastBlockTracker::new(futures::stream::iter([Ok(block_id(5))]));
+        let ended = MasterchainLastBlockTracker::new(futures::stream::empty());
+
+        assert_eq!(tracker.wait_last_timeout(Duration::from_secs(1)).await.unwrap(), block_id(5));
+        assert!(matches!(ended.wait_last().await, Err(Error::Transport(TransportError::ChannelClosed))));
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_stream_test() {
+        let (tx, last) = futures::channel::mpsc::unbounded();
+        let tracker = MasterchainLastBlockTracker::new(last.map(Ok));
+        let mut blocks = tracker.receiver_stream();
+        let mut owned = tracker.into_stream();
+
+        for seqno in 1 ..= 3 {
+            tx.unbounded_send(block_id(seqno)).unwrap();
+
+            assert_eq!(blocks.next().await, Some(block_id(seqno)));
+            assert_eq!(owned.next().await, Some(block_id(seqno)));
+        }
+        drop(tx);
+
+        assert_eq!(blocks.next().await, None);
+        assert_eq!(owned.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn first_block_tracker_waits_for_last_block_test() {
+        let (last_tx, last) = watch::channel(None);
+        let tracker = MasterchainFirstBlockTracker::new(pruned_server(4), last, Duration::from_millis(10));
+        let mut rx = tracker.receiver();
+
+        // a few startup waits time out
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(tracker.first(), None);
+        last_tx.send_replace(Some(block_id(9)));
+
+        tokio::time::timeout(Duration::from_secs(1), rx.changed()).await.unwrap().unwrap();
+        assert_eq!(tracker.first().unwrap().seqno, 4);
+    }
+
+    #[tokio::test]
+    async fn find_first_block_test() {
+        for first in 1 ..= 9 {
+            let header = find_first_block(&pruned_server(first), &block_id(9), 1, 0).await.unwrap();
+
+            assert_eq!(header.id.seqno, first);
+        }
+    }
+
+    #[tokio::test]
+    async fn find_first_block_pruned_during_search_test() {
+        // blocks from 4 for the first three lookups, from 6 afterwards
+        let server = || {
+            let lookups = Arc::new(AtomicUsize::new(0));
+
+            MockLiteServer::default().on(move |request: LiteServerLookupBlock| {
+                let first = if lookups.fetch_add(1, Ordering::SeqCst) < 3 { 4 } else { 6 };
+                match request.id.seqno {
+                    seqno if (first ..= 9).contains(&seqno) => Ok(LiteServerBlockHeader { id: block_id(seqno), mode: 0, header_proof: vec![] }),
+                    _ => Err(Error::NotFound("block not found".to_owned()))
+                }
+            })
+        };
+
+        let unchecked = find_first_block(&server(), &block_id(9), 1, 0).await.unwrap();
+        let checked = find_first_block(&server(), &block_id(9), 1, FIRST_BLOCK_RECHECKS).await.unwrap();
+
+        assert_eq!(unchecked.id.seqno, 4);
+        assert_eq!(checked.id.seqno, 6);
+    }
+
+    #[tokio::test]
+    async fn first_block_tracker_test() {
+        let (_last_tx, last) = watch::channel(Some(block_id(9)));
+        let tracker = MasterchainFirstBlockTracker::new(pruned_server(4), last, Duration::from_millis(10));
+        let mut rx = tracker.receiver();
+
+        rx.changed().await.unwrap();
+
+        assert_eq!(tracker.first().unwrap().seqno, 4);
+    }
+
+    #[tokio::test]
+    async fn first_block_tracker_watchdog_test() {
+        let (_last_tx, last) = watch::channel(Some(block_id(9)));
+        let failing = Arc::new(AtomicBool::new(false));
+        let mock = MockLiteServer::default().on({
+            let failing = failing.clone();
+            move |request: LiteServerLookupBlock| match failing.load(Ordering::SeqCst) {
+                true => Err(Error::Timeout),
+                false if request.id.seqno >= 4 => Ok(LiteServerBlockHeader { id: block_id(request.id.seqno), mode: 0, header_proof: vec![] }),
+                false => Err(Error::NotFound("block not found".to_owned()))
+            }
+        });
+        let fired = Arc::new(AtomicUsize::new(0));
+        let watchdog = Watchdog { stale_after: Duration::from_millis(50), on_stale: Some(Arc::new({
+            let fired = fired.clone();
+            move || { fired.fetch_add(1, Ordering::SeqCst); }
+        })) };
+        let tracker = MasterchainFirstBlockTracker::with_watchdog(mock, last, Duration::from_millis(10), watchdog);
+
+        tracker.receiver().changed().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+
+        failing.store(true, Ordering::SeqCst);
+        let succeeded = tracker.last_success().unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert!(fired.load(Ordering::SeqCst) >= 1);
+        assert_eq!(tracker.last_success(), Some(succeeded));
+        assert_eq!(tracker.current_seqno(), Some(4));
+    }
+
+    async fn next_change(changes: &mut watch::Receiver<Option<FirstBlockChange>>) -> FirstBlockChange {
+        changes.changed().await.unwrap();
+
+        changes.borrow_and_update().clone().unwrap()
+    }
+
+    #[tokio::test]
+    async fn first_block_tracker_hint_test() {
+        // a searched first block is looked up once more to check it wasn't pruned meanwhile
+        for (hint, expected_lookups) in [(4, 1), (2, 1 + 3 + 1)] {
+            let lookups = Arc::new(AtomicUsize::new(0));
+            let server = MockLiteServer::default()
+                .on({
+                    let lookups = lookups.clone();
+
+                    move |request: LiteServerLookupBlock| {
+                        lookups.fetch_add(1, Ordering::SeqCst);
+
+                        match request.id.seqno {
+                            seqno if (4 ..= 9).contains(&seqno) => Ok(LiteServerBlockHeader { id: block_id(seqno), mode: 0, header_proof: vec![] }),
+                            _ => Err(Error::NotFound("block not found".to_owned()))
+                        }
+                    }
+                });
+            let (_last_tx, last) = watch::channel(Some(block_id(9)));
+            let tracker = MasterchainFirstBlockTracker::with_hint(server, last, Duration::from_secs(60), hint);
+            let mut rx = tracker.receiver();
+
+            // the first tick is immediate, the next one is a minute away
+            tokio::time::timeout(Duration::from_secs(1), rx.changed()).await.unwrap().unwrap();
+
+            assert_eq!(tracker.current_seqno(), Some(4));
+            // the hint is the first block, a pruned hint only narrows the search down
+            assert_eq!(lookups.load(Ordering::SeqCst), expected_lookups);
+        }
+    }
+
+    #[tokio::test]
+    async fn last_block_tracker_hint_test() {
+        let behind = MasterchainLastBlockTracker::with_hint(futures::stream::iter([3, 4].map(|seqno| Ok(block_id(seqno)))), 5);
+        let tracker = MasterchainLastBlockTracker::with_hint(futures::stream::iter([3, 5, 7].map(|seqno| Ok(block_id(seqno)))), 5);
+
+        for mut rx in [behind.receiver(), tracker.receiver()] {
+            while rx.changed().await.is_ok() {}
+        }
+
+        assert_eq!(behind.current_seqno(), None);
+        assert_eq!(tracker.current_seqno(), Some(7));
+        assert_eq!(tracker.gap_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn first_block_tracker_reports_changes_test() {
+        let first = Arc::new(AtomicI32::new(4));
+        let server = MockLiteServer::default()
+            .on({
+                let first = first.clone();
+
+                move |request: LiteServerLookupBlock| match request.id.seqno {
+                    seqno if (first.load(Ordering::SeqCst) ..= 9).contains(&seqno) => Ok(LiteServerBlockHeader { id: block_id(seqno), mode: 0, header_proof: vec![] }),
+                    _ => Err(Error::NotFound("block not found".to_owned()))
+                }
+            });
+        let (_last_tx, last) = watch::channel(Some(block_id(9)));
+        let tracker = MasterchainFirstBlockTracker::new(server, last, Duration::from_millis(10));
+        let mut changes = tracker.changes();
+
+        let found = next_change(&mut changes).await;
+        assert_eq!(found, FirstBlockChange { first: block_id(4), previous: None });
+        assert_eq!(found.delta(), 0);
+
+        first.store(7, Ordering::SeqCst);
+        let trimmed = next_change(&mut changes).await;
+        assert_eq!(trimmed.first.seqno, 7);
+        assert_eq!(trimmed.delta(), 3);
+        assert!(trimmed.is_trimmed());
+
+        first.store(8, Ordering::SeqCst);
+        let trimmed = next_change(&mut changes).await;
+        assert_eq!(trimmed.previous, Some(block_id(7)));
+        assert_eq!(trimmed.delta(), 1);
+    }
+
+    #[tokio::test]
+    async fn dropping_tracker_stops_both_tasks_test() {
+        let config = TrackerConfig { last_block_interval: Duration::from_millis(10), first_block_interval: Duration::from_millis(10), ..Default::default() };
+        let tracker = MasterchainTracker::with_service(pruned_server(4), config);
+        let mut first = tracker.first_receiver();
+        let mut last = tracker.last_receiver();
+
+        first.wait_for(Option::is_some).await.unwrap();
+        last.wait_for(Option::is_some).await.unwrap();
+        assert_eq!(first.borrow().as_ref().unwrap().seqno, 4);
+        assert_eq!(last.borrow().as_ref().unwrap().seqno, 9);
+
+        drop(tracker);
+
+        // the senders are dropped once the tasks are cancelled
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while first.changed().await.is_ok() {}
+            while last.changed().await.is_ok() {}
+        }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pinned_tracker_is_consistent_test() {
+        // the backends are at different heights, the first block of one is past the last block of the other
+        let pool = LiteServerPool::new(vec![synced_server(4, 9), synced_server(12, 20)]);
+        let config = TrackerConfig { last_block_interval: Duration::from_millis(10), first_block_interval: Duration::from_millis(10), ..Default::default() };
+
+        for key in 0 .. 4 {
+            let tracker = MasterchainTracker::pinned(&pool, key, config.clone());
+            let mut first = tracker.first_receiver();
+            let mut last = tracker.last_receiver();
+
+            let first = first.wait_for(Option::is_some).await.unwrap().clone().unwrap();
+            let last = last.wait_for(Option::is_some).await.unwrap().clone().unwrap();
+
+            assert!([(4, 9), (12, 20)].contains(&(first.seqno, last.seqno)));
+        }
+    }
+
+    #[tokio::test]
+    async fn trackers_join_shared_task_set_test() {
+        let tasks = TaskTracker::new();
+        let config = TrackerConfig { last_block_interval: Duration::from_millis(10), first_block_interval: Duration::from_millis(10), tasks: Some(tasks.clone()), ..Default::default() };
+        let (_last_tx, last) = watch::channel(Some(block_id(9)));
+
+        let trackers = (
+            MasterchainTracker::with_service(pruned_server(4), config),
+            MasterchainLastBlockTracker::with_tasks(futures::stream::pending(), &tasks),
+            MasterchainFirstBlockTracker::with_tasks(pruned_server(4), last, Duration::from_millis(10), &tasks),
+            KeyBlockTracker::with_tasks(service_fn(lookup), futures::stream::pending(), &tasks)
+        );
+        tasks.close();
+        assert_eq!(tasks.len(), 5);
+
+        drop(trackers);
+
+        tokio::time::timeout(Duration::from_secs(1), tasks.wait()).await.unwrap();
+        assert!(tasks.is_empty());
+    }
+
+    /// Masterchain blocks 1 to 9, every third one is a key block
+    fn headers() -> Vec<LiteServerBlockHeader> {
+        include_str!("../fixtures/key_block_headers.hex")
+            .lines()
+            .map(|line| {
+                let fields: Vec<_> = line.split(' ').collect();
+                let id = TonNodeBlockIdExt {
+                    workchain: -1,
+                    shard: i64::MIN,
+                    seqno: fields[0].parse().unwrap(),
+                    root_hash: hex::decode(fields[1]).unwrap().try_into().unwrap(),
+                    file_hash: hex::decode(fields[2]).unwrap().try_into().unwrap()
+                };
+
+                LiteServerBlockHeader { id, mode: 0, header_proof: hex::decode(fields[3]).unwrap() }
+            })
+            .collect()
+    }
+
+    async fn lookup(request: LiteServerLookupBlock) -> Result<LiteServerBlockHeader, Error> {
+        Ok(headers().into_iter().find(|h| h.id.seqno == request.id.seqno).unwrap())
+    }
+
+    #[tokio::test]
+    async fn follows_key_blocks_test() {
+        let (headers_tx, headers_rx) = mpsc::unbounded();
+        let mock = MockLiteServer::default()
+            .on(|request: LiteServerLookupBlock| Ok(headers()[request.id.seqno as usize - 1].clone()));
+        let tracker = KeyBlockTracker::new(mock, headers_rx);
+        let mut rx = tracker.receiver();
+
+        let mut seen = vec![];
+        for header in headers().into_iter().skip(3) {
+            let key_block = header.id.seqno % 3 == 0;
+            headers_tx.unbounded_send(Ok(header)).unwrap();
+            if key_block || seen.is_empty() {
+                rx.changed().await.unwrap();
+                seen.push(rx.borrow_and_update().as_ref().unwrap().seqno);
+            }
+        }
+
+        // block 4 isn't a key block, so block 3 is looked up before it
+        assert_eq!(seen, vec![3, 6, 9]);
+        assert_eq!(tracker.last().unwrap().seqno, 9);
+    }
+
+    #[tokio::test]
+    async fn skips_forged_headers_test() {
+        let mut forged = headers()[5].clone();
+        forged.id.seqno = 7;
+        let headers = futures::stream::iter([Ok(headers()[2].clone()), Ok(forged)]);
+
+        let tracker = KeyBlockTracker::new(service_fn(lookup), headers);
+        let mut rx = tracker.receiver();
+        // the sender is dropped once the headers end
+        while rx.changed().await.is_ok() {}
+
+        assert_eq!(tracker.last().unwrap().seqno, 3);
+    }
+
+    /// Basechain block of `shard`, the full shard is `0x8000_0000_0000_0000`
+    fn shard_block_id(shard: u64, seqno: i32) -> TonNodeBlockIdExt {
+        TonNodeBlockIdExt { workchain: 0, shard: shard as i64, seqno, root_hash: [0; 32], file_hash: [0; 32] }
+    }
+
+    #[tokio::test]
+    async fn network_tracker_follows_split_and_merge_test() {
+        const FULL: u64 = 0x8000_0000_0000_0000;
+        const LEFT: u64 = 0x4000_0000_0000_0000;
+        const RIGHT: u64 = 0xc000_0000_0000_0000;
+        let lookups = MockLiteServer::default()
+            .on(|request: LiteServerLookupBlock| Ok(LiteServerBlockHeader { id: TonNodeBlockIdExt { workchain: request.id.workchain, shard: request.id.shard, seqno: request.id.seqno, root_hash: [0; 32], file_hash: [0; 32] }, mode: 0, header_proof: vec![] }));
+        let (updates_tx, updates) = mpsc::unbounded();
+        let tracker = NetworkTracker::with_updates(lookups, updates);
+        let mut shards = tracker.shards_receiver();
+
+        updates_tx.unbounded_send(Ok((block_id(1), vec![shard_block_id(FULL, 10)]))).unwrap();
+        updates_tx.unbounded_send(Ok((block_id(2), vec![shard_block_id(FULL, 12)]))).unwrap();
+        // the shard splits
+        updates_tx.unbounded_send(Ok((block_id(3), vec![shard_block_id(LEFT, 13), shard_block_id(RIGHT, 13)]))).unwrap();
+        updates_tx.unbounded_send(Ok((block_id(4), vec![shard_block_id(LEFT, 14), shard_block_id(RIGHT, 13)]))).unwrap();
+        shards.wait_for(|shards| shards.len() == 2 && shards[0].seqno == 14).await.unwrap();
+        // and merges back
+        updates_tx.unbounded_send(Ok((block_id(5), vec![shard_block_id(FULL, 15)]))).unwrap();
+        shards.wait_for(|shards| shards.len() == 1).await.unwrap();
+        assert_eq!(tracker.shards(), vec![shard_block_id(FULL, 15)]);
+        drop(updates_tx);
+
+        let blocks: Vec<_> = tokio::time::timeout(Duration::from_secs(1), tracker.collect()).await.unwrap();
+        let seqnos = |workchain: i32, shard: u64| blocks.iter()
+            .filter(|id| id.workchain == workchain && id.shard == shard as i64)
+            .map(|id| id.seqno)
+            .collect::<Vec<_>>();
+
+        assert_eq!(seqnos(-1, i64::MIN as u64), vec![1, 2, 3, 4, 5]);
+        // block 11 is looked up, the shard is followed again from the merge
+        assert_eq!(seqnos(0, FULL), vec![10, 11, 12, 15]);
+        assert_eq!(seqnos(0, LEFT), vec![13, 14]);
+        assert_eq!(seqnos(0, RIGHT), vec![13]);
+        assert_eq!(blocks.len(), 12);
+    }
+}
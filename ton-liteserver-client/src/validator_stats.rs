@@ -0,0 +1,116 @@
+use anyhow::{bail, ensure};
+
+use crate::cell::{key_to_int256, read_single_root, CellSlice};
+use crate::config_params::mc_state_extra;
+use crate::tl::{Int256, LiteServerValidatorStats};
+
+/// Blocks created by a validator, keyed by its public key in `block_create_stats` of the masterchain state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatorStats {
+    pub public_key: Int256,
+    pub mc_blocks: Counters,
+    pub shard_blocks: Counters,
+}
+
+/// Block counters with exponentially decaying averages over 2048 and 65536 seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    pub last_updated: u32,
+    pub total: u64,
+    pub cnt2048: u64,
+    pub cnt65536: u64,
+}
+
+impl LiteServerValidatorStats {
+    /// Entries proven by `data_proof` in public key order, see [`parse_creator_stats`]
+    pub fn creator_stats(&self) -> anyhow::Result<Vec<CreatorStats>> {
+        parse_creator_stats(&self.data_proof)
+    }
+}
+
+/// Decodes the `block_create_stats` entries of a masterchain state proof. The proof of a page
+/// carries the entries of the page and may carry a few neighbours, the rest of the dictionary is pruned
+pub fn parse_creator_stats(data_proof: &[u8]) -> anyhow::Result<Vec<CreatorStats>> {
+    let root = read_single_root(data_proof)?;
+    let mut slice = mc_state_extra(root.proof_root()?)?;
+    if slice.load_bit()? {
+        // shard_hashes
+        slice.load_reference()?;
+    }
+    // config
+    slice.skip(256)?;
+    slice.load_reference()?;
+
+    let mut slice = slice.load_reference()?.parser();
+    let flags = slice.load_uint(16)?;
+    // validator_info
+    slice.skip(65)?;
+    // prev_blocks and its KeyMaxLt extra
+    if slice.load_bit()? {
+        slice.load_reference()?;
+    }
+    slice.skip(65)?;
+    // after_key_block
+    slice.skip(1)?;
+    if slice.load_bit()? {
+        // last_key_block
+        slice.skip(64 + 32 + 256 + 256)?;
+    }
+    if flags & 1 == 0 {
+        return Ok(Vec::new());
+    }
+
+    let augmented = match slice.load_uint(8)? {
+        0x17 => false,
+        0x34 => true,
+        tag => bail!("unexpected BlockCreateStats tag {:#x}", tag)
+    };
+
+    let mut stats = Vec::new();
+    for (key, mut value) in slice.load_dict(256)? {
+        if augmented {
+            // max last_updated of the subtree
+            value.skip(32)?;
+        }
+        ensure!(value.load_uint(4)? == 4, "not a CreatorStats");
+
+        stats.push(CreatorStats { public_key: key_to_int256(&key)?, mc_blocks: load_counters(&mut value)?, shard_blocks: load_counters(&mut value)? });
+    }
+
+    Ok(stats)
+}
+
+fn load_counters(slice: &mut CellSlice) -> anyhow::Result<Counters> {
+    Ok(Counters {
+        last_updated: slice.load_uint(32)? as u32,
+        total: slice.load_uint(64)?,
+        cnt2048: slice.load_uint(64)?,
+        cnt65536: slice.load_uint(64)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(line: usize) -> Vec<u8> {
+        hex::decode(include_str!("../fixtures/validator_stats.hex").lines().nth(line).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_creator_stats_test() {
+        let stats = parse_creator_stats(&fixture(0)).unwrap();
+
+        assert_eq!(stats.iter().map(|s| s.public_key[0]).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(stats[2].public_key, [3; 32]);
+        assert_eq!(stats[2].mc_blocks, Counters { last_updated: 1718000003, total: 300, cnt2048: 150, cnt65536: 300 });
+        assert_eq!(stats[2].shard_blocks.total, 3000);
+    }
+
+    #[test]
+    fn parse_creator_stats_skips_pruned_test() {
+        let stats = parse_creator_stats(&fixture(1)).unwrap();
+
+        assert_eq!(stats.iter().map(|s| s.public_key[0]).collect::<Vec<_>>(), vec![1, 3, 5]);
+    }
+}
@@ -14,7 +14,7 @@ use futures::future::ready;
 use futures::never::Never;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::watch::{Receiver, Sender};
-use tokio::time::{interval, MissedTickBehavior};
+use tokio::time::{interval, Interval, MissedTickBehavior};
 use tokio_retry::Retry;
 use tokio_retry::strategy::{FibonacciBackoff, jitter};
 use tower::limit::ConcurrencyLimit;
@@ -42,6 +42,24 @@ impl From<&TonBlockIdExt> for ShardId {
 }
 
 type Seqno = i32;
+
+const FIRST_BLOCK_PROBE_WINDOW: Seqno = 32;
+
+/// Settings of the first block discovery of every liteserver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FirstBlockOptions {
+    /// Time between the checks whether the first block is still available, 30s by default
+    pub(crate) poll_interval: Duration,
+    /// How far past the previous first block the search probes first, [`FIRST_BLOCK_PROBE_WINDOW`] by default
+    pub(crate) probe_window: Seqno,
+}
+
+impl Default for FirstBlockOptions {
+    fn default() -> Self {
+        Self { poll_interval: Duration::from_secs(30), probe_window: FIRST_BLOCK_PROBE_WINDOW }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct ShardBounds {
     left: Option<BlocksHeader>,
@@ -240,7 +258,7 @@ impl Routed for CursorClient {
 }
 
 impl CursorClient {
-    pub(crate) fn new(id: String, client: ConcurrencyLimit<SharedService<PeakEwma<Client>>>) -> Self {
+    pub(crate) fn new(id: String, client: ConcurrencyLimit<SharedService<PeakEwma<Client>>>, first_block: FirstBlockOptions) -> Self {
         metrics::describe_counter!("ton_liteserver_last_seqno", "The seqno of the latest block that is available for the liteserver to sync");
         metrics::describe_counter!("ton_liteserver_synced_seqno", "The seqno of the last block with which the liteserver is actually synchronized");
         metrics::describe_counter!("ton_liteserver_first_seqno", "The seqno of the first block that is available for the liteserver to request");
@@ -261,7 +279,7 @@ impl CursorClient {
         };
 
         tokio::spawn(_self.last_block_loop(mtx));
-        let inner = _self.first_block_loop();
+        let inner = _self.first_block_loop(first_block);
         tokio::spawn(async move {
             mc_watcher.changed().await.unwrap();
 
@@ -281,12 +299,12 @@ impl CursorClient {
         discover.discover()
     }
 
-    fn first_block_loop(&self) -> impl Future<Output = Infallible> {
+    fn first_block_loop(&self, options: FirstBlockOptions) -> impl Future<Output = Infallible> {
         let id = self.id.clone();
         let client = self.client.clone();
         let registry = self.registry.clone();
 
-        let discover = FirstBlockDiscover::new(id, client, registry, self.masterchain_info_rx.clone());
+        let discover = FirstBlockDiscover::new(id, client, registry, self.masterchain_info_rx.clone(), options.probe_window, options.poll_interval);
 
         discover.discover()
     }
@@ -369,15 +387,31 @@ async fn check_block_available(client: &mut InnerClient, block_id: TonBlockId) -
 
 #[instrument(skip_all, err, level = "trace")]
 async fn find_first_blocks(client: &mut InnerClient, start: &TonBlockIdExt, lhs: Option<i32>, cur: Option<i32>) -> Result<(BlocksHeader, Vec<BlocksHeader>)> {
-    let length = start.seqno;
-    let mut rhs = length;
-    let mut lhs = lhs.unwrap_or(1);
-    let mut cur = cur.unwrap_or(start.seqno - 200000);
-
     let workchain = start.workchain;
     let shard = start.shard;
 
-    let mut block = check_block_available(client, TonBlockId::new(workchain, shard, cur)).await;
+    let (master, work) = find_first(start.seqno, lhs, cur, |b: &(BlocksHeader, Vec<BlocksHeader>)| b.0.id.seqno, |seqno| {
+        let mut client = client.clone();
+
+        async move { check_block_available(&mut client, TonBlockId::new(workchain, shard, seqno)).await }
+    }).await?;
+
+    tracing::trace!(seqno = master.id.seqno, "first seqno");
+
+    Ok((master, work))
+}
+
+/// Binary search of the first seqno in `lhs..=rhs` for which `check` succeeds, starting with a probe at `cur`
+async fn find_first<T, F, Fut>(rhs: Seqno, lhs: Option<Seqno>, cur: Option<Seqno>, seqno: impl Fn(&T) -> Seqno, mut check: F) -> Result<T>
+    where T: Clone,
+          F: FnMut(Seqno) -> Fut,
+          Fut: Future<Output = Result<T>> {
+    let mut rhs = rhs;
+    let mut lhs = lhs.unwrap_or(1).min(rhs);
+    // the first block may have jumped past the probe and the probe past the last block
+    let mut cur = cur.unwrap_or(rhs - 200000).clamp(lhs, rhs);
+
+    let mut block = check(cur).await;
     let mut success = None;
 
     let mut hops = 0;
@@ -393,7 +427,7 @@ async fn find_first_blocks(client: &mut InnerClient, start: &TonBlockIdExt, lhs:
         cur = (lhs + rhs) / 2;
         if cur == 0 { break; }
 
-        block = check_block_available(client, TonBlockId::new(workchain, shard, cur)).await;
+        block = check(cur).await;
         if block.is_ok() {
             success = Some(block.as_ref().unwrap().clone());
         }
@@ -401,20 +435,18 @@ async fn find_first_blocks(client: &mut InnerClient, start: &TonBlockIdExt, lhs:
         hops += 1;
     }
 
+    tracing::trace!(hops = hops, "first seqno search");
+
     let delta = 4;
-    let (master, work) = match block {
-        Ok(b) => { b },
+    match block {
+        Ok(b) => Ok(b),
         Err(e) => {
             match success {
-                Some(b) if b.0.id.seqno - cur <= delta => { b },
-                _ => { return Err(e) },
+                Some(b) if seqno(&b) - cur <= delta => Ok(b),
+                _ => Err(e),
             }
         }
-    };
-
-    tracing::trace!(hops = hops, seqno = master.id.seqno, "first seqno");
-
-    Ok((master, work))
+    }
 }
 
 async fn wait_for_block_header(block_id: TonBlockIdExt, client: InnerClient) -> Result<BlocksHeader> {
@@ -437,22 +469,26 @@ struct FirstBlockDiscover {
     registry: Arc<Registry>,
     rx: Receiver<Option<BlocksMasterchainInfo>>,
     current: Option<BlocksHeader>,
+    probe_window: Seqno,
+    poll_interval: Duration,
 }
 
 impl FirstBlockDiscover {
-    fn new(id: Cow<'static, str>, client: InnerClient, registry: Arc<Registry>, rx: Receiver<Option<BlocksMasterchainInfo>>) -> Self {
+    /// `probe_window` is how far past the previous first block the search probes first
+    fn new(id: Cow<'static, str>, client: InnerClient, registry: Arc<Registry>, rx: Receiver<Option<BlocksMasterchainInfo>>, probe_window: Seqno, poll_interval: Duration) -> Self {
         Self {
             id,
             client,
             registry,
             rx,
-            current: None
+            current: None,
+            probe_window,
+            poll_interval
         }
     }
 
     async fn discover(mut self) -> Never {
-        let mut timer = interval(Duration::from_secs(30));
-        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut timer = first_block_timer(self.poll_interval);
 
         loop {
             timer.tick().await;
@@ -480,7 +516,7 @@ impl FirstBlockDiscover {
         }
 
         let lhs = self.current.as_ref().map(|n| n.id.seqno + 1);
-        let cur = self.current.as_ref().map(|n| n.id.seqno + 32);
+        let cur = self.current.as_ref().map(|n| n.id.seqno + self.probe_window);
         let (mfb, wfb) = find_first_blocks(&mut self.client, &start, lhs, cur).await?;
 
         metrics::counter!("ton_liteserver_first_seqno", "liteserver_id" => self.id.clone()).absolute(mfb.id.seqno as u64);
@@ -494,6 +530,13 @@ impl FirstBlockDiscover {
     }
 }
 
+fn first_block_timer(poll_interval: Duration) -> Interval {
+    let mut timer = interval(poll_interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    timer
+}
+
 struct LastBlockDiscover {
     id: Cow<'static, str>,
     client: InnerClient,
@@ -613,3 +656,56 @@ impl LastBlockDiscover {
         Ok(Some(info))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use super::*;
+
+    fn available(first: Seqno, last: Seqno) -> impl FnMut(Seqno) -> futures::future::Ready<Result<Seqno>> {
+        move |seqno| ready((first..=last).contains(&seqno).then_some(seqno).ok_or_else(|| anyhow!("block {} not available", seqno)))
+    }
+
+    #[tokio::test]
+    async fn find_first_after_large_gap_test() {
+        let previous = 10;
+
+        let first = find_first(1_000_000, Some(previous + 1), Some(previous + FIRST_BLOCK_PROBE_WINDOW), |s| *s, available(500_000, 1_000_000)).await.unwrap();
+
+        assert_eq!(first, 500_000);
+    }
+
+    #[tokio::test]
+    async fn find_first_probe_past_last_block_test() {
+        let previous = 999_990;
+
+        let first = find_first(1_000_000, Some(previous + 1), Some(previous + FIRST_BLOCK_PROBE_WINDOW), |s| *s, available(999_995, 1_000_000)).await.unwrap();
+
+        assert_eq!(first, 999_995);
+    }
+
+    #[tokio::test]
+    async fn find_first_without_previous_test() {
+        let first = find_first(1_000_000, None, None, |s| *s, available(900_000, 1_000_000)).await.unwrap();
+
+        assert_eq!(first, 900_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn first_block_timer_poll_interval_test() {
+        async fn ticks(poll_interval: Duration) -> usize {
+            let mut timer = first_block_timer(poll_interval);
+            let until = tokio::time::Instant::now() + Duration::from_secs(60);
+
+            let mut ticks = 0;
+            while timer.tick().await <= until {
+                ticks += 1;
+            }
+
+            ticks
+        }
+
+        assert_eq!(ticks(FirstBlockOptions::default().poll_interval).await, 3);
+        assert_eq!(ticks(Duration::from_secs(5)).await, 13);
+    }
+}
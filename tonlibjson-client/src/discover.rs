@@ -20,7 +20,7 @@ use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio::time::{Interval, MissedTickBehavior};
 use tokio_stream::wrappers::IntervalStream;
 use crate::client::Client;
-use crate::cursor_client::CursorClient;
+use crate::cursor_client::{CursorClient, FirstBlockOptions};
 use crate::ton_config::Liteserver;
 
 type DiscoverResult<C> = Result<Change<String, C>, anyhow::Error>;
@@ -141,12 +141,13 @@ impl Stream for ClientDiscover {
 }
 
 pub(crate) struct CursorClientDiscover {
-    discover: PeakEwmaDiscover<ClientDiscover>
+    discover: PeakEwmaDiscover<ClientDiscover>,
+    first_block: FirstBlockOptions
 }
 
 impl CursorClientDiscover {
-    pub(crate) fn new(discover: PeakEwmaDiscover<ClientDiscover>) -> Self {
-        Self { discover }
+    pub(crate) fn new(discover: PeakEwmaDiscover<ClientDiscover>, first_block: FirstBlockOptions) -> Self {
+        Self { discover, first_block }
     }
 }
 
@@ -154,11 +155,12 @@ impl Stream for CursorClientDiscover {
     type Item = DiscoverResult<CursorClient>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let first_block = self.first_block;
         let c = &mut self.discover;
         match Pin::new(&mut *c).poll_next(cx) {
             Poll::Ready(Some(Ok(change))) => match change {
                 Change::Insert(k, client) => Poll::Ready(Some(Ok(
-                    Change::Insert(k.clone(), CursorClientFactory::create(k, client))
+                    Change::Insert(k.clone(), CursorClientFactory::create(k, client, first_block))
                 ))),
                 Change::Remove(k) => Poll::Ready(Some(Ok(Change::Remove(k)))),
             },
@@ -4,7 +4,7 @@ pub mod ton;
 pub mod block;
 mod request;
 mod session;
-mod ton_config;
+pub mod ton_config;
 mod make;
 mod cursor_client;
 mod retry;
@@ -8,7 +8,7 @@ use tower::load::PeakEwma;
 use tracing::debug;
 use crate::block::BlocksGetMasterchainInfo;
 use crate::client::Client;
-use crate::cursor_client::CursorClient;
+use crate::cursor_client::{CursorClient, FirstBlockOptions};
 use crate::shared::SharedLayer;
 use crate::ton_config::TonConfig;
 
@@ -42,14 +42,14 @@ impl Service<TonConfig> for ClientFactory {
 pub(crate) struct CursorClientFactory;
 
 impl CursorClientFactory {
-    pub(crate) fn create(id: String, client: PeakEwma<Client>) -> CursorClient {
+    pub(crate) fn create(id: String, client: PeakEwma<Client>, first_block: FirstBlockOptions) -> CursorClient {
         debug!("make new cursor client");
         let client = SharedLayer
             .layer(client);
         let client = ConcurrencyLimitLayer::new(256)
             .layer(client);
 
-        let client = CursorClient::new(id, client);
+        let client = CursorClient::new(id, client, first_block);
 
         debug!("successfully made new cursor client");
 
@@ -10,6 +10,7 @@ use async_stream::try_stream;
 use futures::future::BoxFuture;
 use itertools::Itertools;
 use serde_json::Value;
+use tokio::time::MissedTickBehavior;
 use tokio_stream::StreamMap;
 use tower::load::PeakEwmaDiscover;
 use tower::retry::budget::Budget;
@@ -25,6 +26,7 @@ use crate::address::InternalAccountAddress;
 use crate::balance::Balance;
 use crate::router::Router;
 use crate::block::{InternalTransactionId, RawTransaction, RawTransactions, BlocksShards, BlocksTransactions, RawSendMessage, AccountAddress, BlocksGetTransactions, BlocksLookupBlock, BlocksGetShards, BlocksGetBlockHeader, RawGetTransactionsV2, RawGetAccountState, GetAccountState, GetShardAccountCell, RawFullAccountState, WithBlock, RawGetAccountStateByTransaction, GetShardAccountCellByTransaction, RawSendMessageReturnHash, BlocksMasterchainInfo, BlocksGetMasterchainInfo, TonBlockIdExt, TonBlockId, BlocksHeader, FullAccountState, BlocksAccountTransactionId, BlocksShortTxId, TvmBoxedStackEntry, SmcRunResult, SmcBoxedMethodId, TvmCell, BlocksGetTransactionsExt, BlocksTransactionsExt};
+use crate::cursor_client::FirstBlockOptions;
 use crate::discover::{ClientDiscover, CursorClientDiscover};
 use crate::error::ErrorService;
 use crate::helper::Side;
@@ -68,7 +70,8 @@ pub struct TonClientBuilder {
     retry_min_per_sec: u32,
     retry_percent: f32,
     retry_first_delay: Duration,
-    retry_max_delay: Duration
+    retry_max_delay: Duration,
+    first_block: FirstBlockOptions
 }
 
 impl Default for TonClientBuilder {
@@ -83,7 +86,8 @@ impl Default for TonClientBuilder {
             retry_min_per_sec: 10,
             retry_percent: 0.1,
             retry_first_delay: Duration::from_millis(128),
-            retry_max_delay: Duration::from_millis(4096)
+            retry_max_delay: Duration::from_millis(4096),
+            first_block: FirstBlockOptions::default()
         }
     }
 }
@@ -157,6 +161,21 @@ impl TonClientBuilder {
         self
     }
 
+    /// How often every liteserver is checked for a moved first block, 30s by default
+    pub fn set_first_block_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.first_block.poll_interval = poll_interval;
+
+        self
+    }
+
+    /// How many seqnos past the previous first block the search for a moved one probes first, 32 by default.
+    /// A node that trims history fast needs fewer search steps with a wider window
+    pub fn set_first_block_probe_window(mut self, probe_window: i32) -> Self {
+        self.first_block.probe_window = probe_window;
+
+        self
+    }
+
     pub async fn build(self) -> anyhow::Result<TonClient> {
         let client_discover = match self.config_source {
             ConfigSource::FromFile { path } => { ClientDiscover::from_path(path).await? }
@@ -170,7 +189,7 @@ impl TonClientBuilder {
             tower::load::CompleteOnResponse::default(),
         );
 
-        let cursor_client_discover = CursorClientDiscover::new(ewma_discover);
+        let cursor_client_discover = CursorClientDiscover::new(ewma_discover, self.first_block);
 
         let router = Router::new(cursor_client_discover);
         let client = Balance::new(router);
@@ -215,6 +234,50 @@ impl TonClient {
             .await
     }
 
+    /// Every masterchain block starting from the current last one. Blocks that appeared between two polls
+    /// are looked up by seqno, so the stream doesn't skip any. A failed poll is logged and repeated on the
+    /// next tick, the stream itself never fails
+    pub fn get_masterchain_block_stream(&self, interval: Duration) -> impl Stream<Item=anyhow::Result<TonBlockIdExt>> + 'static {
+        let this = self.clone();
+
+        async_stream::stream! {
+            let mut last: Option<TonBlockIdExt> = None;
+
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            'poll: loop {
+                ticker.tick().await;
+
+                let current = match this.get_masterchain_info().await {
+                    Ok(info) => info.last,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "masterchain info poll failed, retrying on the next tick");
+
+                        continue
+                    }
+                };
+                let next = last.as_ref().map_or(current.seqno, |last| last.seqno + 1);
+                for seqno in next .. current.seqno {
+                    match this.look_up_block_by_seqno(current.workchain, current.shard, seqno).await {
+                        Ok(block) => {
+                            last = Some(block.clone());
+                            yield Ok::<_, anyhow::Error>(block);
+                        },
+                        Err(e) => {
+                            tracing::warn!(error = %e, seqno, "masterchain block lookup failed, retrying on the next tick");
+
+                            continue 'poll
+                        }
+                    }
+                }
+                if next <= current.seqno {
+                    last = Some(current.clone());
+                    yield Ok::<_, anyhow::Error>(current);
+                }
+            }
+        }
+    }
+
     #[instrument(skip_all, err)]
     pub async fn look_up_block_by_seqno(
         &self,